@@ -0,0 +1,55 @@
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::asset::AssetMetaCheck;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::text::FontLoader;
+use bevy::window::ExitCondition;
+use bevy_renet::netcode::NetcodeServerPlugin;
+use bevy_renet::RenetServerPlugin;
+use marabunta::core::{DedicatedServer, GamePlugin, USAGE};
+use std::time::Duration;
+
+fn main() {
+    let dedicated = match DedicatedServer::from_args(std::env::args().skip(1)) {
+        Ok(dedicated) => dedicated,
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            std::process::exit(1);
+        }
+    };
+
+    // Run the simulation without window, rendering nor audio
+    App::new()
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1. / 60.,
+            ))),
+        )
+        .add_plugins((
+            LogPlugin::default(),
+            TransformPlugin,
+            HierarchyPlugin,
+            InputPlugin,
+            WindowPlugin {
+                primary_window: None,
+                exit_condition: ExitCondition::DontExit,
+                close_when_requested: false,
+            },
+            AssetPlugin {
+                meta_check: AssetMetaCheck::Never,
+                ..default()
+            },
+            ImagePlugin::default(),
+            StatesPlugin,
+        ))
+        // Assets the game systems reference, the map is never drawn
+        .init_asset::<Font>()
+        .init_asset_loader::<FontLoader>()
+        .init_asset::<TextureAtlasLayout>()
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        .insert_resource(dedicated)
+        .add_plugins((RenetServerPlugin, NetcodeServerPlugin, GamePlugin))
+        .run();
+}
@@ -0,0 +1,289 @@
+use crate::core::ants::components::{Action, AntCmp};
+use crate::core::assets::WorldAssets;
+use crate::core::audio::{AudioCategory, PlayAudioEv};
+use crate::core::camera::{center_camera, MainCamera};
+use crate::core::constants::*;
+use crate::core::locale::Locale;
+use crate::core::map::map::Map;
+use crate::core::map::systems::MapCmp;
+use crate::core::map::ui::utils::add_text;
+use crate::core::network::{ServerMessage, ServerSendMessage};
+use crate::core::player::Players;
+use crate::core::spectator::Spectator;
+use crate::utils::NameFromEnum;
+use bevy::prelude::*;
+use bevy::utils::hashbrown::{HashMap, HashSet};
+use bevy_renet::renet::{ClientId, RenetServer};
+use serde::{Deserialize, Serialize};
+
+/// Important events of the match the player is notified about
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AlertKind {
+    QueenAttacked,
+    EggDestroyed,
+    MonstersEmerged,
+    ExpeditionReturned,
+    LeafDepleted,
+    EnemySighted,
+}
+
+impl AlertKind {
+    pub fn image(&self) -> &'static str {
+        match self {
+            AlertKind::QueenAttacked => "attack",
+            AlertKind::EggDestroyed => "egg",
+            AlertKind::MonstersEmerged => "hole1",
+            AlertKind::ExpeditionReturned => "wandering",
+            AlertKind::LeafDepleted => "leaf1",
+            AlertKind::EnemySighted => "battle",
+        }
+    }
+
+    /// Id of the message in the string tables
+    pub fn message_id(&self) -> String {
+        format!("alert.{}", self.to_snake())
+    }
+
+    /// Every kind has its own sound (a different audio or playback rate)
+    pub fn sound(&self) -> PlayAudioEv {
+        let (name, rate) = match self {
+            AlertKind::QueenAttacked => ("warning", 1.),
+            AlertKind::EggDestroyed => ("error", 1.),
+            AlertKind::MonstersEmerged => ("warning", 1.5),
+            AlertKind::ExpeditionReturned => ("message", 1.),
+            AlertKind::LeafDepleted => ("button", 0.7),
+            AlertKind::EnemySighted => ("message", 0.7),
+        };
+
+        PlayAudioEv {
+            volume: 0.5,
+            rate,
+            ..PlayAudioEv::new(name)
+        }
+        .with_category(AudioCategory::Alerts)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub team: ClientId, // Colony that is notified
+    pub pos: Vec2,      // Where the event happened
+}
+
+#[derive(Event)]
+pub struct AlertEv(pub Alert);
+
+impl AlertEv {
+    pub fn new(kind: AlertKind, team: ClientId, pos: Vec2) -> Self {
+        Self(Alert { kind, team, pos })
+    }
+}
+
+/// Alerts received during the match
+#[derive(Resource, Default)]
+pub struct Alerts {
+    /// Alerts with the time they arrived
+    pub feed: Vec<(f32, Alert)>,
+
+    /// Last time every kind of alert was raised per colony (to not flood the feed)
+    pub last: HashMap<(ClientId, AlertKind), f32>,
+
+    /// Teams the player has already seen
+    pub sighted: HashSet<ClientId>,
+}
+
+impl Alerts {
+    /// Alerts still shown in the feed, the newest first
+    pub fn recent(&self, now: f32) -> Vec<&Alert> {
+        self.feed
+            .iter()
+            .rev()
+            .take_while(|(t, _)| now - t < ALERT_DURATION)
+            .take(ALERT_LINES)
+            .map(|(_, a)| a)
+            .collect()
+    }
+}
+
+#[derive(Component)]
+pub struct AlertLineCmp(pub usize);
+
+#[derive(Component)]
+pub struct AlertIconCmp(pub usize);
+
+#[derive(Component)]
+pub struct AlertTextCmp(pub usize);
+
+/// Move the camera to the location of the clicked alert
+fn on_click_alert(
+    trigger: Trigger<Pointer<Click>>,
+    line_q: Query<&AlertLineCmp>,
+    mut camera_q: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+    alerts: Res<Alerts>,
+    time: Res<Time<Real>>,
+) {
+    let AlertLineCmp(i) = line_q.get(trigger.entity()).unwrap();
+    if let Some(alert) = alerts.recent(time.elapsed_secs()).get(*i) {
+        let (mut camera_t, projection) = camera_q.single_mut();
+        center_camera(&mut camera_t, projection, alert.pos);
+    }
+}
+
+pub fn spawn_alerts(mut commands: Commands, assets: Local<WorldAssets>, window: Single<&Window>) {
+    commands
+        .spawn((
+            Node {
+                top: Val::Percent(15.),
+                right: Val::Percent(6.),
+                width: Val::Percent(22.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            PickingBehavior::IGNORE,
+            MapCmp,
+        ))
+        .with_children(|parent| {
+            for i in 0..ALERT_LINES {
+                parent
+                    .spawn((
+                        Node {
+                            width: Val::Percent(100.),
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Percent(1.)),
+                            margin: UiRect::bottom(Val::Percent(2.)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba_u8(88, 57, 39, 200)),
+                        BorderRadius::all(Val::Px(10.)),
+                        Visibility::Hidden,
+                        AlertLineCmp(i),
+                    ))
+                    .observe(on_click_alert)
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Node {
+                                width: Val::Percent(12.),
+                                aspect_ratio: Some(1.),
+                                margin: UiRect::right(Val::Percent(3.)),
+                                ..default()
+                            },
+                            ImageNode::default(),
+                            AlertIconCmp(i),
+                        ));
+
+                        parent.spawn((
+                            add_text("", "medium", LABEL_TEXT_SIZE, &assets, &window),
+                            AlertTextCmp(i),
+                        ));
+                    });
+            }
+        });
+}
+
+/// Raise an alert the first time the units of another team are seen
+pub fn detect_enemies(
+    ant_q: Query<(&Transform, &AntCmp)>,
+    mut alerts: ResMut<Alerts>,
+    mut alert_ev: EventWriter<AlertEv>,
+    players: Res<Players>,
+    map: Res<Map>,
+    spectator: Option<Res<Spectator>>,
+) {
+    // Observers have no colony to warn
+    if spectator.is_some() {
+        return;
+    }
+
+    let player = players.main();
+    for (ant_t, ant) in &ant_q {
+        if ant.health > 0.
+            && ant.action != Action::DoNothing
+            && !alerts.sighted.contains(&ant.team)
+            && !players.are_allies(ant.team, player.id)
+        {
+            if let Some(tile) = map.get_tile_from_coord(&ant_t.translation) {
+                if player.visible_tiles.contains(&(tile.x, tile.y)) {
+                    alerts.sighted.insert(ant.team);
+                    alert_ev.send(AlertEv::new(
+                        AlertKind::EnemySighted,
+                        player.id,
+                        ant_t.translation.truncate(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+pub fn alert_event(
+    mut alert_ev: EventReader<AlertEv>,
+    mut alerts: ResMut<Alerts>,
+    players: Res<Players>,
+    server: Option<Res<RenetServer>>,
+    mut server_send_message: EventWriter<ServerSendMessage>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    time: Res<Time<Real>>,
+) {
+    let now = time.elapsed_secs();
+
+    for AlertEv(alert) in alert_ev.read() {
+        let key = (alert.team, alert.kind);
+        if alerts
+            .last
+            .get(&key)
+            .is_some_and(|t| now - t < ALERT_COOLDOWN)
+        {
+            continue;
+        }
+
+        alerts.last.insert(key, now);
+
+        if alert.team == players.main_id() {
+            play_audio_ev.send(alert.kind.sound());
+            alerts.feed.push((now, alert.clone()));
+        } else if server.is_some() {
+            // The host simulates the colonies of the clients, so it forwards their alerts
+            let player = players.get(alert.team);
+            if player.id == alert.team && player.id != 0 && player.is_human() && !player.is_ai() {
+                server_send_message.send(ServerSendMessage {
+                    message: ServerMessage::Alert(alert.clone()),
+                    client: Some(alert.team),
+                });
+            }
+        }
+    }
+}
+
+pub fn update_alerts(
+    mut line_q: Query<(&mut Visibility, &AlertLineCmp)>,
+    mut icon_q: Query<(&mut ImageNode, &AlertIconCmp)>,
+    mut text_q: Query<(&mut Text, &AlertTextCmp)>,
+    alerts: Res<Alerts>,
+    locale: Res<Locale>,
+    time: Res<Time<Real>>,
+    assets: Local<WorldAssets>,
+) {
+    let recent = alerts.recent(time.elapsed_secs());
+
+    for (mut line_v, AlertLineCmp(i)) in &mut line_q {
+        *line_v = if *i < recent.len() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    for (mut node, AlertIconCmp(i)) in &mut icon_q {
+        if let Some(alert) = recent.get(*i) {
+            node.image = assets.image(alert.kind.image());
+        }
+    }
+
+    for (mut text, AlertTextCmp(i)) in &mut text_q {
+        if let Some(alert) = recent.get(*i) {
+            text.0 = locale.get(&alert.kind.message_id());
+        }
+    }
+}
@@ -1,11 +1,15 @@
-use crate::core::constants::{DEFAULT_WALK_SPEED, TERMITE_TEAM, WASP_TEAM};
+use crate::core::constants::{
+    DEFAULT_WALK_SPEED, RANK_DAMAGE_FACTOR, RANK_EXPERIENCE, RANK_HEALTH_FACTOR, RANK_SPEED_FACTOR,
+    TERMITE_TEAM, WASP_TEAM,
+};
 use crate::core::map::loc::Loc;
 use crate::core::map::tile::Tile;
-use crate::core::menu::settings::AntColor;
+use crate::core::menu::settings::{AntColor, Palette};
 use crate::core::player::Player;
 use crate::core::resources::Resources;
 use crate::core::traits::Trait;
 use crate::utils::NameFromEnum;
+use bevy::color::palettes::basic::LIME;
 use bevy::prelude::*;
 use bevy_renet::renet::ClientId;
 use rand::{rng, Rng};
@@ -19,6 +23,9 @@ pub struct AntHealthWrapperCmp;
 #[derive(Component)]
 pub struct AntHealthCmp;
 
+#[derive(Component)]
+pub struct AntRankCmp(pub usize);
+
 #[derive(Component)]
 pub struct SelectedCmp;
 
@@ -37,6 +44,7 @@ pub struct NutrientCarryCmp;
 #[derive(Component)]
 pub struct TeamCmp(pub u64);
 
+/// Entities simulated by this machine (on the host, every entity)
 #[derive(Component)]
 pub struct Owned;
 
@@ -58,6 +66,27 @@ pub enum Behavior {
     Wander,
 }
 
+impl Behavior {
+    /// Entity the behavior is directed at (if any)
+    pub fn entity_mut(&mut self) -> Option<&mut Entity> {
+        match self {
+            Behavior::Harvest(e)
+            | Behavior::HarvestCorpse(e)
+            | Behavior::Heal(e)
+            | Behavior::ProtectAnt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(EnumIter, Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DamageType {
+    Bite,
+    Sting,
+    Acid,
+    Crush,
+}
+
 #[derive(EnumIter, Clone, Debug, Eq, PartialEq)]
 pub enum Animation {
     Attack,
@@ -77,6 +106,7 @@ pub enum Ant {
     Warrior,
     Alate,
     Mastodon,
+    Spitter,
     BlackScorpion,
     YellowScorpion,
     BlackTermite,
@@ -97,7 +127,8 @@ impl Ant {
             | Ant::Soldier
             | Ant::Warrior
             | Ant::Alate
-            | Ant::Mastodon => true,
+            | Ant::Mastodon
+            | Ant::Spitter => true,
             _ => false,
         }
     }
@@ -114,13 +145,8 @@ impl Ant {
         if *self == Ant::Mastodon || !self.is_ant() {
             Box::new(std::iter::once(AntCmp::base(self)))
         } else {
-            Box::new(
-                [
-                    AntCmp::base(self).with_color(&AntColor::Black),
-                    AntCmp::base(self).with_color(&AntColor::Red),
-                ]
-                .into_iter(),
-            )
+            let kind = self.clone();
+            Box::new(AntColor::iter().map(move |c| AntCmp::base(&kind).with_color(&c)))
         }
     }
 
@@ -179,6 +205,51 @@ impl Ant {
     pub fn can_fly(&self) -> bool {
         self.all_animations().contains(&Animation::Fly)
     }
+
+    /// Type of damage the ant deals
+    pub fn damage_type(&self) -> DamageType {
+        match self {
+            Ant::Mastodon | Ant::WhiteTermite | Ant::WhiteWingedTermite => DamageType::Crush,
+            Ant::Spitter => DamageType::Acid,
+            Ant::BlackScorpion | Ant::YellowScorpion | Ant::Wasp => DamageType::Sting,
+            _ => DamageType::Bite,
+        }
+    }
+
+    /// Factor of the damage of the given type the ant receives (armor)
+    pub fn resistance(&self, damage: &DamageType) -> f32 {
+        match (self, damage) {
+            (Ant::Queen, DamageType::Bite) => 0.8,
+            (Ant::Soldier, DamageType::Bite) => 0.8,
+            (Ant::Warrior, DamageType::Bite) => 0.75,
+            (Ant::Warrior, DamageType::Crush) => 0.9,
+            (Ant::Alate, DamageType::Sting) => 1.5,
+            (Ant::Alate, DamageType::Acid) => 1.25,
+            (Ant::Mastodon, DamageType::Bite) => 0.5,
+            (Ant::Mastodon, DamageType::Crush) => 0.8,
+            (Ant::Spitter, DamageType::Bite) => 1.1,
+            (Ant::Spitter, DamageType::Acid) => 0.5,
+            (Ant::BlackScorpion | Ant::YellowScorpion, DamageType::Bite) => 0.7,
+            (Ant::BlackScorpion | Ant::YellowScorpion, DamageType::Acid) => 1.25,
+            (a, DamageType::Acid) if a.is_termite() => 1.5,
+            (Ant::Wasp, DamageType::Sting) => 0.5,
+            _ => 1.,
+        }
+    }
+
+    /// Seconds the ant lives before dying of old age. None if it never ages
+    pub fn lifespan(&self) -> Option<f32> {
+        match self {
+            Ant::Worker => Some(600.),
+            Ant::Excavator => Some(480.),
+            Ant::Soldier => Some(720.),
+            Ant::Warrior => Some(840.),
+            Ant::Alate => Some(360.),
+            Ant::Mastodon => Some(900.),
+            Ant::Spitter => Some(540.),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Component)]
@@ -207,12 +278,22 @@ pub enum Action {
     Walk(Loc),            // Location to walk to
 }
 
-#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+impl Action {
+    /// Entity the action is directed at (if any)
+    pub fn entity_mut(&mut self) -> Option<&mut Entity> {
+        match self {
+            Action::Attack(e) | Action::TargetedWalk(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AntCmp {
     /// Ant type
     pub kind: Ant,
 
-    /// Key used to create this ant
+    /// Default key used to create this ant (can be rebound in the keymap)
     pub key: Option<KeyCode>,
 
     /// Team the ant corresponds to
@@ -243,6 +324,12 @@ pub struct AntCmp {
     /// Damage the ant does
     pub damage: f32,
 
+    /// Distance in pixels from which the ant attacks. Zero for melee
+    pub range: f32,
+
+    /// Radius in pixels of the splash damage of the ant's projectiles
+    pub splash: f32,
+
     /// Time to hatch from an egg
     pub hatch_time: f32,
 
@@ -260,6 +347,24 @@ pub struct AntCmp {
 
     /// Current action performed by the ant
     pub action: Action,
+
+    /// Speed cap of the formation the ant is walking in
+    pub formation_speed: Option<f32>,
+
+    /// Offset of the ant's slot in the formation from the ant it protects
+    pub formation_offset: Option<Vec2>,
+
+    /// Experience gained from dealing damage and killing enemies
+    pub experience: f32,
+
+    /// Number of enemies killed
+    pub kills: u32,
+
+    /// Seconds the ant has been alive
+    pub age: f32,
+
+    /// Whether the colony can't pay the ant's upkeep
+    pub starving: bool,
 }
 
 impl Default for AntCmp {
@@ -276,12 +381,20 @@ impl Default for AntCmp {
             max_health: 0.,
             speed: DEFAULT_WALK_SPEED,
             damage: 0.,
+            range: 0.,
+            splash: 0.,
             hatch_time: 0.,
             carry: Resources::default(),
             max_carry: Resources::new(1., 1.),
             behavior: Behavior::Attack,
             command: None,
             action: Action::Idle,
+            formation_speed: None,
+            formation_offset: None,
+            experience: 0.,
+            kills: 0,
+            age: 0.,
+            starving: false,
         }
     }
 }
@@ -465,6 +578,25 @@ impl AntCmp {
                 action: Action::Idle,
                 ..default()
             },
+            Ant::Spitter => Self {
+                kind: Ant::Spitter,
+                key: Some(KeyCode::KeyG),
+                team: player.id,
+                color: Some(player.color.clone()),
+                scale: 0.035,
+                z_score: 0.4,
+                price: Resources::new(90., 20.),
+                health: 30.,
+                max_health: 30.,
+                speed: DEFAULT_WALK_SPEED,
+                damage: 7.,
+                range: 40.,
+                splash: 8.,
+                hatch_time: 16.,
+                behavior: Behavior::Attack,
+                action: Action::Idle,
+                ..default()
+            },
             Ant::BlackScorpion => Self {
                 kind: Ant::BlackScorpion,
                 team: rng().random_range(100..1000),
@@ -533,6 +665,7 @@ impl AntCmp {
                 max_health: 50.,
                 speed: DEFAULT_WALK_SPEED,
                 damage: 15.,
+                range: 25.,
                 behavior: Behavior::Attack,
                 action: Action::Idle,
                 ..default()
@@ -585,14 +718,33 @@ impl AntCmp {
         self
     }
 
+    /// Color of the health bar (the colony's color, or lime for monsters)
+    pub fn health_color(&self, palette: &Palette) -> Color {
+        self.color
+            .map_or(Color::from(LIME), |c| c.to_color(palette))
+    }
+
+    /// Color of the ring around the ant when selected
+    pub fn ring_color(&self, palette: &Palette) -> Color {
+        self.color
+            .map_or(Color::BLACK, |c| c.to_color(palette))
+            .with_alpha(0.8)
+    }
+
     pub fn folder(&self) -> String {
-        if self.kind.colors().count() > 1 {
+        // Spitters share the sprites of the soldiers
+        let kind = match self.kind {
+            Ant::Spitter => Ant::Soldier,
+            ref k => k.clone(),
+        };
+
+        if kind.colors().count() > 1 {
             if let Some(color) = &self.color {
-                return format!("{}_{}", color.to_snake(), self.kind.to_snake());
+                return format!("{}_{}", color.to_snake(), kind.to_snake());
             }
         }
 
-        self.kind.to_snake()
+        kind.to_snake()
     }
 
     pub fn atlas(&self, animation: &Animation) -> String {
@@ -604,15 +756,16 @@ impl AntCmp {
             Ant::Queen => Vec2::new(307., 525.),
             Ant::Worker => Vec2::new(307., 438.),
             Ant::Excavator => Vec2::new(307., 474.),
-            Ant::Soldier => match self.color {
+            // Colors without sprites of their own are swapped from the red ones
+            Ant::Soldier | Ant::Spitter => match self.color {
                 Some(AntColor::Black) => Vec2::new(367., 508.),
-                Some(AntColor::Red) => Vec2::new(361., 510.),
-                _ => unreachable!(),
+                Some(_) => Vec2::new(361., 510.),
+                None => unreachable!(),
             },
             Ant::Warrior => match self.color {
                 Some(AntColor::Black) => Vec2::new(466., 623.),
-                Some(AntColor::Red) => Vec2::new(472., 560.),
-                _ => unreachable!(),
+                Some(_) => Vec2::new(472., 560.),
+                None => unreachable!(),
             },
             Ant::Alate => Vec2::new(510., 512.),
             Ant::Mastodon => Vec2::new(513., 577.),
@@ -649,57 +802,64 @@ impl AntCmp {
         }
     }
 
-    pub fn description(&self) -> &'static str {
-        match self.kind {
-            Ant::Queen => {
-                "\
-                The queen is the heart of the colony. She is responsible for \
-                laying eggs and keeping the colony alive. If the queen dies, \
-                you lose the game!"
-            }
-            Ant::Worker => {
-                "\
-                The worker is the most common ant in the colony. \
-                They are responsible for gathering resources."
-            }
-            Ant::Excavator => {
-                "\
-                The excavator ants expands the colonies territory digging \
-                new tunnels. They move fast, but are weak in combat."
-            }
-            Ant::Soldier => {
-                "\
-                The soldiers form the colony's base defense. Their main \
-                task is to protect the workers and queen from any foe."
-            }
-            Ant::Warrior => {
-                "\
-                The warrior ants are the elite fighting units in the colony. \
-                They are stronger and faster than the soldier ants."
-            }
-            Ant::Alate => {
-                "\
-                The alates, also known as flying ants, are the male individuals \
-                in the colony. Alates are incredibly fast ants when attacking. They have \
-                high damage but low health, making them a good choice in the offence but \
-                poor in defense."
-            }
-            Ant::Mastodon => {
-                "\
-                Mastodons are big ants known for their powerful jaws. They have low damage \
-                but are very healthy, making them ideal units to defend narrow tunnels and \
-                slow down the enemy's advance"
-            }
-            _ => unreachable!(),
-        }
+    /// Id of the description in the string tables
+    pub fn description_id(&self) -> String {
+        format!("ant.{}.description", self.kind.to_snake())
     }
 
     pub fn get_behavior(&self) -> &Behavior {
         self.command.as_ref().unwrap_or(&self.behavior)
     }
+
+    /// Replace the entities the ant refers to (used to map them between host and client)
+    pub fn map_entities(&mut self, f: impl Fn(Entity) -> Entity) {
+        for e in self
+            .command
+            .as_mut()
+            .and_then(|c| c.entity_mut())
+            .into_iter()
+            .chain(self.behavior.entity_mut())
+            .chain(self.action.entity_mut())
+        {
+            *e = f(*e);
+        }
+    }
+
+    /// Whether the ant attacks with projectiles
+    pub fn is_ranged(&self) -> bool {
+        self.range > 0.
+    }
+
+    /// Whether the ant can gain experience
+    pub fn is_veteran_kind(&self) -> bool {
+        self.kind.is_ant() && self.kind != Ant::Queen
+    }
+
+    /// Veterancy rank of the ant (0 for recruits)
+    pub fn rank(&self) -> usize {
+        RANK_EXPERIENCE
+            .iter()
+            .filter(|xp| self.experience >= **xp)
+            .count()
+    }
+
+    /// Add experience to the ant, promoting it when it reaches a new rank
+    pub fn add_experience(&mut self, experience: f32) {
+        let rank = self.rank();
+        self.experience += experience;
+        (rank..self.rank()).for_each(|_| self.promote());
+    }
+
+    /// Apply the bonuses of one rank
+    pub fn promote(&mut self) {
+        self.max_health *= RANK_HEALTH_FACTOR;
+        self.health *= RANK_HEALTH_FACTOR;
+        self.damage *= RANK_DAMAGE_FACTOR;
+        self.speed *= RANK_SPEED_FACTOR;
+    }
 }
 
-#[derive(Component, Clone, Serialize, Deserialize)]
+#[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Egg {
     /// Team the egg corresponds to
     pub team: ClientId,
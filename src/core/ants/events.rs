@@ -1,17 +1,22 @@
+use crate::core::alerts::{AlertEv, AlertKind};
 use crate::core::ants::components::*;
 use crate::core::ants::selection::{select_ant_on_click, select_egg_on_click};
 use crate::core::assets::WorldAssets;
 use crate::core::audio::PlayAudioEv;
 use crate::core::constants::*;
+use crate::core::dedicated::DedicatedServer;
 use crate::core::game_settings::{GameMode, GameSettings};
 use crate::core::map::systems::MapCmp;
 use crate::core::menu::settings::FogOfWar;
-use crate::core::multiplayer::EntityMap;
+use crate::core::multiplayer::{EntityMap, SnapshotBuffer};
+use crate::core::network::{ClientMessage, ClientSendMessage};
 use crate::core::player::Players;
+use crate::core::spectator::Spectator;
 use crate::core::states::GameState;
+use crate::core::stats::GameStats;
 use crate::core::traits::Trait;
 use crate::core::utils::{NoRotationChildCmp, NoRotationParentCmp};
-use bevy::color::palettes::basic::{BLACK, LIME};
+use bevy::color::palettes::css::GOLD;
 use bevy::color::Color;
 use bevy::math::{Vec2, Vec3};
 use bevy::prelude::*;
@@ -50,10 +55,13 @@ pub struct DamageAntEv {
 
 pub fn queue_ant_event(
     mut queue_ant_ev: EventReader<QueueAntEv>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
     mut play_audio_ev: EventWriter<PlayAudioEv>,
     mut players: ResMut<Players>,
+    mut stats: ResMut<GameStats>,
 ) {
     for ev in queue_ant_ev.read() {
+        let main_id = players.main_id();
         let player = players.get_mut(ev.id);
         let ant_c = AntCmp::base(&ev.ant);
 
@@ -67,11 +75,17 @@ pub fn queue_ant_event(
 
             if player.resources >= price && player.queue.len() < MAX_QUEUE_LENGTH {
                 player.resources -= &price;
-                player.queue.push_back(ant_c.kind);
-                if player.is_human() {
+                player.queue.push_back(ant_c.kind.clone());
+                stats.colony(player.id).buy(&ant_c.kind, &price);
+                if player.id == main_id {
                     play_audio_ev.send(PlayAudioEv::new("button"));
+
+                    // The queue is predicted locally, but the host decides
+                    client_send_message.send(ClientSendMessage {
+                        message: ClientMessage::QueueAnt(ev.ant.clone()),
+                    });
                 }
-            } else if player.is_human() {
+            } else if player.id == main_id {
                 play_audio_ev.send(PlayAudioEv::new("error"));
             }
         }
@@ -135,7 +149,7 @@ pub fn spawn_egg_event(
                 parent
                     .spawn((
                         Sprite {
-                            color: Color::from(BLACK),
+                            color: HEALTH_BAR_BACKGROUND,
                             custom_size: Some(Vec2::new(ant.size().x * 0.8, ant.size().y * 0.1)),
                             ..default()
                         },
@@ -148,7 +162,7 @@ pub fn spawn_egg_event(
                     .with_children(|parent| {
                         parent.spawn((
                             Sprite {
-                                color: Color::from(LIME),
+                                color: ant.health_color(&game_settings.palette),
                                 custom_size: Some(Vec2::new(
                                     ant.size().x * 0.77,
                                     ant.size().y * 0.08,
@@ -251,7 +265,7 @@ pub fn spawn_ant_event(
                 parent
                     .spawn((
                         Sprite {
-                            color: Color::from(BLACK),
+                            color: HEALTH_BAR_BACKGROUND,
                             custom_size: Some(Vec2::new(ant.size().x * 0.8, ant.size().y * 0.1)),
                             ..default()
                         },
@@ -263,7 +277,7 @@ pub fn spawn_ant_event(
                     .with_children(|parent| {
                         parent.spawn((
                             Sprite {
-                                color: Color::from(LIME),
+                                color: ant.health_color(&game_settings.palette),
                                 custom_size: Some(Vec2::new(
                                     ant.size().x * 0.77,
                                     ant.size().y * 0.08,
@@ -275,11 +289,30 @@ pub fn spawn_ant_event(
                         ));
                     });
 
+                if ant.is_veteran_kind() {
+                    for i in 0..RANK_EXPERIENCE.len() {
+                        parent.spawn((
+                            Sprite {
+                                color: Color::from(GOLD),
+                                custom_size: Some(Vec2::new(
+                                    ant.size().x * 0.12,
+                                    ant.size().y * 0.04,
+                                )),
+                                ..default()
+                            },
+                            AntRankCmp(i),
+                            Visibility::Hidden,
+                            PickingBehavior::IGNORE,
+                            NoRotationChildCmp,
+                        ));
+                    }
+                }
+
                 let r = 0.4 * ant.size().min_element();
                 parent.spawn((
                     Mesh2d(meshes.add(Annulus::new(r, 1.1 * r))),
                     MeshMaterial2d(
-                        materials.add(ColorMaterial::from(Color::srgba(0., 0., 0., 0.8))),
+                        materials.add(ColorMaterial::from(ant.ring_color(&game_settings.palette))),
                     ),
                     Transform::from_translation(Vec3::new(0., 0., -0.1)),
                     SelectedCmp,
@@ -342,6 +375,7 @@ pub fn spawn_ant_event(
 
         if let Some(entity) = entity {
             entity_map.0.insert(*entity, id);
+            commands.entity(id).insert(SnapshotBuffer::default());
         } else {
             commands.entity(id).insert(Owned);
         }
@@ -355,10 +389,13 @@ pub fn despawn_ant_event(
     mut next_game_state: ResMut<NextState<GameState>>,
     game_settings: Res<GameSettings>,
     players: Res<Players>,
+    dedicated: Option<Res<DedicatedServer>>,
+    spectator: Option<Res<Spectator>>,
 ) {
     for DespawnAntEv { entity } in despawn_ant_ev.read() {
         if let Ok(ant) = ant_q.get(*entity) {
-            if ant.kind == Ant::Queen {
+            // A dedicated server has no queen of its own and checks the winner separately
+            if ant.kind == Ant::Queen && dedicated.is_none() {
                 let queens = ant_q
                     .iter()
                     .filter(|a| a.kind == Ant::Queen && a.health > 0.)
@@ -369,8 +406,20 @@ pub fn despawn_ant_event(
                     .filter(|a| a.team == players.main_id())
                     .collect::<Vec<_>>();
 
-                // End game if your queen died or there is only one queen left
-                if player_queens.is_empty() || queens.len() == player_queens.len() {
+                // End game if your queen died or only allied queens are left
+                // (observers have no queen, so only the second condition applies)
+                let game_over = if spectator.is_some() {
+                    queens
+                        .iter()
+                        .all(|a| players.are_allies(a.team, queens[0].team))
+                } else {
+                    player_queens.is_empty()
+                        || queens
+                            .iter()
+                            .all(|a| players.are_allies(a.team, players.main_id()))
+                };
+
+                if game_over {
                     next_game_state.set(GameState::EndGame);
 
                     // Avoid despawning queen if in singleplayer
@@ -387,29 +436,77 @@ pub fn despawn_ant_event(
 
 pub fn damage_event(
     mut damage_ev: EventReader<DamageAntEv>,
-    mut ant_q: Query<&mut AntCmp>,
-    mut egg_q: Query<&mut Egg>,
+    mut ant_q: Query<(&mut AntCmp, Has<Owned>)>,
+    mut egg_q: Query<(&mut Egg, Has<Owned>)>,
+    transform_q: Query<&Transform>,
     players: Res<Players>,
+    mut stats: ResMut<GameStats>,
+    mut alert_ev: EventWriter<AlertEv>,
 ) {
     for DamageAntEv { attacker, defender } in damage_ev.read() {
-        let attacker = ant_q.get(*attacker).unwrap();
-        let player_a = players.get(attacker.team);
-        let damage = attacker.damage;
+        let Ok((attacker_c, attacker_owned)) = ant_q.get(*attacker) else {
+            continue;
+        };
 
-        if let Ok(mut ant) = ant_q.get_mut(*defender) {
-            // Apply extra bonus factors against monsters
+        let player_a = players.get(attacker_c.team);
+        let damage = attacker_c.damage
+            * if attacker_c.starving {
+                STARVATION_DAMAGE_FACTOR
+            } else {
+                1.
+            };
+        let veteran = attacker_owned && attacker_c.is_veteran_kind();
+        let (team, kind) = (attacker_c.team, attacker_c.kind.clone());
+
+        let mut experience = 0.;
+        let mut kill = false;
+        if let Ok((mut ant, defender_owned)) = ant_q.get_mut(*defender) {
+            // Apply the defender's armor and the attacker's bonuses against its type
             let damage = damage
-                * if (ant.kind.is_scorpion() && player_a.has_trait(&Trait::ScorpionKiller))
-                    || (ant.kind == Ant::Wasp && player_a.has_trait(&Trait::WaspKiller))
-                    || (ant.kind.is_termite() && player_a.has_trait(&Trait::TermiteKiller))
-                {
-                    2.
-                } else {
-                    1.
-                };
-            ant.health = (ant.health - damage).max(0.);
-        } else if let Ok(mut egg) = egg_q.get_mut(*defender) {
-            egg.health = (egg.health - damage).max(0.);
+                * ant.kind.resistance(&kind.damage_type())
+                * player_a.damage_factor(&ant.kind);
+
+            experience = damage.min(ant.health);
+            if ant.health > 0. && damage >= ant.health {
+                kill = true;
+                experience += KILL_EXPERIENCE_FACTOR * ant.max_health;
+            }
+
+            if defender_owned {
+                ant.health = (ant.health - damage).max(0.);
+
+                if ant.kind == Ant::Queen && damage > 0. {
+                    if let Ok(queen_t) = transform_q.get(*defender) {
+                        alert_ev.send(AlertEv::new(
+                            AlertKind::QueenAttacked,
+                            ant.team,
+                            queen_t.translation.truncate(),
+                        ));
+                    }
+                }
+
+                if kill {
+                    let colony = stats.colony(team);
+                    *colony.kills.entry(kind).or_default() += 1;
+                    if !ant.kind.is_ant() {
+                        colony.monsters_killed += 1;
+                    }
+                }
+            }
+        } else if let Ok((mut egg, defender_owned)) = egg_q.get_mut(*defender) {
+            experience = damage.min(egg.health);
+            if defender_owned {
+                egg.health = (egg.health - damage).max(0.);
+            }
+        }
+
+        if veteran && experience > 0. {
+            if let Ok((mut ant, _)) = ant_q.get_mut(*attacker) {
+                ant.add_experience(experience);
+                if kill {
+                    ant.kills += 1;
+                }
+            }
         }
     }
 }
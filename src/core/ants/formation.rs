@@ -0,0 +1,130 @@
+use crate::core::ants::components::AntCmp;
+use crate::core::audio::PlayAudioEv;
+use crate::core::keymap::{InputAction, Keymap};
+use crate::core::map::loc::Loc;
+use crate::core::map::map::Map;
+use crate::core::map::tile::Tile;
+use bevy::prelude::*;
+use bevy::utils::hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+/// Maximum number of walkable locations considered around the target
+const MAX_FORMATION_CANDIDATES: usize = 250;
+
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Formation {
+    #[default]
+    Line,
+    Wedge,
+    Column,
+}
+
+impl Formation {
+    pub fn next(&self) -> Self {
+        match self {
+            Formation::Line => Formation::Wedge,
+            Formation::Wedge => Formation::Column,
+            Formation::Column => Formation::Line,
+        }
+    }
+
+    /// Cost of placing an ant on a slot with offset `along` (in the walking
+    /// direction) and `perp` (perpendicular to it) from the target, in bits
+    fn cost(&self, along: f32, perp: f32) -> f32 {
+        match self {
+            Formation::Line => 3. * along.abs() + perp.abs(),
+            Formation::Wedge => 2. * (along + perp.abs()).abs() + perp.abs(),
+            Formation::Column => 3. * perp.abs() + (-along).max(0.) + 2. * along.max(0.),
+        }
+    }
+
+    /// Assign a walkable location near `target` to every ant in the group
+    ///
+    /// The slots are found walking over the walkable bits around the target,
+    /// so narrow tunnels naturally stretch the formation. The front slots are
+    /// assigned to the ants with the most health.
+    pub fn assign(
+        &self,
+        group: &[(Entity, Vec3, AntCmp)],
+        target: &Loc,
+        map: &Map,
+    ) -> HashMap<Entity, Loc> {
+        if group.is_empty() {
+            return HashMap::new();
+        }
+
+        let target_pos = Map::get_coord_from_loc(target);
+        let center = group.iter().map(|(_, t, _)| t.truncate()).sum::<Vec2>() / group.len() as f32;
+        let facing = (target_pos - center).try_normalize().unwrap_or(Vec2::Y);
+        let step = Tile::SIZE / (Tile::SIDE as f32 + 1.);
+
+        // Breadth-first search over the walkable locations around the target
+        let mut candidates = vec![*target];
+        let mut seen = HashSet::from([*target]);
+        let mut queue = VecDeque::from([*target]);
+        while let Some(loc) = queue.pop_front() {
+            if candidates.len() >= MAX_FORMATION_CANDIDATES {
+                break;
+            }
+
+            for l in map.get_neighbors(&loc) {
+                if map.is_walkable(&l) && seen.insert(l) {
+                    candidates.push(l);
+                    queue.push_back(l);
+                }
+            }
+        }
+
+        let mut slots = candidates
+            .into_iter()
+            .map(|l| {
+                let offset = (Map::get_coord_from_loc(&l) - target_pos) / step;
+                (l, offset.dot(facing), offset.dot(facing.perp()))
+            })
+            .collect::<Vec<_>>();
+
+        slots.sort_by(|(_, a1, p1), (_, a2, p2)| {
+            self.cost(*a1, *p1)
+                .partial_cmp(&self.cost(*a2, *p2))
+                .unwrap_or(Ordering::Equal)
+        });
+        slots.truncate(group.len());
+
+        // Front slots first
+        slots.sort_by(|(_, a1, _), (_, a2, _)| a2.partial_cmp(a1).unwrap_or(Ordering::Equal));
+
+        let mut ants = group.iter().collect::<Vec<_>>();
+        ants.sort_by(|(_, _, a1), (_, _, a2)| {
+            a2.max_health
+                .partial_cmp(&a1.max_health)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        ants.iter()
+            .enumerate()
+            .map(|(i, (e, _, _))| (*e, slots.get(i % slots.len()).map_or(*target, |s| s.0)))
+            .collect()
+    }
+}
+
+/// Speed of a group of ants, which moves as fast as its slowest member
+pub fn formation_speed(group: &[(Entity, Vec3, AntCmp)]) -> Option<f32> {
+    group
+        .iter()
+        .map(|(_, _, a)| a.speed)
+        .min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+}
+
+pub fn cycle_formation_keyboard(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
+    mut formation: ResMut<Formation>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+) {
+    if keymap.just_pressed(&keyboard, &InputAction::CycleFormation) {
+        *formation = formation.next();
+        play_audio_ev.send(PlayAudioEv::new("button"));
+    }
+}
@@ -1,5 +1,7 @@
 pub mod components;
 pub mod events;
+pub mod formation;
+pub mod projectile;
 pub mod selection;
 pub mod systems;
 pub mod utils;
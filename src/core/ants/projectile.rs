@@ -0,0 +1,142 @@
+use crate::core::ants::components::{Action, AntCmp, Egg, Owned};
+use crate::core::ants::events::DamageAntEv;
+use crate::core::constants::*;
+use crate::core::game_settings::GameSettings;
+use crate::core::map::map::Map;
+use crate::core::map::systems::MapCmp;
+use crate::core::menu::settings::FogOfWar;
+use crate::core::player::Players;
+use bevy::prelude::*;
+use bevy_renet::renet::ClientId;
+
+#[derive(Component)]
+pub struct ProjectileCmp {
+    /// Ant that fired the projectile
+    pub attacker: Entity,
+
+    /// Entity the projectile flies towards
+    pub target: Entity,
+
+    /// Team of the attacker
+    pub team: ClientId,
+
+    /// Radius in pixels of the splash damage
+    pub splash: f32,
+}
+
+#[derive(Event)]
+pub struct SpawnProjectileEv {
+    pub attacker: Entity,
+    pub target: Entity,
+}
+
+pub fn spawn_projectile_event(
+    mut commands: Commands,
+    mut spawn_projectile_ev: EventReader<SpawnProjectileEv>,
+    ant_q: Query<(&Transform, &AntCmp)>,
+) {
+    for SpawnProjectileEv { attacker, target } in spawn_projectile_ev.read() {
+        if let Ok((ant_t, ant)) = ant_q.get(*attacker) {
+            commands.spawn((
+                Sprite {
+                    color: PROJECTILE_COLOR,
+                    custom_size: Some(Vec2::splat(2.5)),
+                    ..default()
+                },
+                Transform::from_translation(
+                    ant_t.translation.truncate().extend(PROJECTILE_Z_SCORE),
+                ),
+                ProjectileCmp {
+                    attacker: *attacker,
+                    target: *target,
+                    team: ant.team,
+                    splash: ant.splash,
+                },
+                Visibility::Hidden,
+                PickingBehavior::IGNORE,
+                MapCmp,
+            ));
+        }
+    }
+}
+
+pub fn resolve_projectiles(
+    mut commands: Commands,
+    mut projectile_q: Query<(Entity, &mut Transform, &mut Visibility, &ProjectileCmp)>,
+    ant_q: Query<(Entity, &Transform, &AntCmp), Without<ProjectileCmp>>,
+    egg_q: Query<(Entity, &Transform, &Egg), Without<ProjectileCmp>>,
+    owned_q: Query<&Owned>,
+    mut damage_ev: EventWriter<DamageAntEv>,
+    game_settings: Res<GameSettings>,
+    players: Res<Players>,
+    map: Res<Map>,
+    time: Res<Time>,
+) {
+    for (projectile_e, mut projectile_t, mut projectile_v, projectile) in projectile_q.iter_mut() {
+        let target_pos = ant_q
+            .get(projectile.target)
+            .map(|(_, t, _)| t.translation)
+            .or_else(|_| egg_q.get(projectile.target).map(|(_, t, _)| t.translation));
+
+        let Ok(target_pos) = target_pos else {
+            // The target doesn't exist anymore
+            commands.entity(projectile_e).despawn_recursive();
+            continue;
+        };
+
+        let d = target_pos.truncate() - projectile_t.translation.truncate();
+        let step =
+            PROJECTILE_SPEED * game_settings.speed * time.delta_secs().min(CAPPED_DELTA_SECS_SPEED);
+
+        if d.length() <= PROJECTILE_HIT_DISTANCE.max(step) {
+            // Damage the target and the enemies in the splash radius
+            let mut defenders = vec![projectile.target];
+            if projectile.splash > 0. {
+                defenders.extend(
+                    ant_q
+                        .iter()
+                        .filter(|(_, _, a)| a.health > 0. && a.action != Action::DoNothing)
+                        .map(|(e, t, a)| (e, t, a.team))
+                        .chain(egg_q.iter().map(|(e, t, egg)| (e, t, egg.team)))
+                        .filter(|(e, t, team)| {
+                            *e != projectile.target
+                                && !players.are_allies(*team, projectile.team)
+                                && t.translation.truncate().distance(target_pos.truncate())
+                                    <= projectile.splash
+                        })
+                        .map(|(e, _, _)| e),
+                );
+            }
+
+            for defender in defenders {
+                // Same as melee attacks, only the owners of the ants apply the damage
+                if owned_q.get(defender).is_ok() || owned_q.get(projectile.attacker).is_ok() {
+                    damage_ev.send(DamageAntEv {
+                        attacker: projectile.attacker,
+                        defender,
+                    });
+                }
+            }
+
+            commands.entity(projectile_e).despawn_recursive();
+        } else {
+            let next_pos = projectile_t.translation + (d.normalize() * step).extend(0.);
+
+            let loc = map.get_loc(&next_pos);
+            if map.is_walkable(&loc) {
+                projectile_t.translation = next_pos;
+
+                *projectile_v = if game_settings.fog_of_war == FogOfWar::None
+                    || players.main().visible_tiles.contains(&(loc.x, loc.y))
+                {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                };
+            } else {
+                // The projectile hit the soil
+                commands.entity(projectile_e).despawn_recursive();
+            }
+        }
+    }
+}
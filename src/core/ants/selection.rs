@@ -1,10 +1,13 @@
 use crate::core::ants::components::{
     Action, Ant, AntCmp, AttackCmp, Behavior, Corpse, DefendCmp, Egg,
 };
+use crate::core::ants::formation::{formation_speed, Formation};
 use crate::core::assets::WorldAssets;
 use crate::core::audio::PlayAudioEv;
+use crate::core::chat::{is_pinging, PingCmp};
 use crate::core::constants::MAX_Z_SCORE;
 use crate::core::game_settings::GameSettings;
+use crate::core::keymap::{InputAction, Keymap};
 use crate::core::map::events::LeafCmp;
 use crate::core::map::loc::Loc;
 use crate::core::map::map::Map;
@@ -15,6 +18,8 @@ use crate::core::states::GameState;
 use crate::core::traits::Trait;
 use bevy::prelude::*;
 use bevy::utils::hashbrown::{HashMap, HashSet};
+use bevy_renet::renet::ClientId;
+use serde::{Deserialize, Serialize};
 
 #[derive(Resource, Default)]
 pub struct AntSelection(pub HashSet<Entity>);
@@ -24,13 +29,37 @@ pub struct GroupSelection(pub HashMap<u8, HashSet<Entity>>);
 
 #[derive(Event)]
 pub struct SelectAntEv {
-    entity: Entity,
-    clean: bool,
+    pub entity: Entity,
+    pub clean: bool,
 }
 
 #[derive(Event)]
 pub struct PinEv(pub Loc);
 
+/// Target of a command given by a player to a group of ants
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum CommandTarget {
+    /// Protect (or dig towards) a location
+    Loc(Loc),
+    /// Harvest the leaf growing on the tile of the location
+    Leaf(Loc),
+    Egg(Entity),
+    Ant(Entity),
+    /// Remove the player's commands
+    Stop,
+}
+
+/// Command of a player to a group of its ants
+///
+/// Clients only send the command to the host, which derives the actions of the ants.
+#[derive(Event, Clone, Serialize, Deserialize)]
+pub struct AntCommandEv {
+    pub team: ClientId,
+    pub ants: Vec<Entity>,
+    pub target: CommandTarget,
+    pub formation: Formation,
+}
+
 #[derive(Default, PartialEq)]
 pub struct SelectionBox {
     start: Vec2,
@@ -43,14 +72,14 @@ pub struct PinCmp {
     pub index: usize,
 }
 
-pub fn spawn_pin_event(
-    mut commands: Commands,
-    mut pin_ev: EventReader<PinEv>,
-    assets: Local<WorldAssets>,
-) {
-    for PinEv(loc) in pin_ev.read() {
-        commands.spawn((
-            Sprite::from_image(assets.image("pin")),
+/// Spawn an animated pin on a location of the map
+pub fn spawn_pin(commands: &mut Commands, loc: &Loc, color: Color, assets: &WorldAssets) -> Entity {
+    commands
+        .spawn((
+            Sprite {
+                color,
+                ..Sprite::from_image(assets.image("pin"))
+            },
             Transform {
                 translation: (Map::get_coord_from_loc(loc) + Vec2::new(0., 5.)).extend(MAX_Z_SCORE),
                 scale: Vec3::splat(0.03),
@@ -63,7 +92,17 @@ pub fn spawn_pin_event(
                 index: 0,
             },
             MapCmp,
-        ));
+        ))
+        .id()
+}
+
+pub fn spawn_pin_event(
+    mut commands: Commands,
+    mut pin_ev: EventReader<PinEv>,
+    assets: Local<WorldAssets>,
+) {
+    for PinEv(loc) in pin_ev.read() {
+        spawn_pin(&mut commands, loc, Color::WHITE, &assets);
     }
 }
 
@@ -85,12 +124,11 @@ pub fn animate_pin(mut pin_q: Query<(&mut Transform, &mut PinCmp)>, time: Res<Ti
 
 pub fn select_loc_on_click(
     trigger: Trigger<Pointer<Click>>,
-    mut ant_q: Query<(&Transform, &mut AntCmp)>,
-    game_settings: Res<GameSettings>,
     players: Res<Players>,
-    mut map: ResMut<Map>,
+    map: Res<Map>,
     mut selection: ResMut<AntSelection>,
-    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    formation: Res<Formation>,
+    mut ant_command_ev: EventWriter<AntCommandEv>,
     game_state: Res<State<GameState>>,
     keyboard: Res<ButtonInput<KeyCode>>,
     camera: Single<(&Camera, &GlobalTransform)>,
@@ -98,11 +136,11 @@ pub fn select_loc_on_click(
 ) {
     if !matches!(*game_state.get(), GameState::Running | GameState::Paused)
         || selection.0.is_empty()
+        || is_pinging(&keyboard)
     {
         return;
     }
 
-    let player = players.main();
     let (camera, global_t) = *camera;
 
     match trigger.event.button {
@@ -112,62 +150,16 @@ pub fn select_loc_on_click(
             selection.0.clear();
         }
         PointerButton::Secondary => {
-            let mut success = false;
-
             let cursor = camera
                 .viewport_to_world_2d(global_t, window.cursor_position().unwrap())
                 .unwrap();
 
-            let loc = map.get_loc(&cursor.extend(0.));
-            let tile = map.get_tile(loc.x, loc.y).unwrap();
-
-            // Accept location when the tile is walkable and explored or when the
-            // player can walk to it (not always the same as explored for fow=half)
-            // Check if the player can walk to it with the first ant in the selection only
-            let current_loc = map.get_loc(
-                &ant_q
-                    .get(*selection.0.iter().next().unwrap())
-                    .unwrap()
-                    .0
-                    .translation,
-            );
-
-            if map.is_walkable(&loc)
-                && (tile.explored.contains(&player.id)
-                    || (game_settings.fog_of_war != FogOfWar::Full
-                        && map.shortest_path_option(&current_loc, &loc).is_some()))
-            {
-                for ant_e in selection.0.iter() {
-                    if let Ok((_, mut ant)) = ant_q.get_mut(*ant_e) {
-                        // The queen cannot be ordered around except when wandering
-                        if ant.kind != Ant::Queen || player.has_trait(&Trait::WanderingQueen) {
-                            ant.command = Some(Behavior::ProtectLoc(loc));
-                            ant.action = Action::Walk(loc);
-                            success = true;
-                        }
-                    }
-                }
-            } else {
-                for ant_e in selection.0.iter() {
-                    if let Ok((ant_t, mut ant)) = ant_q.get_mut(*ant_e) {
-                        if ant.kind == Ant::Excavator {
-                            let current_loc = map.get_loc(&ant_t.translation);
-                            if let Some(l) = map.find_tunnel(&current_loc, &loc) {
-                                ant.command = Some(Behavior::Dig(loc));
-                                ant.action = Action::Walk(l);
-                                success = true;
-                            }
-                        }
-                    }
-                }
-            }
-
-            if !success {
-                play_audio_ev.send(PlayAudioEv {
-                    name: "error",
-                    volume: 0.5,
-                });
-            }
+            ant_command_ev.send(AntCommandEv {
+                team: players.main_id(),
+                ants: selection.0.iter().copied().collect(),
+                target: CommandTarget::Loc(map.get_loc(&cursor.extend(0.))),
+                formation: *formation,
+            });
         }
         _ => (),
     }
@@ -175,66 +167,30 @@ pub fn select_loc_on_click(
 
 pub fn select_leaf_on_click(
     mut trigger: Trigger<Pointer<Click>>,
-    mut ant_q: Query<(&Transform, &mut AntCmp)>,
-    leaf_q: Query<(Entity, &GlobalTransform), With<LeafCmp>>,
-    mut play_audio_ev: EventWriter<PlayAudioEv>,
-    game_settings: Res<GameSettings>,
+    leaf_q: Query<&GlobalTransform, With<LeafCmp>>,
+    mut ant_command_ev: EventWriter<AntCommandEv>,
     players: Res<Players>,
-    mut map: ResMut<Map>,
+    map: Res<Map>,
     selection: Res<AntSelection>,
+    formation: Res<Formation>,
     game_state: Res<State<GameState>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
 ) {
     if !matches!(*game_state.get(), GameState::Running | GameState::Paused)
         || selection.0.is_empty()
+        || is_pinging(&keyboard)
     {
         return;
     }
 
-    let player = players.main();
-
     if trigger.event.button == PointerButton::Secondary {
-        if let Ok((leaf_e, leaf_t)) = leaf_q.get(trigger.entity()) {
-            let mut success = false;
-
-            let loc = map.get_loc(&leaf_t.translation());
-            let tile = map.get_tile(loc.x, loc.y).unwrap();
-
-            let current_loc = map.get_loc(
-                &ant_q
-                    .get(*selection.0.iter().next().unwrap())
-                    .unwrap()
-                    .0
-                    .translation,
-            );
-
-            if tile.explored.contains(&player.id)
-                || (game_settings.fog_of_war != FogOfWar::Full
-                    && map.shortest_path_option(&current_loc, &loc).is_some())
-            {
-                // Workers go harvest the leaf; the rest protects the location
-                for ant_e in selection.0.iter() {
-                    if let Ok((_, mut sel)) = ant_q.get_mut(*ant_e) {
-                        if sel.kind != Ant::Queen || player.has_trait(&Trait::WanderingQueen) {
-                            if sel.kind == Ant::Worker {
-                                sel.command = Some(Behavior::Harvest(leaf_e));
-                                sel.action = Action::Walk(loc);
-                                success = true;
-                            } else {
-                                sel.command = Some(Behavior::ProtectLoc(loc));
-                                sel.action = Action::Walk(loc);
-                                success = true;
-                            }
-                        }
-                    }
-                }
-            }
-
-            if !success {
-                play_audio_ev.send(PlayAudioEv {
-                    name: "error",
-                    volume: 0.5,
-                });
-            }
+        if let Ok(leaf_t) = leaf_q.get(trigger.entity()) {
+            ant_command_ev.send(AntCommandEv {
+                team: players.main_id(),
+                ants: selection.0.iter().copied().collect(),
+                target: CommandTarget::Leaf(map.get_loc(&leaf_t.translation())),
+                formation: *formation,
+            });
         }
     }
 
@@ -244,80 +200,54 @@ pub fn select_leaf_on_click(
 
 pub fn select_egg_on_click(
     trigger: Trigger<Pointer<Click>>,
-    mut ant_q: Query<(Entity, &Transform, &mut AntCmp)>,
-    egg_q: Query<(Entity, &Egg)>,
     players: Res<Players>,
-    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    mut ant_command_ev: EventWriter<AntCommandEv>,
     selection: Res<AntSelection>,
+    formation: Res<Formation>,
     game_state: Res<State<GameState>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
 ) {
     if !matches!(*game_state.get(), GameState::Running | GameState::Paused)
         || selection.0.is_empty()
+        || is_pinging(&keyboard)
     {
         return;
     }
 
-    let player = players.main();
-    let (egg_e, egg) = egg_q.get(trigger.entity()).unwrap();
-
-    match trigger.event.button {
-        PointerButton::Secondary => {
-            let mut success = false;
-
-            for sel_e in selection.0.iter() {
-                if let Ok((_, _, mut sel)) = ant_q.get_mut(*sel_e) {
-                    // The queen cannot be ordered around except when wandering
-                    if sel.kind != Ant::Queen || player.has_trait(&Trait::WanderingQueen) {
-                        if egg.team != player.id {
-                            // If clicked on an enemy, attack it
-                            sel.command = Some(Behavior::Attack);
-                            sel.action = Action::TargetedWalk(egg_e);
-                        } else {
-                            // If clicked on an ally, protect it
-                            sel.command = Some(Behavior::ProtectAnt(egg_e));
-                            sel.action = Action::TargetedWalk(egg_e);
-                        }
-
-                        success = true;
-                    }
-                }
-            }
-
-            if !success {
-                play_audio_ev.send(PlayAudioEv {
-                    name: "error",
-                    volume: 0.5,
-                });
-            }
-        }
-        _ => (),
+    if trigger.event.button == PointerButton::Secondary {
+        ant_command_ev.send(AntCommandEv {
+            team: players.main_id(),
+            ants: selection.0.iter().copied().collect(),
+            target: CommandTarget::Egg(trigger.entity()),
+            formation: *formation,
+        });
     }
 }
 
 pub fn select_ant_on_click(
     trigger: Trigger<Pointer<Click>>,
-    mut ant_q: Query<(Entity, &Transform, &mut AntCmp)>,
+    ant_q: Query<(Entity, &Transform, &AntCmp)>,
     players: Res<Players>,
-    map: Res<Map>,
     mut select_ants_ev: EventWriter<SelectAntEv>,
-    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    mut ant_command_ev: EventWriter<AntCommandEv>,
     selection: Res<AntSelection>,
+    formation: Res<Formation>,
     game_state: Res<State<GameState>>,
     mut last_clicked_t: Local<f32>,
     time: Res<Time>,
+    keyboard: Res<ButtonInput<KeyCode>>,
     camera: Single<(&Camera, &GlobalTransform)>,
     window: Single<&Window>,
 ) {
-    if !matches!(*game_state.get(), GameState::Running | GameState::Paused) {
+    if !matches!(*game_state.get(), GameState::Running | GameState::Paused) || is_pinging(&keyboard)
+    {
         return;
     }
 
     let player = players.main();
     let (camera, global_t) = *camera;
 
-    let (ant_e, ant_t, ant) = ant_q.get(trigger.entity()).unwrap();
-    let ant = ant.clone();
-    let loc = map.get_loc(&ant_t.translation);
+    let (ant_e, _, ant) = ant_q.get(trigger.entity()).unwrap();
 
     match trigger.event.button {
         // Left mouse button used for selection
@@ -356,57 +286,299 @@ pub fn select_ant_on_click(
             *last_clicked_t = time.elapsed_secs();
         }
         // Right mouse button used to set a new action
-        PointerButton::Secondary => {
-            let mut success = false;
-
-            for sel_e in selection.0.iter() {
-                if let Ok((_, _, mut sel)) = ant_q.get_mut(*sel_e) {
-                    // The queen cannot be ordered around except when wandering
-                    if sel.kind != Ant::Queen || player.has_trait(&Trait::WanderingQueen) {
-                        // Skip commands onto himself
-                        if ant_e != *sel_e {
-                            if ant.health == 0. {
-                                // If clicked on a corpse, go harvest it or protect the location
-                                if sel.kind == Ant::Worker {
-                                    sel.command = Some(Behavior::HarvestCorpse(ant_e));
-                                    sel.action = Action::TargetedWalk(ant_e);
-                                } else {
-                                    sel.command = Some(Behavior::ProtectLoc(loc));
-                                    sel.action = Action::Walk(loc);
-                                }
-
-                                success = true;
-                            } else if ant.team != player.id {
-                                // If clicked on an enemy, attack it if reachable
-                                if map
-                                    .get_tile(loc.x, loc.y)
-                                    .unwrap()
-                                    .explored
-                                    .contains(&player.id)
-                                {
-                                    sel.command = Some(Behavior::Attack);
-                                    sel.action = Action::TargetedWalk(ant_e);
-                                    success = true;
-                                }
+        PointerButton::Secondary if !selection.0.is_empty() => {
+            ant_command_ev.send(AntCommandEv {
+                team: player.id,
+                ants: selection.0.iter().copied().collect(),
+                target: CommandTarget::Ant(ant_e),
+                formation: *formation,
+            });
+        }
+        _ => (),
+    }
+}
+
+/// New command and action for an ant, with its speed and offset in the formation (if any)
+struct Order {
+    command: Behavior,
+    action: Action,
+    speed: Option<f32>,
+    offset: Option<Vec2>,
+}
+
+impl Order {
+    fn new(command: Behavior, action: Action) -> Self {
+        Self {
+            command,
+            action,
+            speed: None,
+            offset: None,
+        }
+    }
+}
+
+/// Whether the team can send ants to a location: when the tile is explored or when
+/// the ants can walk to it (not always the same as explored for fow=half)
+fn is_reachable(
+    map: &mut Map,
+    start: &Loc,
+    loc: &Loc,
+    team: ClientId,
+    fog_of_war: FogOfWar,
+) -> bool {
+    map.get_tile(loc.x, loc.y)
+        .is_some_and(|tile| tile.explored.contains(&team))
+        || (fog_of_war != FogOfWar::Full && map.shortest_path_option(start, loc).is_some())
+}
+
+/// Turn the commands of the players into the actions of their ants
+///
+/// Only the host (or a single player game) resolves the commands, so the
+/// ants, targets and actions are all validated against its own state.
+pub fn resolve_ant_commands(
+    mut ant_command_ev: EventReader<AntCommandEv>,
+    mut ant_q: Query<(Entity, &Transform, &mut AntCmp)>,
+    egg_q: Query<(&Transform, &Egg)>,
+    leaf_q: Query<(Entity, &GlobalTransform), With<LeafCmp>>,
+    game_settings: Res<GameSettings>,
+    players: Res<Players>,
+    mut map: ResMut<Map>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+) {
+    for AntCommandEv {
+        team,
+        ants,
+        target,
+        formation,
+    } in ant_command_ev.read()
+    {
+        let player = players.get(*team);
+
+        // Players can only command their own living ants, and the
+        // queen cannot be ordered around except when wandering
+        let group = ants
+            .iter()
+            .filter_map(|e| {
+                let (_, t, a) = ant_q.get(*e).ok()?;
+                (a.team == *team
+                    && a.health > 0.
+                    && (a.kind != Ant::Queen || player.has_trait(&Trait::WanderingQueen)))
+                .then(|| (*e, t.translation, a.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let Some((_, first_t, _)) = group.first() else {
+            continue;
+        };
+
+        // Check if the player can walk to the target with the first ant in the group only
+        let current_loc = map.get_loc(first_t);
+
+        let mut orders: Vec<(Entity, Order)> = vec![];
+
+        match *target {
+            CommandTarget::Loc(loc) if Map::contains(&loc) => {
+                if map.is_walkable(&loc)
+                    && is_reachable(
+                        &mut map,
+                        &current_loc,
+                        &loc,
+                        *team,
+                        game_settings.fog_of_war,
+                    )
+                {
+                    let speed = formation_speed(&group);
+                    for (ant_e, slot) in formation.assign(&group, &loc, &map) {
+                        orders.push((
+                            ant_e,
+                            Order {
+                                speed,
+                                ..Order::new(Behavior::ProtectLoc(loc), Action::Walk(slot))
+                            },
+                        ));
+                    }
+                } else {
+                    // Excavators dig a tunnel towards unreachable locations
+                    for (ant_e, t, a) in group.iter().filter(|(_, _, a)| a.kind == Ant::Excavator) {
+                        if let Some(l) = map.find_tunnel(&map.get_loc(t), &loc) {
+                            orders.push((*ant_e, Order::new(Behavior::Dig(loc), Action::Walk(l))));
+                        }
+                    }
+                }
+            }
+            CommandTarget::Leaf(loc) => {
+                // Leaves only exist locally, so they are identified by the tile they grow on
+                let Some((leaf_e, leaf_t)) = leaf_q.iter().find(|(_, t)| {
+                    let l = map.get_loc(&t.translation());
+                    l.x == loc.x && l.y == loc.y
+                }) else {
+                    continue;
+                };
+
+                let loc = map.get_loc(&leaf_t.translation());
+                if is_reachable(
+                    &mut map,
+                    &current_loc,
+                    &loc,
+                    *team,
+                    game_settings.fog_of_war,
+                ) {
+                    // Workers go harvest the leaf; the rest protects the location in formation
+                    let (workers, others): (Vec<_>, Vec<_>) = group
+                        .iter()
+                        .cloned()
+                        .partition(|(_, _, a)| a.kind == Ant::Worker);
+
+                    for (ant_e, _, _) in workers {
+                        orders.push((
+                            ant_e,
+                            Order::new(Behavior::Harvest(leaf_e), Action::Walk(loc)),
+                        ));
+                    }
+
+                    let speed = formation_speed(&others);
+                    for (ant_e, slot) in formation.assign(&others, &loc, &map) {
+                        orders.push((
+                            ant_e,
+                            Order {
+                                speed,
+                                ..Order::new(Behavior::ProtectLoc(loc), Action::Walk(slot))
+                            },
+                        ));
+                    }
+                }
+            }
+            CommandTarget::Egg(egg_e) => {
+                let Ok((egg_t, egg)) = egg_q.get(egg_e) else {
+                    continue;
+                };
+
+                let loc = map.get_loc(&egg_t.translation);
+                if !players.are_allies(egg.team, *team) {
+                    // If clicked on an enemy, attack it if explored
+                    if map.get_tile(loc.x, loc.y).unwrap().explored.contains(team) {
+                        for (ant_e, _, _) in group.iter() {
+                            orders.push((
+                                *ant_e,
+                                Order::new(Behavior::Attack, Action::TargetedWalk(egg_e)),
+                            ));
+                        }
+                    }
+                } else {
+                    // If clicked on an ally, walk in formation towards it to protect it
+                    let speed = formation_speed(&group);
+                    let slots = formation.assign(&group, &loc, &map);
+                    for (ant_e, _, _) in group.iter() {
+                        orders.push((
+                            *ant_e,
+                            Order {
+                                speed,
+                                offset: slots.get(ant_e).map(|l| {
+                                    Map::get_coord_from_loc(l) - egg_t.translation.truncate()
+                                }),
+                                ..Order::new(
+                                    Behavior::ProtectAnt(egg_e),
+                                    Action::TargetedWalk(egg_e),
+                                )
+                            },
+                        ));
+                    }
+                }
+            }
+            CommandTarget::Ant(target_e) => {
+                let Ok((_, target_t, target)) = ant_q.get(target_e) else {
+                    continue;
+                };
+
+                let pos = target_t.translation.truncate();
+                let loc = map.get_loc(&target_t.translation);
+                let target = target.clone();
+                let explored = map.get_tile(loc.x, loc.y).unwrap().explored.contains(team);
+
+                // Skip commands onto himself
+                let group = group
+                    .into_iter()
+                    .filter(|(e, _, _)| *e != target_e)
+                    .collect::<Vec<_>>();
+
+                if target.health == 0. {
+                    // If clicked on a corpse, go harvest it or protect the location
+                    if explored {
+                        for (ant_e, _, a) in group.iter() {
+                            if a.kind == Ant::Worker {
+                                orders.push((
+                                    *ant_e,
+                                    Order::new(
+                                        Behavior::HarvestCorpse(target_e),
+                                        Action::TargetedWalk(target_e),
+                                    ),
+                                ));
                             } else {
-                                // If clicked on an ally, protect it
-                                sel.command = Some(Behavior::ProtectAnt(ant_e));
-                                sel.action = Action::TargetedWalk(ant_e);
-                                success = true;
+                                orders.push((
+                                    *ant_e,
+                                    Order::new(Behavior::ProtectLoc(loc), Action::Walk(loc)),
+                                ));
                             }
                         }
                     }
+                } else if !players.are_allies(target.team, *team) {
+                    // If clicked on an enemy, attack it if explored
+                    if explored {
+                        for (ant_e, _, _) in group.iter() {
+                            orders.push((
+                                *ant_e,
+                                Order::new(Behavior::Attack, Action::TargetedWalk(target_e)),
+                            ));
+                        }
+                    }
+                } else {
+                    // If clicked on an ally, walk in formation towards it to protect it
+                    let speed = formation_speed(&group);
+                    let slots = formation.assign(&group, &loc, &map);
+                    for (ant_e, _, _) in group.iter() {
+                        orders.push((
+                            *ant_e,
+                            Order {
+                                speed,
+                                offset: slots.get(ant_e).map(|l| Map::get_coord_from_loc(l) - pos),
+                                ..Order::new(
+                                    Behavior::ProtectAnt(target_e),
+                                    Action::TargetedWalk(target_e),
+                                )
+                            },
+                        ));
+                    }
                 }
             }
+            CommandTarget::Stop => {
+                for (ant_e, _, _) in group.iter() {
+                    if let Ok((_, _, mut ant)) = ant_q.get_mut(*ant_e) {
+                        ant.command = None;
+                        ant.formation_speed = None;
+                        ant.formation_offset = None;
+                    }
+                }
+                continue;
+            }
+            _ => (),
+        }
+
+        // Only the player that gave the command hears it failing
+        if orders.is_empty() && *team == players.main_id() {
+            play_audio_ev.send(PlayAudioEv {
+                volume: 0.5,
+                ..PlayAudioEv::new("error")
+            });
+        }
 
-            if !success {
-                play_audio_ev.send(PlayAudioEv {
-                    name: "error",
-                    volume: 0.5,
-                });
+        for (ant_e, order) in orders {
+            if let Ok((_, _, mut ant)) = ant_q.get_mut(ant_e) {
+                ant.command = Some(order.command);
+                ant.action = order.action;
+                ant.formation_speed = order.speed;
+                ant.formation_offset = order.offset;
             }
         }
-        _ => (),
     }
 }
 
@@ -424,8 +596,8 @@ pub fn select_ants_from_rect(
     let player = players.main();
     let (camera, global_t) = *camera;
 
-    // If shift is pressed, the camera moves
-    if !keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+    // If shift is pressed, the camera moves (and alt places pings)
+    if !keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) && !is_pinging(&keyboard) {
         if let Some(cursor) = window.cursor_position() {
             // Transform global cursor coord to world coord
             let cursor = camera.viewport_to_world_2d(global_t, cursor).unwrap();
@@ -472,29 +644,17 @@ pub fn select_ants_to_res(
     mut groups: ResMut<GroupSelection>,
     mouse: Res<ButtonInput<MouseButton>>,
     keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
 ) {
     if mouse.just_released(MouseButton::Left)
         && !keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+        && !is_pinging(&keyboard)
     {
         selection.0.clear();
     }
 
-    for key in keyboard.get_just_released() {
-        let digit = match key {
-            KeyCode::Digit0 => Some(0),
-            KeyCode::Digit1 => Some(1),
-            KeyCode::Digit2 => Some(2),
-            KeyCode::Digit3 => Some(3),
-            KeyCode::Digit4 => Some(4),
-            KeyCode::Digit5 => Some(5),
-            KeyCode::Digit6 => Some(6),
-            KeyCode::Digit7 => Some(7),
-            KeyCode::Digit8 => Some(8),
-            KeyCode::Digit9 => Some(9),
-            _ => None,
-        };
-
-        if let Some(index) = digit {
+    for index in 0..10 {
+        if keymap.just_released(&keyboard, &InputAction::SelectGroup(index)) {
             if keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
                 groups.0.insert(index, selection.0.clone());
             } else {
@@ -516,15 +676,21 @@ pub fn select_ants_to_res(
 }
 
 pub fn remove_command_from_selection(
-    mut ant_q: Query<&mut AntCmp>,
+    players: Option<Res<Players>>,
     selection: Res<AntSelection>,
+    formation: Res<Formation>,
+    mut ant_command_ev: EventWriter<AntCommandEv>,
     keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
 ) {
-    if keyboard.just_pressed(KeyCode::Delete) {
-        for sel_e in selection.0.iter() {
-            if let Ok(mut ant) = ant_q.get_mut(*sel_e) {
-                ant.command = None;
-            }
+    if keymap.just_pressed(&keyboard, &InputAction::RemoveCommand) && !selection.0.is_empty() {
+        if let Some(players) = players {
+            ant_command_ev.send(AntCommandEv {
+                team: players.main_id(),
+                ants: selection.0.iter().copied().collect(),
+                target: CommandTarget::Stop,
+                formation: *formation,
+            });
         }
     }
 }
@@ -533,7 +699,7 @@ pub fn update_selection_icons(
     mut commands: Commands,
     ant_q: Query<(Entity, &AntCmp)>,
     egg_q: Query<Entity, With<Egg>>,
-    pin_q: Query<(Entity, &PinCmp)>,
+    pin_q: Query<(Entity, &PinCmp), Without<PingCmp>>,
     leaf_q: Query<(Entity, &GlobalTransform), With<LeafCmp>>,
     corpse_q: Query<(Entity, &GlobalTransform), With<Corpse>>,
     mut attack_q: Query<&mut Visibility, With<AttackCmp>>,
@@ -1,24 +1,31 @@
+use crate::core::alerts::{AlertEv, AlertKind};
 use crate::core::ants::components::*;
 use crate::core::ants::events::*;
+use crate::core::ants::projectile::SpawnProjectileEv;
 use crate::core::ants::selection::AntSelection;
-use crate::core::ants::utils::walk;
+use crate::core::ants::utils::{in_range, walk};
 use crate::core::assets::WorldAssets;
-use crate::core::audio::PlayAudioEv;
+use crate::core::audio::{AudioCategory, PlayAudioEv};
 use crate::core::constants::*;
 use crate::core::game_settings::GameSettings;
+use crate::core::keymap::{InputAction, Keymap};
 use crate::core::map::events::LeafCmp;
 use crate::core::map::map::Map;
 use crate::core::map::tile::Tile;
+use crate::core::menu::settings::Palette;
 use crate::core::player::Players;
 use crate::core::resources::Resources;
+use crate::core::stats::GameStats;
 use crate::core::traits::Trait;
 use crate::core::utils::{collision, scale_duration};
 use bevy::prelude::*;
 use bevy::utils::hashbrown::{HashMap, HashSet};
+use bevy_renet::renet::ClientId;
 use rand::distr::weighted::WeightedIndex;
 use rand::distr::Distribution;
 use rand::{rng, Rng};
 use std::f32::consts::PI;
+use std::time::Duration;
 use strum::IntoEnumIterator;
 
 pub fn hatch_eggs(
@@ -60,6 +67,7 @@ pub fn animate_ants(
     mut ant_q: Query<(Entity, &mut Sprite, &AntCmp, &mut AnimationCmp)>,
     owned_q: Query<&Owned>,
     mut damage_ev: EventWriter<DamageAntEv>,
+    mut spawn_projectile_ev: EventWriter<SpawnProjectileEv>,
     game_settings: Res<GameSettings>,
     assets: Local<WorldAssets>,
     time: Res<Time>,
@@ -83,15 +91,22 @@ pub fn animate_ants(
                         atlas.index + 1
                     };
 
-                    // Apply damage halfway the animation (only to own ants)
+                    // Apply damage halfway the animation (only to own ants
+                    // or to gain experience when attacking with own ants)
                     if let Action::Attack(entity) = ant.action {
-                        if atlas.index == animation.last_index / 2 + 1
-                            && owned_q.get(entity).is_ok()
-                        {
-                            damage_ev.send(DamageAntEv {
-                                attacker: ant_e,
-                                defender: entity,
-                            });
+                        if atlas.index == animation.last_index / 2 + 1 {
+                            if ant.is_ranged() {
+                                // Projectiles are spawned for everyone to see them fly
+                                spawn_projectile_ev.send(SpawnProjectileEv {
+                                    attacker: ant_e,
+                                    target: entity,
+                                });
+                            } else if owned_q.get(entity).is_ok() || owned_q.get(ant_e).is_ok() {
+                                damage_ev.send(DamageAntEv {
+                                    attacker: ant_e,
+                                    defender: entity,
+                                });
+                            }
                         }
                     }
                 }
@@ -127,6 +142,7 @@ pub fn resolve_digging(
     mut map: ResMut<Map>,
     mut spawn_ant_ev: EventWriter<SpawnAntEv>,
     mut play_audio_ev: EventWriter<PlayAudioEv>,
+    mut stats: ResMut<GameStats>,
     game_settings: Res<GameSettings>,
     players: Res<Players>,
     time: Res<Time>,
@@ -167,16 +183,20 @@ pub fn resolve_digging(
                 tile.terraform -= terraform;
             } else {
                 // Possibly spawn a scorpion on the newly dug tile (only for players)
-                if ants.iter().all(|(_, a)| a.team == players.main_id()) {
+                if ants.iter().all(|(_, a)| players.get(a.team).is_human()) {
                     if let Some(enemy) = match rng().random::<f32>() {
                         0.96..0.99 => Some(Ant::BlackScorpion),
                         0.99..1. => Some(Ant::YellowScorpion),
                         _ => None,
                     } {
-                        play_audio_ev.send(PlayAudioEv {
-                            name: "warning",
-                            volume: 0.5,
-                        });
+                        play_audio_ev.send(
+                            PlayAudioEv {
+                                volume: 0.5,
+                                ..PlayAudioEv::new("warning")
+                            }
+                            .with_category(AudioCategory::Alerts)
+                            .at(Map::get_coord_from_xy(tile.x, tile.y)),
+                        );
 
                         spawn_ant_ev.send(SpawnAntEv {
                             ant: AntCmp::base(&enemy),
@@ -192,6 +212,10 @@ pub fn resolve_digging(
 
                 map.find_and_replace_tile(&tile, &directions);
 
+                for team in ants.iter().map(|(_, a)| a.team).collect::<HashSet<_>>() {
+                    stats.colony(team).tiles_dug += 1;
+                }
+
                 // Set digging ants onto a new task
                 ants.iter_mut().for_each(|(_, ant)| {
                     ant.action = if matches!(ant.command, Some(Behavior::Dig(_)))
@@ -213,10 +237,13 @@ pub fn resolve_digging(
 pub fn resolve_harvesting(
     mut ant_q: Query<(&Transform, &mut AntCmp), With<Owned>>,
     mut map: ResMut<Map>,
+    mut stats: ResMut<GameStats>,
+    mut alert_ev: EventWriter<AlertEv>,
     game_settings: Res<GameSettings>,
     players: Res<Players>,
     time: Res<Time>,
 ) {
+    let mut harvested = vec![];
     for (ant_t, mut ant) in ant_q.iter_mut().filter(|(_, a)| {
         a.action == Action::Harvest
             && matches!(
@@ -243,6 +270,8 @@ pub fn resolve_harvesting(
                     })
                 .min(leaf.quantity);
 
+                let synced = leaf.synced_quantity();
+                let carry = ant.carry.leaves;
                 if ant.carry.leaves + leaves > ant.max_carry.leaves {
                     ant.carry.leaves = ant.max_carry.leaves;
                     leaf.quantity -= ant.max_carry.leaves - ant.carry.leaves;
@@ -252,8 +281,20 @@ pub fn resolve_harvesting(
                     leaf.quantity -= leaves;
                 }
 
+                stats.colony(ant.team).gathered.leaves += ant.carry.leaves - carry;
+
+                // Only send the tile to the clients when the leaf visibly changed
+                if leaf.synced_quantity() != synced {
+                    harvested.push((tile.x, tile.y));
+                }
+
                 if leaf.quantity == 0. {
                     tile.leaf = None;
+                    alert_ev.send(AlertEv::new(
+                        AlertKind::LeafDepleted,
+                        ant.team,
+                        Map::get_coord_from_xy(tile.x, tile.y),
+                    ));
                 }
             } else {
                 ant.command = None;
@@ -261,11 +302,14 @@ pub fn resolve_harvesting(
             }
         }
     }
+
+    map.changed.extend(harvested);
 }
 
 pub fn resolve_harvesting_corpse(
     mut ant_q: Query<(Entity, &mut AntCmp), With<Owned>>,
     corpse_q: Query<Entity, With<Corpse>>,
+    mut stats: ResMut<GameStats>,
     game_settings: Res<GameSettings>,
     players: Res<Players>,
     time: Res<Time>,
@@ -292,12 +336,15 @@ pub fn resolve_harvesting_corpse(
                         1.
                     };
 
+                let carry = ant.carry.nutrients;
                 if ant.carry.nutrients + nutrients > ant.max_carry.nutrients {
                     ant.carry.nutrients = ant.max_carry.nutrients;
                     ant.action = Action::Idle;
                 } else {
                     ant.carry.nutrients += nutrients;
                 }
+
+                stats.colony(ant.team).gathered.nutrients += ant.carry.nutrients - carry;
             } else {
                 ant.command = None;
                 ant.action = Action::Idle;
@@ -335,6 +382,88 @@ pub fn resolve_healing(
     }
 }
 
+pub fn resolve_aging(
+    mut ant_q: Query<&mut AntCmp, With<Owned>>,
+    game_settings: Res<GameSettings>,
+    time: Res<Time>,
+) {
+    if !game_settings.upkeep {
+        return;
+    }
+
+    for mut ant in ant_q
+        .iter_mut()
+        .filter(|a| a.health > 0. && a.action != Action::DoNothing)
+    {
+        ant.age += game_settings.speed * time.delta_secs();
+
+        // Ants that reach their lifespan die of old age
+        if ant.kind.lifespan().is_some_and(|l| ant.age >= l) {
+            ant.health = 0.;
+        }
+    }
+}
+
+pub fn resolve_upkeep(
+    mut ant_q: Query<&mut AntCmp, With<Owned>>,
+    mut players: ResMut<Players>,
+    game_settings: Res<GameSettings>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    mut elapsed: Local<Duration>,
+    time: Res<Time>,
+) {
+    if !game_settings.upkeep {
+        return;
+    }
+
+    // The upkeep is paid every UPKEEP_TIMER of game time
+    *elapsed += scale_duration(time.delta(), game_settings.speed);
+    if *elapsed < Duration::from_millis(UPKEEP_TIMER) {
+        return;
+    }
+    *elapsed -= Duration::from_millis(UPKEEP_TIMER);
+
+    let mut upkeep: HashMap<ClientId, Resources> = HashMap::new();
+    for ant in ant_q
+        .iter()
+        .filter(|a| a.kind.is_ant() && a.kind != Ant::Queen && a.health > 0.)
+    {
+        *upkeep.entry(ant.team).or_default() += ant.price * UPKEEP_FACTOR;
+    }
+
+    let mut starving = HashSet::new();
+    for (team, cost) in upkeep.iter() {
+        let main_id = players.main_id();
+        let player = players.get_mut(*team);
+
+        if player.resources >= *cost {
+            player.resources -= cost;
+        } else {
+            // Spend what's left and let the colony starve
+            player.resources = Resources::new(
+                (player.resources.leaves - cost.leaves).max(0.),
+                (player.resources.nutrients - cost.nutrients).max(0.),
+            );
+            starving.insert(*team);
+
+            if *team == main_id {
+                play_audio_ev
+                    .send(PlayAudioEv::new("warning").with_category(AudioCategory::Alerts));
+            }
+        }
+    }
+
+    for mut ant in ant_q
+        .iter_mut()
+        .filter(|a| a.kind.is_ant() && a.health > 0.)
+    {
+        ant.starving = starving.contains(&ant.team);
+        if ant.starving && ant.kind != Ant::Queen {
+            ant.health = (ant.health - STARVATION_HEALTH_LOSS * ant.max_health).max(0.);
+        }
+    }
+}
+
 pub fn resolve_pre_action(
     mut ant_q: Query<(Entity, &Transform, &mut AntCmp, Option<&Owned>)>,
     egg_q: Query<(Entity, &Transform, &Egg)>,
@@ -364,7 +493,7 @@ pub fn resolve_pre_action(
             let loc2 = map.get_loc(enemy_t);
 
             // All ants immediately attack enemies who are nearby
-            if ant.team != *enemy_team
+            if !players.are_allies(ant.team, *enemy_team)
                 && map.tile_distance(&loc1, &loc2) <= 1
                 && map.can_see(
                     &ant_t.translation,
@@ -375,6 +504,8 @@ pub fn resolve_pre_action(
             {
                 ant.command = Some(Behavior::Attack);
                 ant.action = Action::TargetedWalk(*enemy_e);
+                ant.formation_speed = None;
+                ant.formation_offset = None;
                 continue 'ant;
             }
         }
@@ -405,10 +536,12 @@ pub fn resolve_pre_action(
 pub fn resolve_death(
     mut commands: Commands,
     mut ant_q: Query<(Entity, &mut Transform, &mut AntCmp), With<Owned>>,
-    egg_q: Query<(Entity, &Egg), With<Owned>>,
+    egg_q: Query<(Entity, &Transform, &Egg), (With<Owned>, Without<AntCmp>)>,
     mut players: ResMut<Players>,
     mut selection: ResMut<AntSelection>,
+    mut stats: ResMut<GameStats>,
     mut play_audio_ev: EventWriter<PlayAudioEv>,
+    mut alert_ev: EventWriter<AlertEv>,
 ) {
     let n_queens = ant_q
         .iter()
@@ -421,6 +554,11 @@ pub fn resolve_death(
 
             commands.entity(ant_e).insert(Corpse);
             selection.0.remove(&ant_e);
+            *stats
+                .colony(ant.team)
+                .lost
+                .entry(ant.kind.clone())
+                .or_default() += 1;
 
             let mut death_time = DEATH_TIME
                 * if player.has_trait(&Trait::Corpses) {
@@ -430,7 +568,7 @@ pub fn resolve_death(
                 };
 
             if ant.kind == Ant::Queen && ant.team == players.main_id() {
-                play_audio_ev.send(PlayAudioEv::new("defeat"));
+                play_audio_ev.send(PlayAudioEv::new("defeat").with_category(AudioCategory::Alerts));
 
                 // The last queen despawns fast to not wait long for the end-game state
                 if n_queens == 0 {
@@ -445,8 +583,13 @@ pub fn resolve_death(
         }
     }
 
-    for (egg_e, egg) in &egg_q {
+    for (egg_e, egg_t, egg) in &egg_q {
         if egg.health == 0. {
+            alert_ev.send(AlertEv::new(
+                AlertKind::EggDestroyed,
+                egg.team,
+                egg_t.translation.truncate(),
+            ));
             commands.entity(egg_e).despawn_recursive();
         }
     }
@@ -455,6 +598,7 @@ pub fn resolve_death(
 pub fn resolve_attack_action(
     mut ant_q: Query<(Entity, &Transform, &Sprite, Option<&Owned>, &mut AntCmp)>,
     egg_q: Query<(Entity, &Transform, &Sprite), With<Egg>>,
+    map: Res<Map>,
     images: Res<Assets<Image>>,
     atlases: Res<Assets<TextureAtlasLayout>>,
 ) {
@@ -470,8 +614,14 @@ pub fn resolve_attack_action(
         if owned.is_some() {
             if let Action::Attack(entity) = ant.action {
                 if let Some((enemy_t, enemy_s)) = enemies.get(&entity) {
-                    if !collision((ant_t, ant_s), (enemy_t, enemy_s), &images, &atlases) {
-                        // The enemy is not adjacent anymore
+                    let in_reach = if ant.is_ranged() {
+                        in_range(ant_t, &ant, enemy_t, &map)
+                    } else {
+                        collision((ant_t, ant_s), (enemy_t, enemy_s), &images, &atlases)
+                    };
+
+                    if !in_reach {
+                        // The enemy is not adjacent (or in range) anymore
                         ant.action = Action::TargetedWalk(entity);
                     }
                 } else {
@@ -599,7 +749,7 @@ pub fn resolve_idle_action(
                             .map(|(e, t, egg)| (e, egg.team, &t.translation)),
                     )
                     .filter(|(_, team, t)| {
-                        ant.team != *team
+                        !players.are_allies(ant.team, *team)
                             && map.can_see(&ant_t.translation, t, &player, &players.get(*team))
                     })
                     .collect();
@@ -764,6 +914,7 @@ pub fn resolve_targeted_walk_action(
     for (_, mut ant_t, ant_s, mut ant) in ant_q.iter_mut() {
         if let Action::TargetedWalk(entity) = ant.action {
             if let Ok((_, target_t, target_s, team)) = sprite_q.get(entity) {
+                let allied = players.are_allies(team.0, ant.team);
                 let player = players.get_mut(ant.team);
 
                 let target_t = target_t.compute_transform();
@@ -771,24 +922,60 @@ pub fn resolve_targeted_walk_action(
                 let current_loc = map.get_loc(&ant_t.translation);
                 let target_loc = map.get_loc(&target_t.translation);
 
-                if !collision((&ant_t, ant_s), (&target_t, target_s), &images, &atlases)
+                // Ranged ants stop walking when the enemy is within range
+                let ranged = ant.is_ranged()
+                    && team.0 != ant.team
+                    && corpse_q.get(entity).is_err()
+                    && !matches!(
+                        ant.get_behavior(),
+                        Behavior::Harvest(_)
+                            | Behavior::HarvestCorpse(_)
+                            | Behavior::HarvestRandom
+                            | Behavior::Heal(_)
+                    )
+                    && in_range(&ant_t, &ant, &target_t, &map);
+
+                // Ants in a formation walk at the speed of the slowest member
+                let speed = ant.formation_speed.map_or(ant.speed, |s| s.min(ant.speed))
+                    * game_settings.speed
+                    * time.delta_secs().min(CAPPED_DELTA_SECS_SPEED)
+                    * if ant.kind.can_fly() {
+                        FLY_SPEED_FACTOR
+                    } else {
+                        1.
+                    }
+                    * if ant.starving {
+                        STARVATION_SPEED_FACTOR
+                    } else {
+                        1.
+                    }
+                    * if player.has_trait(&Trait::Haste) {
+                        HASTE_SPEED_FACTOR
+                    } else {
+                        1.
+                    };
+
+                // Ants protecting an ant in formation walk to their slot next to it
+                let slot = ant
+                    .formation_offset
+                    .filter(|_| ant.command == Some(Behavior::ProtectAnt(entity)))
+                    .map(|offset| map.get_loc(&(target_t.translation + offset.extend(0.))))
+                    .filter(|l| map.is_walkable(l));
+
+                if let Some(slot) = slot {
+                    if current_loc != slot {
+                        walk(&mut ant_t, &slot, speed, &mut map, &game_settings, &time);
+                    } else {
+                        // Ant reached its slot -> protect the ant from there
+                        ant.formation_speed = None;
+                        ant.formation_offset = None;
+                        ant.action = Action::Idle;
+                    }
+                } else if !ranged
+                    && !collision((&ant_t, ant_s), (&target_t, target_s), &images, &atlases)
                     && current_loc != target_loc
                 {
                     // The ant isn't adjacent to the target yet -> keep walking
-                    let speed = ant.speed
-                        * game_settings.speed
-                        * time.delta_secs().min(CAPPED_DELTA_SECS_SPEED)
-                        * if ant.kind.can_fly() {
-                            FLY_SPEED_FACTOR
-                        } else {
-                            1.
-                        }
-                        * if player.has_trait(&Trait::Haste) {
-                            HASTE_SPEED_FACTOR
-                        } else {
-                            1.
-                        };
-
                     walk(
                         &mut ant_t,
                         &target_loc,
@@ -797,11 +984,15 @@ pub fn resolve_targeted_walk_action(
                         &game_settings,
                         &time,
                     );
-                } else if team.0 == ant.team && corpse_q.get(entity).is_err() {
-                    if matches!(
-                        ant.get_behavior(),
-                        Behavior::Harvest(_) | Behavior::HarvestCorpse(_) | Behavior::HarvestRandom
-                    ) {
+                } else if allied && corpse_q.get(entity).is_err() {
+                    if team.0 == ant.team
+                        && matches!(
+                            ant.get_behavior(),
+                            Behavior::Harvest(_)
+                                | Behavior::HarvestCorpse(_)
+                                | Behavior::HarvestRandom
+                        )
+                    {
                         // Ant reached the queen -> deposit food
                         player.resources += &ant.carry;
                         ant.carry = Resources::default();
@@ -856,9 +1047,15 @@ pub fn resolve_walk_action(
 
             let current_loc = map.get_loc(&ant_t.translation);
             if current_loc != target_loc {
-                let speed = ant.speed
+                // Ants in a formation walk at the speed of the slowest member
+                let speed = ant.formation_speed.map_or(ant.speed, |s| s.min(ant.speed))
                     * game_settings.speed
                     * time.delta_secs().min(CAPPED_DELTA_SECS_SPEED)
+                    * if ant.starving {
+                        STARVATION_SPEED_FACTOR
+                    } else {
+                        1.
+                    }
                     * if player.has_trait(&Trait::Haste) {
                         HASTE_SPEED_FACTOR
                     } else {
@@ -874,6 +1071,9 @@ pub fn resolve_walk_action(
                     &time,
                 );
             } else {
+                ant.formation_speed = None;
+                ant.formation_offset = None;
+
                 // If the ant reached a hole, hide it
                 if let Some(Behavior::ProtectLoc(loc)) = ant.command {
                     if ant.kind != Ant::Queen
@@ -923,6 +1123,40 @@ pub fn resolve_walk_action(
     }
 }
 
+/// Apply the chosen palette to the selection rings and health bars
+pub fn update_team_colors(
+    ant_q: Query<(Entity, &AntCmp)>,
+    egg_q: Query<(Entity, &Egg)>,
+    mut health_q: Query<&mut Sprite, With<AntHealthCmp>>,
+    ring_q: Query<&MeshMaterial2d<ColorMaterial>, With<SelectedCmp>>,
+    children_q: Query<&Children>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    game_settings: Res<GameSettings>,
+    mut palette: Local<Option<Palette>>,
+) {
+    if *palette == Some(game_settings.palette) {
+        return;
+    }
+
+    let ants = ant_q
+        .iter()
+        .chain(egg_q.iter().map(|(e, egg)| (e, &egg.ant)));
+
+    for (entity, ant) in ants {
+        for child in children_q.iter_descendants(entity) {
+            if let Ok(mut health_s) = health_q.get_mut(child) {
+                health_s.color = ant.health_color(&game_settings.palette);
+            }
+
+            if let Some(material) = ring_q.get(child).ok().and_then(|m| materials.get_mut(&m.0)) {
+                material.color = ant.ring_color(&game_settings.palette);
+            }
+        }
+    }
+
+    *palette = Some(game_settings.palette);
+}
+
 pub fn update_ant_components(
     ant_q: Query<
         (Entity, &Transform, &AntCmp),
@@ -1040,13 +1274,43 @@ pub fn update_ant_components(
     }
 }
 
+pub fn update_rank_insignia(
+    ant_q: Query<(Entity, &Transform, &AntCmp)>,
+    mut rank_q: Query<(&mut Transform, &mut Visibility, &AntRankCmp), Without<AntCmp>>,
+    children_q: Query<&Children>,
+) {
+    for (ant_e, ant_t, ant) in ant_q.iter() {
+        let rank = ant.rank();
+        for child in children_q.iter_descendants(ant_e) {
+            if let Ok((mut rank_t, mut rank_v, AntRankCmp(i))) = rank_q.get_mut(child) {
+                if ant.health > 0. && *i < rank {
+                    *rank_v = Visibility::Inherited;
+
+                    // Stack the stripes on the left of the health bar
+                    let offset = Vec3::new(
+                        -ant.size().x * 0.5,
+                        ant.size().y * (0.5 - 0.06 * *i as f32),
+                        0.1,
+                    );
+                    rank_t.translation = ant_t.rotation.inverse() * offset;
+                } else {
+                    *rank_v = Visibility::Hidden;
+                }
+            }
+        }
+    }
+}
+
 pub fn queue_ants_keyboard(
     keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
     players: Res<Players>,
     mut queue_ant_ev: EventWriter<QueueAntEv>,
 ) {
     for ant in Ant::iter().filter(|a| players.main().has_ant(a)) {
-        if matches!(AntCmp::base(&ant).key, Some(key) if keyboard.just_pressed(key)) {
+        if AntCmp::base(&ant).key.is_some()
+            && keymap.just_pressed(&keyboard, &InputAction::QueueAnt(ant.clone()))
+        {
             queue_ant_ev.send(QueueAntEv {
                 id: players.main_id(),
                 ant,
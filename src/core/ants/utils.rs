@@ -10,14 +10,34 @@ use std::f32::consts::PI;
 pub fn transform_ant(transform: &mut Transform, ant: &mut AntCmp, new_ant: &AntCmp) {
     transform.scale = Vec3::splat(new_ant.scale);
 
+    // Veterans keep their rank bonuses
+    let mut new_ant = AntCmp {
+        experience: ant.experience,
+        kills: ant.kills,
+        age: ant.age,
+        starving: ant.starving,
+        ..new_ant.clone()
+    };
+    (0..new_ant.rank()).for_each(|_| new_ant.promote());
+
     let max_health = new_ant.max_health.max(ant.max_health);
     *ant = AntCmp {
         health: (ant.health / ant.max_health) * max_health, // Keep the same health ratio
         max_health,
-        ..new_ant.clone()
+        ..new_ant
     };
 }
 
+/// Whether the target is within range of a ranged ant and there is no soil in between
+pub fn in_range(ant_t: &Transform, ant: &AntCmp, target_t: &Transform, map: &Map) -> bool {
+    ant_t
+        .translation
+        .truncate()
+        .distance(target_t.translation.truncate())
+        <= ant.range
+        && map.is_clear_shot(&ant_t.translation, &target_t.translation)
+}
+
 pub fn walk(
     ant_t: &mut Transform,
     target_loc: &Loc,
@@ -1,4 +1,6 @@
 use crate::core::ants::components::Ant;
+use crate::core::dedicated::DedicatedServer;
+use crate::core::palette::swapped_image;
 use bevy::asset::{AssetServer, Handle};
 use bevy::prelude::*;
 use bevy_kira_audio::AudioSource;
@@ -62,14 +64,25 @@ impl FromWorld for WorldAssets {
     fn from_world(world: &mut World) -> Self {
         let assets = world.get_resource::<AssetServer>().unwrap();
 
-        let audio = HashMap::from([
-            ("button", assets.load("audio/button.ogg")),
-            ("message", assets.load("audio/message.ogg")),
-            ("warning", assets.load("audio/warning.ogg")),
-            ("error", assets.load("audio/error.ogg")),
-            ("defeat", assets.load("audio/defeat.ogg")),
-            ("music", assets.load("audio/music.ogg")),
-        ]);
+        // The dedicated server runs without audio plugin
+        let audio = if world.contains_resource::<DedicatedServer>() {
+            HashMap::new()
+        } else {
+            HashMap::from([
+                ("button", assets.load("audio/button.ogg")),
+                ("message", assets.load("audio/message.ogg")),
+                ("warning", assets.load("audio/warning.ogg")),
+                ("error", assets.load("audio/error.ogg")),
+                ("defeat", assets.load("audio/defeat.ogg")),
+                ("dig", assets.load("audio/dig.wav")),
+                ("harvest", assets.load("audio/harvest.wav")),
+                ("bite", assets.load("audio/bite.wav")),
+                ("death", assets.load("audio/death.wav")),
+                ("hatch", assets.load("audio/hatch.wav")),
+                ("music", assets.load("audio/music.ogg")),
+                ("music-intensity", assets.load("audio/music-intensity.wav")),
+            ])
+        };
 
         let fonts = HashMap::from([
             ("bold", assets.load("fonts/FiraSans-Bold.ttf")),
@@ -132,6 +145,7 @@ impl FromWorld for WorldAssets {
             ("battle", assets.load("images/traits/battle.png")),
             ("cannibal", assets.load("images/traits/cannibal.png")),
             ("corpses", assets.load("images/traits/corpses.png")),
+            ("defense", assets.load("images/traits/defense.png")),
             ("double-queen", assets.load("images/traits/two-queens.png")),
             ("eggs", assets.load("images/traits/eggs.png")),
             ("harvest", assets.load("images/traits/harvest.png")),
@@ -162,10 +176,14 @@ impl FromWorld for WorldAssets {
                 for ant_c in ant.colors() {
                     let name = Box::leak(Box::new(ant_c.atlas(&animation))).as_str();
 
-                    images.insert(
-                        name,
-                        assets.load(&format!("images/ants/{}/{}.png", ant_c.folder(), name)),
-                    );
+                    // Colors without sprites of their own are swapped at runtime
+                    let image = if ant_c.color.map_or(true, |c| c.has_sprites()) {
+                        assets.load(&format!("images/ants/{}/{}.png", ant_c.folder(), name))
+                    } else {
+                        swapped_image(name)
+                    };
+
+                    images.insert(name, image);
                 }
             }
         }
@@ -1,24 +1,98 @@
+use crate::core::ants::components::{Action, AntCmp, Egg};
 use crate::core::assets::WorldAssets;
-use crate::core::constants::{NORMAL_BUTTON_COLOR, PRESSED_BUTTON_COLOR};
+use crate::core::camera::MainCamera;
+use crate::core::constants::*;
 use crate::core::game_settings::GameSettings;
+use crate::core::keymap::{InputAction, Keymap};
 use crate::core::menu::settings::SettingsBtn;
+use crate::core::player::Players;
 use crate::core::states::AudioState;
 use bevy::prelude::*;
+use bevy::utils::hashbrown::{HashMap, HashSet};
 use bevy_kira_audio::prelude::*;
+use rand::{rng, Rng};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use strum_macros::EnumIter;
+
+/// Categories of sounds with their own volume in the mixer
+#[derive(EnumIter, Clone, Copy, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum AudioCategory {
+    Music,
+    Ui,
+    Combat,
+    Ambient,
+    Alerts,
+}
+
+impl AudioCategory {
+    /// Whether the sounds are throttled to not overlap when many play at once
+    pub fn is_pooled(&self) -> bool {
+        matches!(self, AudioCategory::Combat | AudioCategory::Ambient)
+    }
+}
+
+/// Volume (in percentage) of every category of sounds
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Mixer(pub HashMap<AudioCategory, u8>);
+
+impl Mixer {
+    pub fn level(&self, category: &AudioCategory) -> u8 {
+        self.0.get(category).copied().unwrap_or(100)
+    }
+
+    pub fn volume(&self, category: &AudioCategory) -> f64 {
+        self.level(category) as f64 / 100.
+    }
+}
 
 #[derive(Event)]
 pub struct PlayAudioEv {
     pub name: &'static str,
     pub volume: f64,
+    pub rate: f64, // Playback rate (changes the pitch of the sound)
+    pub category: AudioCategory,
+    pub position: Option<Vec2>, // Location on the map (None for sounds heard everywhere)
 }
 
 impl PlayAudioEv {
     pub fn new(name: &'static str) -> Self {
-        Self { name, volume: 1. }
+        Self {
+            name,
+            volume: 1.,
+            rate: 1.,
+            category: AudioCategory::Ui,
+            position: None,
+        }
+    }
+
+    pub fn with_category(mut self, category: AudioCategory) -> Self {
+        self.category = category;
+        self
+    }
+
+    /// Play the sound at a location of the map
+    pub fn at(mut self, position: Vec2) -> Self {
+        self.position = Some(position);
+        self
     }
 }
 
+/// Music playing in the background, with the layer that fades in during fights
+#[derive(Resource)]
+pub struct MusicInstance {
+    pub music: Handle<AudioInstance>,
+    pub intensity: Handle<AudioInstance>,
+}
+
+/// How intense the music plays (0 when calm, 1 during large fights)
+#[derive(Resource, Default)]
+pub struct MusicIntensity(pub f32);
+
+/// Times at which the pooled sounds started playing
+#[derive(Resource, Default)]
+pub struct AudioPool(pub HashMap<&'static str, Vec<f32>>);
+
 #[derive(Component)]
 pub struct MusicBtnCmp;
 
@@ -46,15 +120,200 @@ pub fn setup_music_btn(mut commands: Commands, assets: Local<WorldAssets>) {
         });
 }
 
-pub fn play_music(assets: Local<WorldAssets>, audio: Res<Audio>) {
-    audio
+/// Apply the audio setting of the last session
+pub fn restore_audio(
+    game_settings: Res<GameSettings>,
+    mut change_audio_ev: EventWriter<ChangeAudioEv>,
+) {
+    change_audio_ev.send(ChangeAudioEv(Some(game_settings.audio)));
+}
+
+pub fn play_music(
+    mut commands: Commands,
+    game_settings: Res<GameSettings>,
+    assets: Local<WorldAssets>,
+    audio: Res<Audio>,
+) {
+    let music = audio
         .play(assets.audio("music"))
         .fade_in(AudioTween::new(
             Duration::from_secs(2),
             AudioEasing::OutPowi(2),
         ))
-        .with_volume(0.03)
-        .looped();
+        .with_volume(MUSIC_VOLUME * game_settings.mixer.volume(&AudioCategory::Music))
+        .looped()
+        .handle();
+
+    // The intensity layer plays silently until there is a fight
+    let intensity = audio
+        .play(assets.audio("music-intensity"))
+        .with_volume(0.)
+        .looped()
+        .handle();
+
+    commands.insert_resource(MusicInstance { music, intensity });
+}
+
+/// Follow the number of ants of the player that are fighting
+pub fn update_music_intensity(
+    ant_q: Query<&AntCmp>,
+    players: Option<Res<Players>>,
+    mut intensity: ResMut<MusicIntensity>,
+    time: Res<Time<Real>>,
+) {
+    let fighting = players.map_or(0, |players| {
+        ant_q
+            .iter()
+            .filter(|a| {
+                matches!(a.action, Action::Attack(_))
+                    && players.are_allies(a.team, players.main_id())
+            })
+            .count()
+    });
+
+    // Move slowly towards the target to not jump between layers
+    let target = (fighting as f32 / FIGHT_INTENSITY_ANTS).min(1.);
+    let step = MUSIC_INTENSITY_SPEED * time.delta_secs();
+    let new = intensity.0 + (target - intensity.0).clamp(-step, step);
+
+    if (new - intensity.0).abs() > f32::EPSILON {
+        intensity.0 = new;
+    }
+}
+
+/// Apply the volume of the mixer and the intensity layer to the music that is playing
+pub fn update_music(
+    game_settings: Res<GameSettings>,
+    intensity: Res<MusicIntensity>,
+    music: Option<Res<MusicInstance>>,
+    mut instances: ResMut<Assets<AudioInstance>>,
+) {
+    if game_settings.is_changed() || intensity.is_changed() {
+        let Some(music) = music else {
+            return;
+        };
+
+        let volume = MUSIC_VOLUME * game_settings.mixer.volume(&AudioCategory::Music);
+
+        if let Some(instance) = instances.get_mut(&music.music) {
+            instance.set_volume(volume, AudioTween::default());
+        }
+
+        if let Some(instance) = instances.get_mut(&music.intensity) {
+            instance.set_volume(
+                volume * MUSIC_INTENSITY_VOLUME * intensity.0 as f64,
+                AudioTween::default(),
+            );
+        }
+    }
+}
+
+/// Last known state of an egg on the map
+pub struct SeenEgg {
+    position: Vec2,
+    visible: bool,
+    hatching: bool,
+}
+
+/// Make the noises of the colonies from the state of the ants, which every player
+/// receives (also the clients). Only what is visible for the player is heard
+pub fn play_ant_sounds(
+    ant_q: Query<(Entity, &Transform, &Visibility, &AntCmp)>,
+    egg_q: Query<(Entity, &Transform, &Visibility, &Egg), Without<AntCmp>>,
+    target_q: Query<(&Transform, &Visibility)>,
+    mut removed_eggs: RemovedComponents<Egg>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    game_settings: Res<GameSettings>,
+    time: Res<Time>,
+    mut dying: Local<HashSet<Entity>>,
+    mut eggs: Local<HashMap<Entity, SeenEgg>>,
+) {
+    let delta = game_settings.speed * time.delta_secs();
+    let mut play = |name, volume, rate, category, position: Vec2| {
+        play_audio_ev.send(
+            PlayAudioEv {
+                volume,
+                rate,
+                ..PlayAudioEv::new(name)
+            }
+            .with_category(category)
+            .at(position),
+        );
+    };
+
+    let mut now_dying = HashSet::new();
+    for (ant_e, ant_t, ant_v, ant) in &ant_q {
+        let visible = *ant_v != Visibility::Hidden;
+        let pos = ant_t.translation.truncate();
+
+        match ant.action {
+            Action::Attack(entity) if visible => {
+                if rng().random::<f32>() < ATTACK_SOUND_CHANCE * delta {
+                    // Ranged ants are heard where they hit
+                    let hit = if ant.is_ranged() {
+                        target_q
+                            .get(entity)
+                            .ok()
+                            .filter(|(_, v)| **v != Visibility::Hidden)
+                            .map(|(t, _)| t.translation.truncate())
+                    } else {
+                        Some(pos)
+                    };
+
+                    if let Some(hit) = hit {
+                        play("bite", 0.3, 1., AudioCategory::Combat, hit);
+                    }
+                }
+            }
+            Action::Dig(_) if visible => {
+                if rng().random::<f32>() < DIG_SOUND_CHANCE * delta {
+                    play("dig", 0.4, 1., AudioCategory::Ambient, pos);
+                }
+            }
+            Action::Harvest if visible => {
+                if rng().random::<f32>() < HARVEST_SOUND_CHANCE * delta {
+                    play("harvest", 0.3, 1., AudioCategory::Ambient, pos);
+                }
+            }
+            Action::Die(_) => {
+                if !dying.contains(&ant_e) && visible {
+                    // Monsters sound deeper than ants
+                    let rate = if ant.kind.is_ant() { 1. } else { 0.7 };
+                    play("death", 0.4, rate, AudioCategory::Combat, pos);
+                }
+
+                now_dying.insert(ant_e);
+            }
+            _ => (),
+        }
+    }
+
+    *dying = now_dying;
+
+    // Eggs that are gone either hatched or were destroyed
+    for egg_e in removed_eggs.read() {
+        if let Some(egg) = eggs.get(&egg_e).filter(|e| e.visible) {
+            if egg.hatching {
+                play("hatch", 0.4, 1., AudioCategory::Ambient, egg.position);
+            } else {
+                play("death", 0.4, 1.3, AudioCategory::Combat, egg.position);
+            }
+        }
+    }
+
+    *eggs = egg_q
+        .iter()
+        .map(|(egg_e, egg_t, egg_v, egg)| {
+            (
+                egg_e,
+                SeenEgg {
+                    position: egg_t.translation.truncate(),
+                    visible: *egg_v != Visibility::Hidden,
+                    hatching: egg.timer.remaining_secs() < HATCH_SOUND_MARGIN,
+                },
+            )
+        })
+        .collect();
 }
 
 pub fn change_audio_event(
@@ -115,22 +374,86 @@ pub fn change_audio_event(
 
 pub fn toggle_music_keyboard(
     keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
     mut change_audio_ev: EventWriter<ChangeAudioEv>,
 ) {
-    if keyboard.just_pressed(KeyCode::KeyM) {
+    if keymap.just_pressed(&keyboard, &InputAction::ToggleMusic) {
         change_audio_ev.send(ChangeAudioEv(None));
     }
 }
 
+/// Volume and panning of a sound depending on where it happens relative to the camera
+fn spatialize(
+    position: Vec2,
+    camera_t: &Transform,
+    projection: &OrthographicProjection,
+) -> (f64, f64) {
+    let half_view = (projection.area.max - projection.area.min) / 2.;
+    let offset = position - camera_t.translation.truncate();
+
+    // Sounds inside the view play at full volume and fade out with the distance to it
+    let distance = ((offset.abs() - half_view).max(Vec2::ZERO) / half_view).length();
+    let mut volume = (1. - distance / AUDIO_FALLOFF).max(0.);
+
+    // The further the camera is zoomed out, the quieter the sounds
+    volume *= 1. - ZOOM_ATTENUATION * (projection.scale - MIN_ZOOM) / (MAX_ZOOM - MIN_ZOOM);
+
+    // Sounds to the left of the camera play in the left speaker and vice versa
+    let panning = 0.5 + 0.5 * MAX_PANNING * (offset.x / half_view.x).clamp(-1., 1.);
+
+    (volume as f64, panning as f64)
+}
+
 pub fn play_audio_event(
     mut ev: EventReader<PlayAudioEv>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    game_settings: Res<GameSettings>,
     audio_state: Res<State<AudioState>>,
+    mut pool: ResMut<AudioPool>,
     audio: Res<Audio>,
     assets: Local<WorldAssets>,
+    time: Res<Time<Real>>,
 ) {
-    if *audio_state.get() != AudioState::Mute {
-        for PlayAudioEv { name, volume } in ev.read() {
-            audio.play(assets.audio(name)).with_volume(*volume);
+    if *audio_state.get() == AudioState::Mute {
+        ev.clear();
+        return;
+    }
+
+    let mut sounds = ev
+        .read()
+        .filter_map(|ev| {
+            let (distance_volume, panning) = match (ev.position, camera_q.get_single()) {
+                (Some(pos), Ok((camera_t, projection))) => spatialize(pos, camera_t, projection),
+                _ => (1., 0.5),
+            };
+
+            let volume = ev.volume * distance_volume * game_settings.mixer.volume(&ev.category);
+            (volume > 0.).then_some((ev, volume, panning))
+        })
+        .collect::<Vec<_>>();
+
+    // The loudest sounds (closest to the camera) get the free places of the pool
+    sounds.sort_by(|(_, v1, _), (_, v2, _)| v2.total_cmp(v1));
+
+    let now = time.elapsed_secs();
+    for (ev, volume, panning) in sounds {
+        if ev.category.is_pooled() {
+            let started = pool.0.entry(ev.name).or_default();
+            started.retain(|t| now - t < SFX_DURATION);
+
+            if started.len() >= MAX_SFX_INSTANCES
+                || started.last().is_some_and(|t| now - t < SFX_INTERVAL)
+            {
+                continue;
+            }
+
+            started.push(now);
         }
+
+        audio
+            .play(assets.audio(ev.name))
+            .with_volume(volume)
+            .with_playback_rate(ev.rate)
+            .with_panning(panning);
     }
 }
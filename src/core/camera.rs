@@ -1,13 +1,29 @@
-use crate::core::constants::{LERP_FACTOR, MAX_ZOOM, MIN_ZOOM, ZOOM_FACTOR};
+use crate::core::alerts::Alerts;
+use crate::core::ants::components::{Ant, AntCmp};
+use crate::core::ants::selection::AntSelection;
+use crate::core::constants::*;
+use crate::core::game_settings::GameSettings;
+use crate::core::keymap::{InputAction, Keymap};
 use crate::core::map::map::Map;
+use crate::core::player::Players;
 use bevy::input::mouse::{MouseMotion, MouseWheel};
 use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
 use bevy::window::SystemCursorIcon;
 use bevy::winit::cursor::CursorIcon;
 
 #[derive(Component)]
 pub struct MainCamera;
 
+/// Camera positions saved by the player and the ant the camera follows
+#[derive(Resource, Default)]
+pub struct CameraControl {
+    /// Position and zoom of every bookmark
+    pub bookmarks: HashMap<u8, (Vec2, f32)>,
+
+    pub follow: Option<Entity>,
+}
+
 pub fn clamp_to_rect(pos: Vec2, view_size: Vec2, bounds: Rect) -> Vec2 {
     let min_x = bounds.min.x + view_size.x * 0.5;
     let min_y = bounds.min.y + view_size.y * 0.5;
@@ -24,6 +40,13 @@ pub fn clamp_to_rect(pos: Vec2, view_size: Vec2, bounds: Rect) -> Vec2 {
     }
 }
 
+/// Center the camera on a position, without leaving the map's bounds
+pub fn center_camera(camera_t: &mut Transform, projection: &OrthographicProjection, pos: Vec2) {
+    let view_size = projection.area.max - projection.area.min;
+    camera_t.translation =
+        clamp_to_rect(pos, view_size, Map::MAP_VIEW).extend(camera_t.translation.z);
+}
+
 pub fn setup_camera(mut commands: Commands) {
     commands.spawn((
         Camera2d,
@@ -47,6 +70,8 @@ pub fn move_camera(
     mut scroll_ev: EventReader<MouseWheel>,
     mut motion_ev: EventReader<MouseMotion>,
     mouse: Res<ButtonInput<MouseButton>>,
+    control: Res<CameraControl>,
+    game_settings: Res<GameSettings>,
     window: Single<(Entity, &Window)>,
 ) {
     let (camera, global_t, mut camera_t, mut projection) = camera_q.single_mut();
@@ -95,6 +120,23 @@ pub fn move_camera(
             .insert(Into::<CursorIcon>::into(SystemCursorIcon::Default));
     }
 
+    // Move the camera when the cursor touches the window's border
+    if game_settings.edge_scrolling && control.follow.is_none() {
+        if let Some(cursor) = window.cursor_position() {
+            let step = 10. * projection.scale;
+            if cursor.x <= EDGE_SCROLL_MARGIN {
+                camera_t.translation.x -= step;
+            } else if cursor.x >= window.width() - EDGE_SCROLL_MARGIN {
+                camera_t.translation.x += step;
+            }
+            if cursor.y <= EDGE_SCROLL_MARGIN {
+                camera_t.translation.y += step;
+            } else if cursor.y >= window.height() - EDGE_SCROLL_MARGIN {
+                camera_t.translation.y -= step;
+            }
+        }
+    }
+
     let mut position = camera_t.translation.truncate();
 
     // Compute the camera's current view size based on projection
@@ -112,25 +154,101 @@ pub fn move_camera(
 
 pub fn move_camera_keyboard(
     keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
     mut camera_q: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
 ) {
     let (mut camera_t, projection) = camera_q.single_mut();
 
     let transform = 10. * projection.scale;
-    if keyboard.pressed(KeyCode::KeyA) {
+    if keymap.pressed(&keyboard, &InputAction::CameraLeft) {
         camera_t.translation.x -= transform;
     }
-    if keyboard.pressed(KeyCode::KeyD) {
+    if keymap.pressed(&keyboard, &InputAction::CameraRight) {
         camera_t.translation.x += transform;
     }
-    if keyboard.pressed(KeyCode::KeyW) {
+    if keymap.pressed(&keyboard, &InputAction::CameraUp) {
         camera_t.translation.y += transform;
     }
-    if keyboard.pressed(KeyCode::KeyS) {
+    if keymap.pressed(&keyboard, &InputAction::CameraDown) {
         camera_t.translation.y -= transform;
     }
 }
 
+pub fn camera_shortcuts(
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+    ant_q: Query<(Entity, &Transform, &AntCmp), Without<MainCamera>>,
+    mut control: ResMut<CameraControl>,
+    players: Res<Players>,
+    selection: Res<AntSelection>,
+    alerts: Res<Alerts>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
+) {
+    let (mut camera_t, mut projection) = camera_q.single_mut();
+
+    // Ctrl+key saves the bookmark, the key alone moves the camera to it
+    for i in 0..CAMERA_BOOKMARKS {
+        if keymap.just_pressed(&keyboard, &InputAction::CameraBookmark(i)) {
+            if keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+                control
+                    .bookmarks
+                    .insert(i, (camera_t.translation.truncate(), projection.scale));
+            } else if let Some((pos, scale)) = control.bookmarks.get(&i).copied() {
+                projection.scale = scale;
+                center_camera(&mut camera_t, &projection, pos);
+                control.follow = None;
+            }
+        }
+    }
+
+    if keymap.just_pressed(&keyboard, &InputAction::FollowSelection) {
+        control.follow = if control.follow.is_some() {
+            None
+        } else {
+            selection.0.iter().min().copied()
+        };
+    }
+
+    if keymap.just_pressed(&keyboard, &InputAction::JumpToQueen) {
+        if let Some((_, queen_t, _)) = ant_q
+            .iter()
+            .find(|(_, _, a)| a.kind == Ant::Queen && a.team == players.main_id() && a.health > 0.)
+        {
+            center_camera(&mut camera_t, &projection, queen_t.translation.truncate());
+            control.follow = None;
+        }
+    }
+
+    if keymap.just_pressed(&keyboard, &InputAction::JumpToAlert) {
+        if let Some((_, alert)) = alerts.feed.last() {
+            center_camera(&mut camera_t, &projection, alert.pos);
+            control.follow = None;
+        }
+    }
+
+    // Moving the camera manually stops following the ant
+    if [
+        InputAction::CameraUp,
+        InputAction::CameraLeft,
+        InputAction::CameraDown,
+        InputAction::CameraRight,
+    ]
+    .iter()
+    .any(|a| keymap.pressed(&keyboard, a))
+    {
+        control.follow = None;
+    }
+
+    if let Some(entity) = control.follow {
+        match ant_q.get(entity) {
+            Ok((_, ant_t, ant)) if ant.health > 0. => {
+                center_camera(&mut camera_t, &projection, ant_t.translation.truncate());
+            }
+            _ => control.follow = None,
+        }
+    }
+}
+
 pub fn reset_camera(
     mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
 ) {
@@ -0,0 +1,427 @@
+use crate::core::ants::selection::spawn_pin;
+use crate::core::assets::WorldAssets;
+use crate::core::audio::{AudioCategory, PlayAudioEv};
+use crate::core::constants::*;
+use crate::core::game_settings::GameSettings;
+use crate::core::locale::Locale;
+use crate::core::map::loc::Loc;
+use crate::core::map::map::Map;
+use crate::core::map::systems::MapCmp;
+use crate::core::map::ui::utils::add_text;
+use crate::core::menu::lobby::Lobby;
+use crate::core::menu::settings::AntColor;
+use crate::core::network::{ClientMessage, ClientSendMessage, ServerMessage, ServerSendMessage};
+use crate::core::player::Players;
+use crate::utils::NameFromEnum;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy_renet::renet::{ClientId, RenetServer};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PingKind {
+    AttackHere,
+    Help,
+    Danger,
+}
+
+/// A chat message or a map ping sent by a player
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ChatEntry {
+    Message {
+        id: ClientId,
+        name: String,
+        team: bool, // Only sent to the allies of the player
+        text: String,
+    },
+    Ping {
+        id: ClientId,
+        name: String,
+        color: AntColor,
+        kind: PingKind,
+        loc: Loc,
+    },
+}
+
+impl ChatEntry {
+    pub fn sender(&self) -> ClientId {
+        match self {
+            ChatEntry::Message { id, .. } | ChatEntry::Ping { id, .. } => *id,
+        }
+    }
+
+    /// Rebuild an entry received from a client with the name and color the host knows
+    /// of the sender, a valid text and a location on the map (None if it's not valid)
+    pub fn sanitize(self, players: &Players) -> Option<Self> {
+        let player = players.get(self.sender());
+
+        match self {
+            ChatEntry::Message { id, team, text, .. } => {
+                let text = text
+                    .chars()
+                    .filter(|c| !c.is_control())
+                    .take(MAX_CHAT_LENGTH)
+                    .collect::<String>()
+                    .trim()
+                    .to_string();
+
+                (!text.is_empty()).then(|| ChatEntry::Message {
+                    id,
+                    name: player.name.clone(),
+                    team,
+                    text,
+                })
+            }
+            ChatEntry::Ping { id, kind, loc, .. } => Map::contains(&loc).then(|| ChatEntry::Ping {
+                id,
+                name: player.name.clone(),
+                color: player.color,
+                kind,
+                loc,
+            }),
+        }
+    }
+
+    /// Whether the player can see the entry (team messages and pings only reach allies)
+    pub fn is_visible(&self, players: &Players, id: ClientId) -> bool {
+        match self {
+            ChatEntry::Message { team: false, .. } => true,
+            _ => players.are_allies(self.sender(), id),
+        }
+    }
+
+    /// Connected clients (other than the sender) the host forwards the entry to.
+    /// Observers only read the messages to everyone
+    pub fn recipients(&self, players: &Players, observers: &[ClientId]) -> Vec<ClientId> {
+        let observers = match self {
+            ChatEntry::Message { team: false, .. } => observers,
+            _ => &[],
+        };
+
+        players
+            .0
+            .iter()
+            .filter(|p| {
+                p.id != 0
+                    && p.id != self.sender()
+                    && p.is_human()
+                    && !p.is_ai()
+                    && self.is_visible(players, p.id)
+            })
+            .map(|p| p.id)
+            .chain(observers.iter().copied())
+            .collect()
+    }
+
+    /// Text and color of the entry in the chat history
+    fn to_line(&self, locale: &Locale) -> (String, Color) {
+        match self {
+            ChatEntry::Message {
+                name, team, text, ..
+            } => {
+                if *team {
+                    (
+                        format!("[{}] {name}: {text}", locale.get("chat.team")),
+                        Color::srgb(0.6, 0.9, 0.6),
+                    )
+                } else {
+                    (format!("{name}: {text}"), Color::WHITE)
+                }
+            }
+            ChatEntry::Ping { name, kind, .. } => (
+                format!(
+                    "{name}: {}",
+                    locale.get(&format!("ping.{}", kind.to_snake()))
+                ),
+                Color::srgb(1., 0.85, 0.3),
+            ),
+        }
+    }
+}
+
+/// Chat messages and pings received during the match with the time they arrived
+#[derive(Resource, Default)]
+pub struct ChatHistory(pub Vec<(f32, ChatEntry)>);
+
+/// State of the chat box
+#[derive(Resource, Default)]
+pub struct ChatInput {
+    pub open: bool,
+    pub team: bool,
+    pub text: String,
+    pub scroll: usize, // Lines scrolled back in the history
+}
+
+#[derive(Event)]
+pub struct ChatEv(pub ChatEntry);
+
+#[derive(Component)]
+pub struct ChatCmp;
+
+#[derive(Component)]
+pub struct ChatLineCmp(pub usize);
+
+#[derive(Component)]
+pub struct ChatInputCmp;
+
+#[derive(Component)]
+pub struct PingCmp(pub Timer);
+
+/// Whether a click places a ping instead of selecting or commanding ants
+pub fn is_pinging(keyboard: &ButtonInput<KeyCode>) -> bool {
+    keyboard.any_pressed([KeyCode::AltLeft, KeyCode::AltRight])
+}
+
+pub fn spawn_chat(mut commands: Commands, assets: Local<WorldAssets>, window: Single<&Window>) {
+    commands
+        .spawn((
+            Node {
+                bottom: Val::Percent(16.),
+                left: Val::Percent(7.),
+                width: Val::Percent(35.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Percent(0.5)),
+                ..default()
+            },
+            BackgroundColor(Color::NONE),
+            BorderRadius::all(Val::Px(10.)),
+            PickingBehavior::IGNORE,
+            ChatCmp,
+            MapCmp,
+        ))
+        .with_children(|parent| {
+            for i in 0..CHAT_LINES {
+                parent.spawn((
+                    add_text("", "medium", LABEL_TEXT_SIZE, &assets, &window),
+                    PickingBehavior::IGNORE,
+                    ChatLineCmp(i),
+                ));
+            }
+
+            parent.spawn((
+                add_text("", "bold", LABEL_TEXT_SIZE, &assets, &window),
+                PickingBehavior::IGNORE,
+                ChatInputCmp,
+            ));
+        });
+}
+
+pub fn type_chat(
+    mut chat: ResMut<ChatInput>,
+    players: Res<Players>,
+    mut chat_ev: EventWriter<ChatEv>,
+    mut keyboard_ev: EventReader<KeyboardInput>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+) {
+    if !chat.open {
+        keyboard_ev.clear();
+
+        // Enter opens the chat to everyone, shift+enter to the team
+        if keyboard.just_pressed(KeyCode::Enter) {
+            chat.open = true;
+            chat.team = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+            keyboard.reset_all();
+        }
+
+        return;
+    }
+
+    for ev in keyboard_ev
+        .read()
+        .filter(|ev| ev.state == ButtonState::Pressed)
+    {
+        match &ev.logical_key {
+            Key::Character(c) if chat.text.chars().count() < MAX_CHAT_LENGTH => {
+                chat.text.extend(c.chars().filter(|c| !c.is_control()));
+            }
+            Key::Space if chat.text.chars().count() < MAX_CHAT_LENGTH => chat.text.push(' '),
+            Key::Backspace => {
+                chat.text.pop();
+            }
+            Key::Tab => chat.team = !chat.team,
+            Key::ArrowUp => chat.scroll += 1,
+            Key::ArrowDown => chat.scroll = chat.scroll.saturating_sub(1),
+            Key::Enter => {
+                let text = chat.text.trim().to_string();
+                if !text.is_empty() {
+                    let player = players.main();
+                    chat_ev.send(ChatEv(ChatEntry::Message {
+                        id: player.id,
+                        name: player.name.clone(),
+                        team: chat.team,
+                        text,
+                    }));
+                }
+
+                *chat = ChatInput::default();
+            }
+            Key::Escape => *chat = ChatInput::default(),
+            _ => (),
+        }
+    }
+
+    // Don't trigger the game's shortcuts while typing
+    keyboard.reset_all();
+}
+
+pub fn place_ping(
+    players: Res<Players>,
+    map: Res<Map>,
+    mut chat_ev: EventWriter<ChatEv>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    camera: Single<(&Camera, &GlobalTransform)>,
+    window: Single<&Window>,
+) {
+    if !is_pinging(&keyboard) {
+        return;
+    }
+
+    let kind = if mouse.just_pressed(MouseButton::Left) {
+        PingKind::AttackHere
+    } else if mouse.just_pressed(MouseButton::Middle) {
+        PingKind::Help
+    } else if mouse.just_pressed(MouseButton::Right) {
+        PingKind::Danger
+    } else {
+        return;
+    };
+
+    let (camera, global_t) = *camera;
+    if let Some(cursor) = window.cursor_position() {
+        let cursor = camera.viewport_to_world_2d(global_t, cursor).unwrap();
+
+        let loc = map.get_loc(&cursor.extend(0.));
+        if map.get_tile(loc.x, loc.y).is_some() {
+            let player = players.main();
+            chat_ev.send(ChatEv(ChatEntry::Ping {
+                id: player.id,
+                name: player.name.clone(),
+                color: player.color,
+                kind,
+                loc,
+            }));
+        }
+    }
+}
+
+pub fn chat_event(
+    mut commands: Commands,
+    mut chat_ev: EventReader<ChatEv>,
+    mut history: ResMut<ChatHistory>,
+    players: Res<Players>,
+    lobby: Res<Lobby>,
+    server: Option<Res<RenetServer>>,
+    mut server_send_message: EventWriter<ServerSendMessage>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    game_settings: Res<GameSettings>,
+    time: Res<Time<Real>>,
+    assets: Local<WorldAssets>,
+) {
+    for ChatEv(entry) in chat_ev.read() {
+        if entry.sender() == players.main_id() {
+            // Share the entry with the other players
+            if server.is_some() {
+                for id in entry.recipients(&players, &lobby.observers) {
+                    server_send_message.send(ServerSendMessage {
+                        message: ServerMessage::Chat(entry.clone()),
+                        client: Some(id),
+                    });
+                }
+            } else {
+                client_send_message.send(ClientSendMessage {
+                    message: ClientMessage::Chat(entry.clone()),
+                });
+            }
+        } else {
+            play_audio_ev.send(match entry {
+                ChatEntry::Message { .. } => PlayAudioEv::new("message"),
+                ChatEntry::Ping { .. } => {
+                    PlayAudioEv::new("warning").with_category(AudioCategory::Alerts)
+                }
+            });
+        }
+
+        if let ChatEntry::Ping { color, loc, .. } = entry {
+            let pin_e = spawn_pin(
+                &mut commands,
+                loc,
+                color.to_color(&game_settings.palette),
+                &assets,
+            );
+            commands
+                .entity(pin_e)
+                .insert(PingCmp(Timer::from_seconds(PING_DURATION, TimerMode::Once)));
+        }
+
+        history.0.push((time.elapsed_secs(), entry.clone()));
+    }
+}
+
+pub fn despawn_pings(
+    mut commands: Commands,
+    mut ping_q: Query<(Entity, &mut PingCmp)>,
+    time: Res<Time<Real>>,
+) {
+    for (ping_e, mut ping) in &mut ping_q {
+        if ping.0.tick(time.delta()).finished() {
+            commands.entity(ping_e).despawn_recursive();
+        }
+    }
+}
+
+pub fn update_chat(
+    mut chat_q: Query<&mut BackgroundColor, With<ChatCmp>>,
+    mut line_q: Query<(&mut Text, &mut TextColor, &ChatLineCmp)>,
+    mut input_q: Query<&mut Text, (With<ChatInputCmp>, Without<ChatLineCmp>)>,
+    mut chat: ResMut<ChatInput>,
+    history: Res<ChatHistory>,
+    locale: Res<Locale>,
+    time: Res<Time<Real>>,
+) {
+    let n = history.0.len();
+
+    // While typing, the whole history can be scrolled, else only recent entries are shown
+    let entries = if chat.open {
+        chat.scroll = chat.scroll.min(n.saturating_sub(CHAT_LINES));
+        &history.0[(n - chat.scroll).saturating_sub(CHAT_LINES)..n - chat.scroll]
+    } else {
+        let start = history
+            .0
+            .iter()
+            .position(|(t, _)| time.elapsed_secs() - t < CHAT_DURATION)
+            .unwrap_or(n);
+
+        &history.0[start.max(n.saturating_sub(CHAT_LINES))..]
+    };
+
+    // Align the entries to the bottom of the chat box
+    let offset = CHAT_LINES - entries.len();
+    for (mut text, mut color, ChatLineCmp(i)) in &mut line_q {
+        if let Some((_, entry)) = i.checked_sub(offset).and_then(|i| entries.get(i)) {
+            (text.0, color.0) = entry.to_line(&locale);
+        } else {
+            text.0 = String::new();
+        }
+    }
+
+    if let Ok(mut text) = input_q.get_single_mut() {
+        text.0 = if chat.open {
+            let channel = locale.get(if chat.team { "chat.team" } else { "chat.all" });
+            format!("[{channel}] {}_", chat.text)
+        } else {
+            String::new()
+        };
+    }
+
+    if let Ok(mut bgcolor) = chat_q.get_single_mut() {
+        bgcolor.0 = if chat.open {
+            Color::srgba_u8(88, 57, 39, 200)
+        } else {
+            Color::NONE
+        };
+    }
+}
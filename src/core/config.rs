@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+
+/// Path of a config file in the platform's config directory
+#[cfg(not(target_arch = "wasm32"))]
+fn config_path(name: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("marabunta").join(name))
+}
+
+/// Store data in the config file with the given name
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_config<T: Serialize>(name: &str, data: &T) {
+    if let Some(path) = config_path(name) {
+        let result = std::fs::create_dir_all(path.parent().unwrap()).and_then(|_| {
+            std::fs::write(
+                &path,
+                bincode::serialize(data).expect("Failed to serialize data."),
+            )
+        });
+
+        if let Err(err) = result {
+            warn!("Failed to save the config file {}: {err}", path.display());
+        }
+    }
+}
+
+/// Read the config file with the given name (None if it doesn't exist or is invalid)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_config<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let buffer = std::fs::read(config_path(name)?).ok()?;
+    bincode::deserialize(&buffer).ok()
+}
+
+/// Browsers have no file system, so the config files are stored in the local storage
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_config<T: Serialize>(name: &str, data: &T) {
+    if let Some(storage) = local_storage() {
+        // The local storage only accepts strings, so encode the bytes as hex
+        let value = bincode::serialize(data)
+            .expect("Failed to serialize data.")
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+
+        if storage
+            .set_item(&format!("marabunta/{name}"), &value)
+            .is_err()
+        {
+            warn!("Failed to save the config file {name}.");
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn load_config<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let value = local_storage()?
+        .get_item(&format!("marabunta/{name}"))
+        .ok()??;
+
+    let buffer = (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    bincode::deserialize(&buffer).ok()
+}
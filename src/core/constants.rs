@@ -9,26 +9,87 @@ pub const NORMAL_BUTTON_COLOR: Color = Color::srgb(0.15, 0.15, 0.15);
 pub const HOVERED_BUTTON_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
 pub const PRESSED_BUTTON_COLOR: Color = Color::srgb(0.35, 0.65, 0.35);
 pub const DISABLED_BUTTON_COLOR: Color = Color::srgb(0.8, 0.5, 0.5);
+pub const MAX_PASSWORD_LENGTH: usize = 32;
+pub const MAX_NAME_LENGTH: usize = 16;
 
 // Camera
 pub const MIN_ZOOM: f32 = 0.2;
 pub const MAX_ZOOM: f32 = 1.;
 pub const ZOOM_FACTOR: f32 = 1.1;
 pub const LERP_FACTOR: f32 = 0.05;
+pub const CAMERA_BOOKMARKS: u8 = 6;
+pub const EDGE_SCROLL_MARGIN: f32 = 5.; // Pixels from the window border that scroll the camera
 
 // Game settings
 pub const MAX_GAME_SPEED: f32 = 5.;
 pub const GAME_SPEED_STEP: f32 = 0.5;
 pub const MAX_QUEUE_LENGTH: usize = 12;
+pub const MAX_SELECTION_CARDS: usize = 30; // Selected ants shown in the selection panel
 pub const TRAIT_TIMER: f32 = 120.;
 pub const MAX_TRAITS: usize = 7;
+pub const MAX_COLONIES: usize = 6; // Colonies (players + npcs) that fit on the map
+pub const MAX_ALLIANCES: u8 = 3;
 pub const ENEMY_TIMER: u64 = 300;
 pub const NETWORK_TIMER: u64 = 50;
+pub const UPKEEP_TIMER: u64 = 10_000;
+
+// Network
+pub const SNAPSHOT_HISTORY: usize = 32;
+pub const POSITION_PRECISION: f32 = 8.; // Units per pixel of the positions sent to clients
+pub const INTERPOLATION_DELAY: u64 = 100; // Clients show remote ants this many ms in the past
+pub const MAX_EXTRAPOLATION: u64 = 250;
+pub const DESYNC_TIMER: u64 = 5_000;
+pub const POPULATION_CHECK_FRAMES: u32 = 2; // Frames to spawn the entities of a snapshot
+pub const END_GAME_DELAY: f32 = 1.; // Seconds a dedicated server waits before closing a finished game
+
+// Audio
+pub const MUSIC_VOLUME: f64 = 0.03;
+pub const VOLUME_LEVELS: [u8; 5] = [0, 25, 50, 75, 100]; // Volumes (in %) of the mixer
+pub const AUDIO_FALLOFF: f32 = 2.; // Half views away from the camera at which sounds fade out
+pub const ZOOM_ATTENUATION: f32 = 0.5; // Volume reduction when the camera is fully zoomed out
+pub const MAX_PANNING: f32 = 0.8; // Stereo panning of the sounds at the border of the view
+pub const MAX_SFX_INSTANCES: usize = 4; // Same sounds that can play at the same time
+pub const SFX_DURATION: f32 = 0.6; // Seconds a pooled sound occupies its place in the pool
+pub const SFX_INTERVAL: f32 = 0.08; // Minimum seconds between two of the same sounds
+pub const ATTACK_SOUND_CHANCE: f32 = 0.8; // Chance per second a fighting ant makes noise
+pub const HARVEST_SOUND_CHANCE: f32 = 0.3; // Chance per second a harvesting ant makes noise
+pub const DIG_SOUND_CHANCE: f32 = 0.5; // Chance per second a digging ant makes noise
+pub const HATCH_SOUND_MARGIN: f32 = 1.; // Seconds to hatch under which a vanished egg hatched
+pub const FIGHT_INTENSITY_ANTS: f32 = 30.; // Fighting ants at which the music is most intense
+pub const MUSIC_INTENSITY_SPEED: f32 = 0.2; // Change of the music intensity per second
+pub const MUSIC_INTENSITY_VOLUME: f64 = 1.; // Volume of the intensity layer at full intensity
+
+// Chat
+pub const MAX_CHAT_LENGTH: usize = 100;
+pub const CHAT_LINES: usize = 8; // Lines of the chat history shown at once
+pub const CHAT_DURATION: f32 = 10.; // Seconds a message stays visible while the chat is closed
+pub const PING_DURATION: f32 = 6.; // Seconds a ping stays on the map
+
+// Alerts
+pub const ALERT_LINES: usize = 5; // Alerts shown at once in the feed
+pub const ALERT_DURATION: f32 = 8.; // Seconds an alert stays in the feed
+pub const ALERT_COOLDOWN: f32 = 15.; // Seconds before the same kind of alert is raised again
+
+// Statistics
+pub const STATS_INTERVAL: f32 = 10.; // Seconds of game time between samples of the timeline graphs
+pub const GRAPH_COLORS: [Color; MAX_COLONIES] = [
+    Color::srgb(0.95, 0.8, 0.2),
+    Color::srgb(0.3, 0.6, 0.95),
+    Color::srgb(0.9, 0.3, 0.3),
+    Color::srgb(0.4, 0.85, 0.4),
+    Color::srgb(0.8, 0.45, 0.9),
+    Color::srgb(0.95, 0.55, 0.2),
+];
+
+// Palette
+pub const MAX_PALETTE_SWAPS: usize = 4; // Sprites recolored per frame (to not freeze the game)
+pub const HEALTH_BAR_BACKGROUND: Color = Color::srgba(1., 1., 1., 0.5); // Light to show dark colonies
 
 // Z-scores
 pub const TILE_Z_SCORE: f32 = 0.;
 pub const EGG_Z_SCORE: f32 = 1.;
 pub const ANT_Z_SCORE: f32 = 2.;
+pub const PROJECTILE_Z_SCORE: f32 = 3.;
 pub const MAX_Z_SCORE: f32 = 10.;
 
 // Teams
@@ -57,8 +118,21 @@ pub const TUNNEL_SPEED_FACTOR: f32 = 2.; // Dig speed increase for the trait tun
 pub const HARVEST_SPEED: f32 = 5.; // Food harvesting per ant per second
 pub const HARVEST_SPEED_FACTOR: f32 = 2.; // Harvesting speed increase for trait harvesting
 pub const HARVEST_DECREASE_FACTOR: f32 = 0.5; // Harvesting speed decrease for trait warlike
+pub const LEAF_SYNC_STEP: f32 = 25.; // Leaf quantity harvested before the tile is sent to clients
 pub const HEAL_SPEED_RATIO: f32 = 0.05; // Health ratio healed per second
 pub const FLY_SPEED_FACTOR: f32 = 2.; // Times flying is faster than base
 pub const HASTE_SPEED_FACTOR: f32 = 1.2; // Walk speed increase for the trait haste
 pub const SAME_TUNNEL_DIG_CHANCE: f32 = 0.95; // Chance of continuing digging in the same tunnel
 pub const MAX_DISTANCE_PROTECT: usize = 5; // Maximum distance of target to protect
+pub const RANK_EXPERIENCE: [f32; 3] = [50., 150., 400.]; // Experience required for every rank
+pub const KILL_EXPERIENCE_FACTOR: f32 = 0.5; // Fraction of the victim's max health gained as experience
+pub const RANK_HEALTH_FACTOR: f32 = 1.15; // Health increase per rank
+pub const RANK_DAMAGE_FACTOR: f32 = 1.1; // Damage increase per rank
+pub const RANK_SPEED_FACTOR: f32 = 1.05; // Speed increase per rank
+pub const UPKEEP_FACTOR: f32 = 0.02; // Fraction of the ant's price paid as upkeep every UPKEEP_TIMER tick
+pub const STARVATION_SPEED_FACTOR: f32 = 0.7; // Walk speed decrease for starving ants
+pub const STARVATION_DAMAGE_FACTOR: f32 = 0.7; // Damage decrease for starving ants
+pub const STARVATION_HEALTH_LOSS: f32 = 0.1; // Fraction of max health starving ants lose every UPKEEP_TIMER tick
+pub const PROJECTILE_SPEED: f32 = 80.; // Speed of projectiles in pixels per second
+pub const PROJECTILE_HIT_DISTANCE: f32 = 3.; // Distance to the target at which a projectile hits
+pub const PROJECTILE_COLOR: Color = Color::srgb(0.7, 0.9, 0.2); // Color of the projectiles
@@ -0,0 +1,284 @@
+use crate::core::ants::components::{Ant, AntCmp};
+use crate::core::constants::{END_GAME_DELAY, MAX_COLONIES};
+use crate::core::game_settings::{GameMode, GameSettings};
+use crate::core::menu::lobby::Lobby;
+use crate::core::menu::settings::{Background, FogOfWar};
+use crate::core::multiplayer::start_multiplayer_game;
+use crate::core::network::{new_renet_server, HostSettings, ServerSendMessage};
+use crate::core::player::{Player, Players};
+use crate::core::states::{AppState, GameState};
+use crate::utils::NameFromEnum;
+use bevy::prelude::*;
+use bevy_renet::renet::{RenetServer, ServerEvent};
+use std::collections::HashSet;
+use strum::IntoEnumIterator;
+
+pub const USAGE: &str = "\
+Usage: marabunta-server [options]
+
+Options:
+  --address <ip>        Address to bind the server to (default: 0.0.0.0)
+  --port <port>         Port to listen on (default: 5000)
+  --players <n>         Number of players that start the game (default: 2)
+  --npcs <n>            Number of npc colonies (default: 1)
+  --observers <n>       Number of observers that can watch the game (default: 0)
+  --background <name>   Map background: soil or rock (default: soil)
+  --fog-of-war <name>   Fog of war: none, half or full (default: full)
+  --password <password> Password players need to join (default: none)
+  --resync <on|off>     Replace the tiles of clients that diverge from the server (default: on)
+  --upkeep              Enable aging and upkeep";
+
+/// Settings of a dedicated (headless) server
+#[derive(Resource, Clone)]
+pub struct DedicatedServer {
+    pub host: HostSettings,
+    pub npcs: u64,
+    pub observers: usize,
+    pub background: Background,
+    pub fog_of_war: FogOfWar,
+    pub upkeep: bool,
+    pub resync: bool,
+}
+
+impl Default for DedicatedServer {
+    fn default() -> Self {
+        Self {
+            host: HostSettings {
+                max_clients: 2,
+                ..default()
+            },
+            npcs: 1,
+            observers: 0,
+            background: Background::default(),
+            fog_of_war: FogOfWar::default(),
+            upkeep: false,
+            resync: true,
+        }
+    }
+}
+
+impl DedicatedServer {
+    /// Parse the command line arguments of the server binary
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut server = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {arg}."));
+
+            match arg.as_str() {
+                "--address" => {
+                    server.host.address = value()?
+                        .parse()
+                        .map_err(|_| "Invalid address.".to_string())?
+                }
+                "--port" => {
+                    server.host.port = value()?.parse().map_err(|_| "Invalid port.".to_string())?
+                }
+                "--players" => {
+                    server.host.max_clients = value()?
+                        .parse()
+                        .map_err(|_| "Invalid number of players.".to_string())?
+                }
+                "--npcs" => {
+                    server.npcs = value()?
+                        .parse()
+                        .map_err(|_| "Invalid number of npcs.".to_string())?
+                }
+                "--observers" => {
+                    server.observers = value()?
+                        .parse()
+                        .map_err(|_| "Invalid number of observers.".to_string())?
+                }
+                "--background" => {
+                    let value = value()?;
+                    server.background = Background::iter()
+                        .find(|b| b.to_lowername() == value)
+                        .ok_or(format!("Unknown background {value}."))?
+                }
+                "--fog-of-war" => {
+                    let value = value()?;
+                    server.fog_of_war = FogOfWar::iter()
+                        .find(|f| f.to_lowername() == value)
+                        .ok_or(format!("Unknown fog of war {value}."))?
+                }
+                "--password" => server.host.password = value()?,
+                "--resync" => {
+                    server.resync = match value()?.as_str() {
+                        "on" => true,
+                        "off" => false,
+                        v => return Err(format!("Invalid resync value {v}.")),
+                    }
+                }
+                "--upkeep" => server.upkeep = true,
+                _ => return Err(format!("Unknown argument {arg}.")),
+            }
+        }
+
+        if server.host.max_clients == 0 {
+            return Err("The server needs at least one player.".to_string());
+        } else if server.host.max_clients + (server.npcs as usize) < 2 {
+            return Err("The game needs at least two colonies (players + npcs).".to_string());
+        } else if server.host.max_clients + (server.npcs as usize) > MAX_COLONIES {
+            return Err(format!(
+                "The map fits at most {MAX_COLONIES} colonies (players + npcs)."
+            ));
+        }
+
+        Ok(server)
+    }
+}
+
+pub fn start_dedicated_server(
+    mut commands: Commands,
+    dedicated: Res<DedicatedServer>,
+    mut game_settings: ResMut<GameSettings>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    *game_settings = GameSettings {
+        game_mode: GameMode::Multiplayer,
+        npcs: dedicated.npcs,
+        background: dedicated.background,
+        fog_of_war: dedicated.fog_of_war,
+        upkeep: dedicated.upkeep,
+        resync: dedicated.resync,
+        ..default()
+    };
+
+    // Observers take a place on the server, but not in the lobby
+    let host = HostSettings {
+        max_clients: dedicated.host.max_clients + dedicated.observers,
+        ..dedicated.host.clone()
+    };
+
+    let (server, transport, token_server) = match new_renet_server(&host) {
+        Ok(server) => server,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    commands.insert_resource(server);
+    commands.insert_resource(transport);
+    commands.insert_resource(token_server);
+    commands.insert_resource(Lobby {
+        max_humans: dedicated.host.max_clients,
+        ..Lobby::new(&game_settings, false)
+    });
+
+    println!(
+        "Server listening on {}:{}. Waiting for {} players to join...",
+        dedicated.host.address, dedicated.host.port, dedicated.host.max_clients
+    );
+
+    next_app_state.set(AppState::Lobby);
+}
+
+pub fn dedicated_lobby_update(
+    mut commands: Commands,
+    server: Res<RenetServer>,
+    mut server_ev: EventReader<ServerEvent>,
+    dedicated: Res<DedicatedServer>,
+    lobby: Res<Lobby>,
+    game_settings: Res<GameSettings>,
+    mut server_send_message: EventWriter<ServerSendMessage>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    for ev in server_ev.read() {
+        match ev {
+            ServerEvent::ClientConnected { client_id } => {
+                println!("Client {client_id} joined the lobby");
+            }
+            ServerEvent::ClientDisconnected { client_id, reason } => {
+                println!("Client {client_id} left the lobby: {reason}");
+            }
+        }
+    }
+
+    let n_players = lobby.humans();
+    if n_players == dedicated.host.max_clients && lobby.all_ready() {
+        // The server's own player is only a spectator without colony
+        let mut players = vec![Player::new(0, game_settings.color)];
+        players.extend(lobby.to_players());
+
+        let map = start_multiplayer_game(
+            &mut commands,
+            &server,
+            &game_settings,
+            &players,
+            &mut server_send_message,
+            true,
+        );
+
+        players.push(Player::default());
+
+        commands.insert_resource(map);
+        commands.insert_resource(Players(players));
+
+        println!(
+            "Starting game with {n_players} players and {} npcs",
+            game_settings.npcs
+        );
+        next_app_state.set(AppState::Game);
+    }
+}
+
+pub fn dedicated_game_update(
+    server: Res<RenetServer>,
+    ant_q: Query<&AntCmp>,
+    players: Res<Players>,
+    mut started: Local<bool>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let teams = ant_q
+        .iter()
+        .filter_map(|a| (a.kind == Ant::Queen && a.health > 0.).then_some(a.team))
+        .collect::<HashSet<_>>();
+
+    // Wait until the queens of all colonies are spawned
+    if !*started {
+        *started = teams.len() > 1;
+        return;
+    }
+
+    // The game ends when only allied colonies are left
+    let winner = teams.iter().next();
+    if teams
+        .iter()
+        .all(|t| winner.is_some_and(|w| players.are_allies(*t, *w)))
+    {
+        match winner {
+            Some(_) if teams.len() > 1 => println!("Game over: alliance {teams:?} won"),
+            Some(id) if players.get(*id).is_human() => println!("Game over: client {id} won"),
+            Some(id) => println!("Game over: npc {id} won"),
+            None => println!("Game over: no colony survived"),
+        }
+    } else if server.clients_id().is_empty() {
+        println!("Game over: all players left");
+    } else {
+        return;
+    }
+
+    // The final statistics are sent when entering the end of the game
+    *started = false;
+    next_game_state.set(GameState::EndGame);
+}
+
+/// Return to the lobby to wait for the next game once the clients received the
+/// final statistics
+pub fn dedicated_end_game(
+    mut server: ResMut<RenetServer>,
+    mut lobby: ResMut<Lobby>,
+    mut timer: Local<Option<Timer>>,
+    time: Res<Time<Real>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::from_seconds(END_GAME_DELAY, TimerMode::Once));
+
+    if timer.tick(time.delta()).finished() {
+        timer.reset();
+        server.disconnect_all();
+        lobby.players.retain(|p| p.is_npc());
+        next_game_state.set(GameState::default());
+        next_app_state.set(AppState::Lobby);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::core::ants::components::{AntCmp, Egg};
+use crate::core::constants::POPULATION_CHECK_FRAMES;
+use crate::core::map::map::Map;
+use crate::core::map::tile::Tile;
+use crate::core::network::{ClientMessage, ClientSendMessage, ServerMessage, ServerSendMessage};
+use crate::core::snapshot::{Snapshot, SnapshotHistory};
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashSet;
+use bevy_renet::renet::ClientId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Hashes of the game state that must be equal on the host and the clients
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checksum {
+    /// Sequence number of the snapshot the population was counted from
+    pub seq: u32,
+
+    /// Hash of every row of tiles in the map
+    pub rows: Vec<u64>,
+
+    /// Number of ants and eggs per team
+    pub population: BTreeMap<ClientId, usize>,
+}
+
+impl Checksum {
+    pub fn new(map: &Map, seq: u32, snapshot: Option<&Snapshot>) -> Self {
+        let mut population = BTreeMap::new();
+        if let Some(snapshot) = snapshot {
+            for team in snapshot
+                .ants
+                .values()
+                .map(|s| s.ant.team)
+                .chain(snapshot.eggs.values().map(|s| s.egg.team))
+            {
+                *population.entry(team).or_default() += 1;
+            }
+        }
+
+        Self {
+            seq,
+            rows: map
+                .tiles
+                .chunks(Map::MAP_SIZE.x as usize)
+                .map(|row| {
+                    let mut hasher = blake3::Hasher::new();
+                    row.iter().for_each(|tile| hash_tile(tile, &mut hasher));
+                    u64::from_le_bytes(hasher.finalize().as_bytes()[..8].try_into().unwrap())
+                })
+                .collect(),
+            population,
+        }
+    }
+}
+
+/// Hash the fields of a tile that are synchronized by the host. The exploration
+/// and terraform progress are calculated locally, so they are skipped
+fn hash_tile(tile: &Tile, hasher: &mut blake3::Hasher) {
+    hasher.update(&(tile.texture_index as u64).to_le_bytes());
+    hasher.update(&tile.rotation.to_le_bytes());
+    hasher.update(&tile.base.unwrap_or(ClientId::MAX).to_le_bytes());
+    hasher.update(&[tile.has_stone as u8]);
+    if let Some(leaf) = &tile.leaf {
+        hasher.update(leaf.image.as_bytes());
+        hasher.update(&leaf.synced_quantity().to_le_bytes());
+    }
+}
+
+fn same_tile(t1: &Tile, t2: &Tile) -> bool {
+    let mut h1 = blake3::Hasher::new();
+    let mut h2 = blake3::Hasher::new();
+    hash_tile(t1, &mut h1);
+    hash_tile(t2, &mut h2);
+    h1.finalize() == h2.finalize()
+}
+
+#[derive(Resource, Default)]
+pub struct DesyncCheck {
+    /// Rows of tiles that differed from the host in the last check
+    pub rows: HashSet<u32>,
+
+    /// Population of the host waiting to be compared with the local entities
+    pub population: Option<(u32, BTreeMap<ClientId, usize>)>,
+
+    /// Sequence number of the last snapshot received and the frames since then
+    pub last_snapshot: (u32, u32),
+}
+
+#[derive(Event)]
+pub struct ChecksumEv(pub Checksum);
+
+/// Write the differences with the host to a file (to the console on the web)
+fn write_dump(lines: Vec<String>) {
+    let dump = lines.join("\n");
+    println!("Desync detected with the host:\n{dump}");
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::time::SystemTime;
+
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let file_path = format!("desync-{time}.log");
+        if let Err(err) = std::fs::write(&file_path, dump) {
+            println!("Failed to write {file_path}: {err}");
+        }
+    }
+}
+
+pub fn server_send_checksum(
+    mut server_send_message: EventWriter<ServerSendMessage>,
+    map: Res<Map>,
+    history: Res<SnapshotHistory>,
+) {
+    server_send_message.send(ServerSendMessage {
+        message: ServerMessage::Checksum(Checksum::new(
+            &map,
+            history.seq,
+            history.get(history.seq),
+        )),
+        client: None,
+    });
+}
+
+pub fn check_desync(
+    mut checksum_ev: EventReader<ChecksumEv>,
+    ant_q: Query<&AntCmp>,
+    egg_q: Query<&Egg, Without<AntCmp>>,
+    map: Res<Map>,
+    history: Res<SnapshotHistory>,
+    mut desync: ResMut<DesyncCheck>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
+) {
+    // The entities of a snapshot are spawned and despawned a few frames after it arrives
+    if desync.last_snapshot.0 == history.seq {
+        desync.last_snapshot.1 += 1;
+    } else {
+        desync.last_snapshot = (history.seq, 0);
+    }
+
+    for ChecksumEv(checksum) in checksum_ev.read() {
+        let local = Checksum::new(&map, checksum.seq, None);
+
+        desync.population = Some((checksum.seq, checksum.population.clone()));
+
+        let rows = (0..checksum.rows.len() as u32)
+            .filter(|y| checksum.rows.get(*y as usize) != local.rows.get(*y as usize))
+            .collect::<HashSet<_>>();
+
+        // Tile updates can arrive after the checksum, so a row only
+        // diverges if it differs in two consecutive checks
+        let mut diverging = rows.intersection(&desync.rows).copied().collect::<Vec<_>>();
+        desync.rows = rows;
+
+        if !diverging.is_empty() {
+            diverging.sort();
+            client_send_message.send(ClientSendMessage {
+                message: ClientMessage::Resync(diverging),
+            });
+        }
+    }
+
+    // Compare the entities on the map once they show the snapshot the host counted. When
+    // a newer snapshot arrived before that, the population can't be compared anymore
+    if let Some((seq, population)) = desync.population.take() {
+        let (last_seq, frames) = desync.last_snapshot;
+
+        if last_seq == seq && frames >= POPULATION_CHECK_FRAMES {
+            let mut local = BTreeMap::new();
+            for team in ant_q
+                .iter()
+                .map(|a| a.team)
+                .chain(egg_q.iter().map(|e| e.team))
+            {
+                *local.entry(team).or_default() += 1;
+            }
+
+            if local != population {
+                write_dump(vec![
+                    format!("Population of snapshot {seq}:"),
+                    format!("  host: {population:?}"),
+                    format!("  client: {local:?}"),
+                ]);
+            }
+        } else if last_seq <= seq {
+            desync.population = Some((seq, population));
+        }
+    }
+}
+
+/// Compare the tiles of the host with the local ones and replace them if enabled
+pub fn resync_tiles(map: &mut Map, tiles: Vec<Tile>, replace: bool) {
+    let mut lines = vec![];
+    for tile in tiles {
+        if let Some(local) = map.get_tile(tile.x, tile.y) {
+            if !same_tile(local, &tile) {
+                lines.push(format!("Tile ({}, {}):", tile.x, tile.y));
+                lines.push(format!("  host: {:?}", tile));
+                lines.push(format!("  client: {:?}", local));
+
+                if replace {
+                    map.replace_tile(&tile);
+                }
+            }
+        }
+    }
+
+    if !lines.is_empty() {
+        write_dump(lines);
+    }
+}
@@ -1,6 +1,11 @@
 use crate::core::ants::components::Ant;
+use crate::core::audio::Mixer;
+use crate::core::config::{load_config, save_config};
 use crate::core::constants::TRAIT_TIMER;
-use crate::core::menu::settings::{AntColor, Background, FogOfWar};
+use crate::core::locale::Language;
+use crate::core::menu::lobby::Nickname;
+use crate::core::menu::settings::{AntColor, Background, FogOfWar, Palette};
+use crate::core::menu::systems::Ip;
 use crate::core::states::AudioState;
 use bevy::prelude::*;
 use bevy::utils::hashbrown::HashMap;
@@ -12,15 +17,31 @@ pub enum GameMode {
     Multiplayer,
 }
 
+/// Rules of the game chosen by the user, kept aside while playing with the rules of a host
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct UserRules {
+    pub background: Background,
+    pub npcs: u64,
+    pub fog_of_war: FogOfWar,
+    pub upkeep: bool,
+}
+
 #[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct GameSettings {
     pub game_mode: GameMode,
     pub color: AntColor,
+    pub palette: Palette,
+    pub language: Language,
     pub background: Background,
     pub npcs: u64,
     pub fog_of_war: FogOfWar,
     pub audio: AudioState,
+    pub upkeep: bool,
+    pub edge_scrolling: bool,
+    pub mixer: Mixer,
     pub speed: f32,
+    pub resync: bool, // Whether clients replace the tiles that diverge from the host's
+    pub user_rules: Option<UserRules>,
     pub trait_timer: Timer,
     pub termite_queue: HashMap<(u32, u32), Vec<Ant>>,
 }
@@ -32,9 +53,16 @@ impl Default for GameSettings {
             fog_of_war: FogOfWar::default(),
             background: Background::default(),
             color: AntColor::default(),
+            palette: Palette::default(),
+            language: Language::default(),
             npcs: 1,
             audio: AudioState::default(),
+            upkeep: false,
+            edge_scrolling: false,
+            mixer: Mixer::default(),
             speed: 1.0,
+            resync: true,
+            user_rules: None,
             trait_timer: Timer::from_seconds(TRAIT_TIMER, TimerMode::Repeating),
             termite_queue: HashMap::new(),
         }
@@ -42,9 +70,126 @@ impl Default for GameSettings {
 }
 
 impl GameSettings {
+    /// Play a multiplayer game with the rules of the host, keeping the user's own
+    pub fn apply_host_rules(&mut self, background: Background, fog_of_war: FogOfWar, upkeep: bool) {
+        self.user_rules.get_or_insert(UserRules {
+            background: self.background,
+            npcs: self.npcs,
+            fog_of_war: self.fog_of_war,
+            upkeep: self.upkeep,
+        });
+
+        self.game_mode = GameMode::Multiplayer;
+        self.background = background;
+        self.fog_of_war = fog_of_war;
+        self.upkeep = upkeep;
+    }
+
     /// Reset in-game settings
     pub fn reset(&mut self) {
         self.trait_timer.reset();
         self.termite_queue.clear();
+
+        // Return to the rules chosen by the user
+        if let Some(rules) = self.user_rules.take() {
+            self.background = rules.background;
+            self.npcs = rules.npcs;
+            self.fog_of_war = rules.fog_of_war;
+            self.upkeep = rules.upkeep;
+        }
+    }
+}
+
+/// Name of the file where the user settings are stored
+const SETTINGS_FILE: &str = "settings.bin";
+
+/// Settings chosen by the user that are stored between sessions
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserSettings {
+    pub color: AntColor,
+    pub palette: Palette,
+    pub language: Language,
+    pub background: Background,
+    pub npcs: u64,
+    pub fog_of_war: FogOfWar,
+    pub audio: AudioState,
+    pub upkeep: bool,
+    pub edge_scrolling: bool,
+    pub mixer: Mixer,
+    pub speed: f32,
+    pub ip: String,
+    pub nickname: String,
+}
+
+impl UserSettings {
+    pub fn new(game_settings: &GameSettings, ip: &Ip, nickname: &Nickname) -> Self {
+        // Never store the rules of a host as the user's own
+        let rules = game_settings.user_rules.unwrap_or(UserRules {
+            background: game_settings.background,
+            npcs: game_settings.npcs,
+            fog_of_war: game_settings.fog_of_war,
+            upkeep: game_settings.upkeep,
+        });
+
+        Self {
+            color: game_settings.color,
+            palette: game_settings.palette,
+            language: game_settings.language,
+            background: rules.background,
+            npcs: rules.npcs,
+            fog_of_war: rules.fog_of_war,
+            audio: game_settings.audio,
+            upkeep: rules.upkeep,
+            edge_scrolling: game_settings.edge_scrolling,
+            mixer: game_settings.mixer.clone(),
+            speed: game_settings.speed,
+            ip: ip.0.clone(),
+            nickname: nickname.0.clone(),
+        }
+    }
+
+    /// Load the settings of the last session (None if there are no stored settings)
+    pub fn load() -> Option<Self> {
+        load_config(SETTINGS_FILE)
+    }
+
+    pub fn save(&self) {
+        save_config(SETTINGS_FILE, self);
+    }
+
+    pub fn game_settings(&self) -> GameSettings {
+        GameSettings {
+            color: self.color,
+            palette: self.palette,
+            language: self.language,
+            background: self.background,
+            npcs: self.npcs,
+            fog_of_war: self.fog_of_war,
+            audio: self.audio,
+            upkeep: self.upkeep,
+            edge_scrolling: self.edge_scrolling,
+            mixer: self.mixer.clone(),
+            // Don't start the next session paused
+            speed: if self.speed > 0. { self.speed } else { 1. },
+            ..default()
+        }
+    }
+}
+
+pub fn save_settings(
+    game_settings: Res<GameSettings>,
+    ip: Res<Ip>,
+    nickname: Res<Nickname>,
+    mut last: Local<Option<UserSettings>>,
+) {
+    let settings = UserSettings::new(&game_settings, &ip, &nickname);
+
+    if last.as_ref() != Some(&settings) {
+        // The first run only stores the settings loaded at startup
+        if last.is_some() {
+            settings.save();
+        }
+
+        *last = Some(settings);
     }
 }
@@ -0,0 +1,212 @@
+use crate::core::ants::components::{Ant, AntCmp};
+use crate::core::config::{load_config, save_config};
+use crate::core::constants::CAMERA_BOOKMARKS;
+use crate::core::locale::Locale;
+use crate::utils::NameFromEnum;
+use bevy::input::keyboard::NativeKeyCode;
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+/// Name of the file where the keymap is stored
+const KEYMAP_FILE: &str = "keymap.bin";
+
+/// Keys used by fixed shortcuts (menu, chat, network overlay, etc...) that can't be rebound
+const RESERVED_KEYS: [KeyCode; 12] = [
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::F3,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::SuperLeft,
+    KeyCode::SuperRight,
+];
+
+/// Action of the player that can be bound to a key
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum InputAction {
+    CameraUp,
+    CameraLeft,
+    CameraDown,
+    CameraRight,
+    CameraBookmark(u8),
+    FollowSelection,
+    JumpToQueen,
+    JumpToAlert,
+    QueueAnt(Ant),
+    SelectGroup(u8),
+    RemoveCommand,
+    CycleFormation,
+    Pause,
+    ToggleMusic,
+}
+
+impl InputAction {
+    /// All actions in the order they are shown in the controls screen
+    pub fn all() -> Vec<Self> {
+        [
+            InputAction::CameraUp,
+            InputAction::CameraLeft,
+            InputAction::CameraDown,
+            InputAction::CameraRight,
+        ]
+        .into_iter()
+        .chain((0..CAMERA_BOOKMARKS).map(InputAction::CameraBookmark))
+        .chain([
+            InputAction::FollowSelection,
+            InputAction::JumpToQueen,
+            InputAction::JumpToAlert,
+        ])
+        .chain(
+            Ant::iter()
+                .filter(|a| AntCmp::base(a).key.is_some())
+                .map(InputAction::QueueAnt),
+        )
+        .chain((0..10).map(InputAction::SelectGroup))
+        .chain([
+            InputAction::RemoveCommand,
+            InputAction::CycleFormation,
+            InputAction::Pause,
+            InputAction::ToggleMusic,
+        ])
+        .collect()
+    }
+
+    pub fn default_key(&self) -> KeyCode {
+        match self {
+            InputAction::CameraUp => KeyCode::KeyW,
+            InputAction::CameraLeft => KeyCode::KeyA,
+            InputAction::CameraDown => KeyCode::KeyS,
+            InputAction::CameraRight => KeyCode::KeyD,
+            // F3 is reserved for the network overlay
+            InputAction::CameraBookmark(i) => [
+                KeyCode::F1,
+                KeyCode::F2,
+                KeyCode::F4,
+                KeyCode::F5,
+                KeyCode::F6,
+                KeyCode::F7,
+            ][*i as usize % CAMERA_BOOKMARKS as usize],
+            InputAction::FollowSelection => KeyCode::KeyL,
+            InputAction::JumpToQueen => KeyCode::KeyH,
+            InputAction::JumpToAlert => KeyCode::KeyJ,
+            InputAction::QueueAnt(ant) => AntCmp::base(ant)
+                .key
+                .unwrap_or(KeyCode::Unidentified(NativeKeyCode::Unidentified)),
+            InputAction::SelectGroup(i) => [
+                KeyCode::Digit0,
+                KeyCode::Digit1,
+                KeyCode::Digit2,
+                KeyCode::Digit3,
+                KeyCode::Digit4,
+                KeyCode::Digit5,
+                KeyCode::Digit6,
+                KeyCode::Digit7,
+                KeyCode::Digit8,
+                KeyCode::Digit9,
+            ][*i as usize % 10],
+            InputAction::RemoveCommand => KeyCode::Delete,
+            InputAction::CycleFormation => KeyCode::KeyF,
+            InputAction::Pause => KeyCode::Space,
+            InputAction::ToggleMusic => KeyCode::KeyM,
+        }
+    }
+
+    /// Description of the action in the controls screen
+    pub fn to_label(&self, locale: &Locale) -> String {
+        match self {
+            InputAction::QueueAnt(ant) => locale.fmt(
+                "action.queue_ant",
+                &[("ant", locale.get(&format!("ant.{}", ant.to_snake())))],
+            ),
+            InputAction::SelectGroup(i) => {
+                locale.fmt("action.select_group", &[("n", i.to_string())])
+            }
+            InputAction::CameraBookmark(i) => {
+                locale.fmt("action.camera_bookmark", &[("n", (i + 1).to_string())])
+            }
+            _ => locale.get(&format!("action.{}", self.to_snake())),
+        }
+    }
+}
+
+/// Short name of a key to show in the UI
+pub fn key_name(key: &KeyCode) -> String {
+    let name = format!("{key:?}");
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+/// Keys bound to every action of the player
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct Keymap(pub HashMap<InputAction, KeyCode>);
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self(
+            InputAction::all()
+                .into_iter()
+                .map(|a| {
+                    let key = a.default_key();
+                    (a, key)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Keymap {
+    /// Load the keymap from the config file (or the default keymap if there is none)
+    pub fn load() -> Self {
+        load_config(KEYMAP_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        save_config(KEYMAP_FILE, self);
+    }
+
+    pub fn key(&self, action: &InputAction) -> KeyCode {
+        self.0
+            .get(action)
+            .copied()
+            .unwrap_or_else(|| action.default_key())
+    }
+
+    /// Whether the key is used by a shortcut that can't be rebound
+    pub fn is_reserved(key: &KeyCode) -> bool {
+        RESERVED_KEYS.contains(key)
+    }
+
+    pub fn bind(&mut self, action: &InputAction, key: KeyCode) {
+        self.0.insert(action.clone(), key);
+    }
+
+    /// Other actions bound to the same key as the given action
+    pub fn conflicts(&self, action: &InputAction) -> Vec<InputAction> {
+        let key = self.key(action);
+        InputAction::all()
+            .into_iter()
+            .filter(|a| a != action && self.key(a) == key)
+            .collect()
+    }
+
+    pub fn pressed(&self, keyboard: &ButtonInput<KeyCode>, action: &InputAction) -> bool {
+        keyboard.pressed(self.key(action))
+    }
+
+    pub fn just_pressed(&self, keyboard: &ButtonInput<KeyCode>, action: &InputAction) -> bool {
+        keyboard.just_pressed(self.key(action))
+    }
+
+    pub fn just_released(&self, keyboard: &ButtonInput<KeyCode>, action: &InputAction) -> bool {
+        keyboard.just_released(self.key(action))
+    }
+}
@@ -0,0 +1,162 @@
+use crate::core::game_settings::GameSettings;
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, LoadContext};
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+#[derive(EnumIter, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    Russian,
+}
+
+impl Language {
+    /// Name of the language in the language itself
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+            Language::Russian => "Русский",
+        }
+    }
+
+    /// Path of the string table of the language in the assets folder
+    fn path(&self) -> &'static str {
+        match self {
+            Language::English => "locales/en.lang",
+            Language::German => "locales/de.lang",
+            Language::Russian => "locales/ru.lang",
+        }
+    }
+}
+
+/// English string table, embedded to be available before the tables are loaded
+const FALLBACK_TABLE: &str = include_str!("../../assets/locales/en.lang");
+
+/// Parse a string table with one `id = text` per line
+fn parse_table(table: &str) -> HashMap<String, String> {
+    table
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(id, text)| (id.trim().to_string(), text.trim().replace("\\n", "\n")))
+        .collect()
+}
+
+/// String table of a language loaded from the assets
+#[derive(Asset, TypePath)]
+pub struct LangTable(HashMap<String, String>);
+
+#[derive(Default)]
+pub struct LangLoader;
+
+impl AssetLoader for LangLoader {
+    type Asset = LangTable;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+
+        let table = String::from_utf8(bytes).map_err(std::io::Error::other)?;
+        Ok(LangTable(parse_table(&table)))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["lang"]
+    }
+}
+
+/// Texts of the interface in the chosen language
+#[derive(Resource)]
+pub struct Locale {
+    language: Option<Language>,
+    table: Handle<LangTable>,
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self {
+            language: None,
+            table: Handle::default(),
+            strings: HashMap::new(),
+            fallback: parse_table(FALLBACK_TABLE),
+        }
+    }
+}
+
+impl Locale {
+    /// Text with the given id (in English if the language has no translation for it)
+    pub fn get(&self, id: &str) -> String {
+        self.strings
+            .get(id)
+            .or_else(|| self.fallback.get(id))
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    /// Text with the given id where every `{name}` is replaced with its value
+    pub fn fmt(&self, id: &str, args: &[(&str, String)]) -> String {
+        args.iter().fold(self.get(id), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+    }
+}
+
+/// Text that is translated to the chosen language
+#[derive(Component)]
+pub struct Localized(pub String);
+
+impl Localized {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+}
+
+pub fn update_locale(
+    game_settings: Res<GameSettings>,
+    mut locale: ResMut<Locale>,
+    mut asset_ev: EventReader<AssetEvent<LangTable>>,
+    tables: Res<Assets<LangTable>>,
+    asset_server: Res<AssetServer>,
+) {
+    let changed = locale.language != Some(game_settings.language);
+    if changed {
+        // Keep showing the current texts until the new table is loaded
+        locale.language = Some(game_settings.language);
+        locale.table = asset_server.load(game_settings.language.path());
+    }
+
+    // The table may already be loaded or change later with hot reloading
+    let id = locale.table.id();
+    let loaded = asset_ev
+        .read()
+        .any(|ev| ev.is_loaded_with_dependencies(id) || ev.is_modified(id));
+
+    if changed || loaded {
+        if let Some(table) = tables.get(id) {
+            locale.strings = table.0.clone();
+        }
+    }
+}
+
+pub fn localize_texts(mut text_q: Query<(&mut Text, Ref<Localized>)>, locale: Res<Locale>) {
+    for (mut text, localized) in &mut text_q {
+        if locale.is_changed() || localized.is_changed() {
+            text.0 = locale.get(&localized.0);
+        }
+    }
+}
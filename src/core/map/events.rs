@@ -7,7 +7,6 @@ use crate::core::map::map::Map;
 use crate::core::map::systems::MapCmp;
 use crate::core::map::tile::Tile;
 use crate::core::menu::settings::{Background, FogOfWar};
-use crate::core::network::{ClientMessage, ClientSendMessage, ServerMessage, ServerSendMessage};
 use crate::core::player::Players;
 use crate::core::states::AppState;
 use crate::core::utils::{NoRotationChildCmp, NoRotationParentCmp};
@@ -114,8 +113,6 @@ pub fn spawn_tile_event(
     players: Res<Players>,
     mut map: ResMut<Map>,
     mut spawn_tile_ev: EventReader<SpawnTileEv>,
-    mut server_send_message: EventWriter<ServerSendMessage>,
-    mut client_send_message: EventWriter<ClientSendMessage>,
     assets: Local<WorldAssets>,
 ) {
     for SpawnTileEv { tile, pos } in spawn_tile_ev.read() {
@@ -157,22 +154,6 @@ pub fn spawn_tile_event(
                     &background,
                     &assets,
                 );
-
-                // Only send tiles that are visible by the player or a npc
-                for player in players.0.iter().filter(|p| p.id == players.main_id() || p.is_npc()) {
-                    if player.visible_tiles.contains(&(tile_c.x, tile_c.y)) {
-                        server_send_message.send(ServerSendMessage {
-                            message: ServerMessage::TileUpdate(tile.clone()),
-                            client: None,
-                        });
-
-                        client_send_message.send(ClientSendMessage {
-                            message: ClientMessage::TileUpdate(tile.clone()),
-                        });
-
-                        break;
-                    }
-                }
             }
         } else if let Some(pos) = pos {
             _spawn_tile(&mut commands, &tile, *pos, color, &background, &assets);
@@ -1,3 +1,4 @@
+use crate::core::alerts::{AlertEv, AlertKind};
 use crate::core::ants::components::{Action, Ant, AntCmp, Owned};
 use crate::core::ants::events::{DespawnAntEv, SpawnAntEv};
 use crate::core::constants::MONSTER_SPAWN_CHANCE;
@@ -12,6 +13,7 @@ use std::f32::consts::PI;
 
 pub fn spawn_enemies(
     mut spawn_ant_ev: EventWriter<SpawnAntEv>,
+    mut alert_ev: EventWriter<AlertEv>,
     mut game_settings: ResMut<GameSettings>,
     players: Res<Players>,
     map: Res<Map>,
@@ -20,7 +22,19 @@ pub fn spawn_enemies(
     if players.main_id() == 0 {
         map.tiles.iter().for_each(|tile| {
             if !tile.explored.is_empty() {
+                // Warn the colonies that know about the hole
+                let mut alert = || {
+                    for id in tile.explored.iter() {
+                        alert_ev.send(AlertEv::new(
+                            AlertKind::MonstersEmerged,
+                            *id,
+                            Map::get_coord_from_xy(tile.x, tile.y),
+                        ));
+                    }
+                };
+
                 if tile.texture_index == 64 && rng().random::<f32>() < MONSTER_SPAWN_CHANCE {
+                    alert();
                     spawn_ant_ev.send(SpawnAntEv {
                         ant: AntCmp::base(&Ant::Wasp),
                         transform: Transform {
@@ -44,6 +58,7 @@ pub fn spawn_enemies(
                         });
                     }
 
+                    alert();
                     game_settings.termite_queue = HashMap::from([((tile.x, tile.y), queue)]);
                 }
             }
@@ -71,6 +86,7 @@ pub fn spawn_enemies(
 pub fn resolve_expeditions(
     mut ant_q: Query<(Entity, &mut Transform, &mut Visibility, &mut AntCmp), With<Owned>>,
     mut despawn_ant_ev: EventWriter<DespawnAntEv>,
+    mut alert_ev: EventWriter<AlertEv>,
 ) {
     for (ant_e, mut ant_t, mut ant_v, mut ant) in ant_q.iter_mut() {
         if ant.action == Action::DoNothing {
@@ -92,6 +108,11 @@ pub fn resolve_expeditions(
                 0.98..1.0 => {
                     ant.action = Action::Idle;
                     *ant_v = Visibility::Inherited;
+                    alert_ev.send(AlertEv::new(
+                        AlertKind::ExpeditionReturned,
+                        ant.team,
+                        ant_t.translation.truncate(),
+                    ));
                 }
                 _ => (),
             }
@@ -64,6 +64,10 @@ impl PathCache {
 pub struct Map {
     pub tiles: Vec<Tile>,
     pub cache: PathCache,
+
+    /// Tiles changed since they were last sent to the clients
+    #[serde(skip)]
+    pub changed: HashSet<(u32, u32)>,
 }
 
 /// The default implementation is used as starting
@@ -75,6 +79,7 @@ impl Default for Map {
                 .flat_map(|y| (0..Self::MAP_SIZE.x).map(move |x| Tile::soil(x, y)))
                 .collect(),
             cache: PathCache::new(),
+            changed: HashSet::default(),
         }
     }
 }
@@ -401,12 +406,28 @@ impl Map {
         }
     }
 
+    /// Whether the location lies on the map
+    pub fn contains(loc: &Loc) -> bool {
+        loc.x < Self::MAP_SIZE.x && loc.y < Self::MAP_SIZE.y && loc.bit < Tile::SIDE.pow(2)
+    }
+
     pub fn is_walkable(&self, loc: &Loc) -> bool {
         self.get_tile(loc.x, loc.y).map_or(false, |tile| {
             tile.bitmap() & (1 << Tile::SIDE.pow(2) - loc.bit - 1) != 0
         })
     }
 
+    /// Whether a projectile can fly in a straight line between two positions
+    pub fn is_clear_shot(&self, start: &Vec3, end: &Vec3) -> bool {
+        let step = 0.5 * Tile::SIZE / Tile::SIDE as f32;
+        let steps = (start.truncate().distance(end.truncate()) / step)
+            .ceil()
+            .max(1.) as usize;
+
+        (0..=steps)
+            .all(|i| self.is_walkable(&self.get_loc(&start.lerp(*end, i as f32 / steps as f32))))
+    }
+
     pub fn get_neighbors(&self, loc: &Loc) -> Vec<Loc> {
         let moves = [
             (-1, 0),
@@ -620,12 +641,14 @@ impl Map {
         }
 
         self.replace_tile(&new_t);
+        self.changed.insert((new_t.x, new_t.y));
 
         // Replace tiles in the provided directions
         for dir in directions.iter() {
             if let Some(t) = self.get_adjacent_tile(tile.x, tile.y, &dir.opposite()) {
                 let new_t = self.find_tile(t, &HashSet::new());
                 self.replace_tile(&new_t);
+                self.changed.insert((new_t.x, new_t.y));
             }
         }
     }
@@ -7,8 +7,10 @@ use crate::core::game_settings::GameSettings;
 use crate::core::map::events::SpawnTileEv;
 use crate::core::map::map::Map;
 use crate::core::map::tile::Tile;
+use crate::core::menu::settings::FogOfWar;
 use crate::core::persistence::GameLoaded;
 use crate::core::player::{Player, Players};
+use crate::core::spectator::Spectator;
 use bevy::prelude::*;
 use rand::{rng, Rng};
 use std::f32::consts::PI;
@@ -63,15 +65,19 @@ pub fn draw_map(
     players: Res<Players>,
     map: Res<Map>,
     loaded: Option<Res<GameLoaded>>,
+    spectator: Option<Res<Spectator>>,
     assets: Local<WorldAssets>,
 ) {
     let (mut camera_t, mut projection) = camera.into_inner();
 
-    for (i, tile) in map
-        .world(&game_settings.fog_of_war, players.main_id())
-        .iter()
-        .enumerate()
-    {
+    // Observers start seeing the whole map
+    let fog_of_war = if spectator.is_some() {
+        FogOfWar::None
+    } else {
+        game_settings.fog_of_war
+    };
+
+    for (i, tile) in map.world(&fog_of_war, players.main_id()).iter().enumerate() {
         let pos = Vec2::new(
             Map::WORLD_VIEW.min.x + Tile::SIZE * ((i as u32 % Map::WORLD_SIZE.x) as f32 + 0.5),
             Map::WORLD_VIEW.max.y - Tile::SIZE * ((i as u32 / Map::WORLD_SIZE.x) as f32 + 0.5),
@@ -101,8 +107,8 @@ pub fn draw_map(
                     MapCmp,
                 ));
 
-                // Skip spawning the queen when loading a game
-                if loaded.is_none() && (player.id == players.main_id() || player.is_npc()) {
+                // The host spawns the queens of all colonies (except when loading a game)
+                if loaded.is_none() && players.main_id() == 0 {
                     spawn_ant_ev.send(SpawnAntEv {
                         ant: AntCmp::new(&Ant::Queen, player),
                         transform: Transform {
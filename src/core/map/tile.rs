@@ -1,4 +1,4 @@
-use crate::core::constants::{MAX_TERRAFORM_POINTS, NON_MAP_ID};
+use crate::core::constants::{LEAF_SYNC_STEP, MAX_TERRAFORM_POINTS, NON_MAP_ID};
 use crate::core::map::loc::Direction;
 use crate::core::map::utils::rotate_bitmap;
 use bevy::prelude::*;
@@ -31,6 +31,11 @@ impl Leaf {
             quantity: rng().random_range(500.0..900.),
         }
     }
+
+    /// Quantity in the steps in which it's synchronized with the clients
+    pub fn synced_quantity(&self) -> u32 {
+        (self.quantity / LEAF_SYNC_STEP).ceil() as u32
+    }
 }
 
 #[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -1,2 +1,3 @@
+pub mod selection;
 pub mod systems;
 pub mod utils;
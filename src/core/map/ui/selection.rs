@@ -0,0 +1,372 @@
+use crate::core::ants::components::{Animation, Ant, AntCmp};
+use crate::core::ants::selection::{AntSelection, SelectAntEv};
+use crate::core::assets::WorldAssets;
+use crate::core::constants::{LABEL_TEXT_SIZE, MAX_SELECTION_CARDS};
+use crate::core::locale::Locale;
+use crate::core::map::systems::MapCmp;
+use crate::core::map::ui::utils::{add_text, recolor};
+use crate::utils::NameFromEnum;
+use bevy::color::palettes::basic::{BLACK, LIME};
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashSet;
+use strum::IntoEnumIterator;
+
+/// Caste tab of the selection the player is commanding
+#[derive(Resource, Default)]
+pub struct SelectionPanel {
+    /// Whole selection before a caste tab was chosen
+    pub group: HashSet<Entity>,
+
+    /// Caste of the group that is selected (None for all)
+    pub caste: Option<Ant>,
+}
+
+#[derive(Component)]
+pub struct SelectionPanelCmp;
+
+#[derive(Component)]
+pub struct SelectionCardCmp(pub Entity);
+
+#[derive(Component)]
+pub struct CardHealthCmp(pub Entity);
+
+#[derive(Component)]
+pub struct CardInfoCmp(pub Entity);
+
+#[derive(Component)]
+pub struct CasteTabCmp(pub Option<Ant>);
+
+/// Text shown on the card of a selected ant
+fn card_info(ant: &AntCmp, locale: &Locale) -> String {
+    let mut lines = vec![
+        locale.get(&format!("task.{}", ant.action.to_snake())),
+        locale.get(&format!("behavior.{}", ant.get_behavior().to_snake())),
+    ];
+
+    if ant.carry.leaves > 0. || ant.carry.nutrients > 0. {
+        lines.push(locale.fmt(
+            "ui.carry",
+            &[
+                ("leaves", format!("{:.0}", ant.carry.leaves)),
+                ("nutrients", format!("{:.0}", ant.carry.nutrients)),
+            ],
+        ));
+    }
+
+    lines.push(locale.fmt("ui.kills", &[("n", ant.kills.to_string())]));
+
+    lines.join("\n")
+}
+
+fn on_click_selection_card(
+    trigger: Trigger<Pointer<Click>>,
+    card_q: Query<&SelectionCardCmp>,
+    selection: Res<AntSelection>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut select_ant_ev: EventWriter<SelectAntEv>,
+) {
+    if trigger.event.button != PointerButton::Primary {
+        return;
+    }
+
+    let SelectionCardCmp(ant_e) = card_q.get(trigger.entity()).unwrap();
+
+    // Releasing the mouse clears the selection, so the ants to keep are selected again
+    if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        for entity in selection.0.iter().filter(|e| *e != ant_e) {
+            select_ant_ev.send(SelectAntEv {
+                entity: *entity,
+                clean: false,
+            });
+        }
+    } else {
+        select_ant_ev.send(SelectAntEv {
+            entity: *ant_e,
+            clean: false,
+        });
+    }
+}
+
+fn on_click_caste_tab(
+    trigger: Trigger<Pointer<Click>>,
+    tab_q: Query<&CasteTabCmp>,
+    ant_q: Query<&AntCmp>,
+    selection: Res<AntSelection>,
+    mut panel: ResMut<SelectionPanel>,
+    mut select_ant_ev: EventWriter<SelectAntEv>,
+) {
+    let CasteTabCmp(caste) = tab_q.get(trigger.entity()).unwrap();
+
+    if panel.caste.is_none() {
+        panel.group = selection.0.clone();
+    }
+
+    panel.caste = caste.clone();
+
+    for entity in panel.group.iter().filter(|e| {
+        caste.is_none()
+            || ant_q
+                .get(**e)
+                .is_ok_and(|a| Some(&a.kind) == caste.as_ref())
+    }) {
+        select_ant_ev.send(SelectAntEv {
+            entity: *entity,
+            clean: false,
+        });
+    }
+}
+
+pub fn spawn_selection_panel(mut commands: Commands) {
+    commands.spawn((
+        Node {
+            bottom: Val::Percent(12.),
+            right: Val::Percent(6.),
+            width: Val::Percent(45.),
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::Column,
+            padding: UiRect::all(Val::Percent(0.5)),
+            ..default()
+        },
+        BackgroundColor(Color::srgba_u8(88, 57, 39, 200)),
+        BorderRadius::all(Val::Px(10.)),
+        Visibility::Hidden,
+        SelectionPanelCmp,
+        MapCmp,
+    ));
+}
+
+pub fn update_selection_panel(
+    mut commands: Commands,
+    mut panel_q: Query<(Entity, &mut Visibility), With<SelectionPanelCmp>>,
+    ant_q: Query<(Entity, &AntCmp)>,
+    mut health_q: Query<(&mut Node, &CardHealthCmp)>,
+    mut info_q: Query<(&mut Text, &CardInfoCmp)>,
+    selection: Res<AntSelection>,
+    mut panel: ResMut<SelectionPanel>,
+    locale: Res<Locale>,
+    assets: Local<WorldAssets>,
+    window: Single<&Window>,
+) {
+    let Ok((panel_e, mut panel_v)) = panel_q.get_single_mut() else {
+        return;
+    };
+
+    // Selecting other ants leaves the caste tab
+    if selection.is_changed()
+        && panel.caste.is_some()
+        && (selection.0.is_empty() || selection.0.iter().any(|e| !panel.group.contains(e)))
+    {
+        *panel = SelectionPanel::default();
+    }
+
+    if selection.is_changed() || panel.is_changed() || locale.is_changed() {
+        *panel_v = if selection.0.is_empty() {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+
+        let mut ants = ant_q
+            .iter_many(selection.0.iter())
+            .filter(|(_, a)| a.health > 0.)
+            .collect::<Vec<_>>();
+        ants.sort_by_key(|(e, a)| (Ant::iter().position(|k| k == a.kind), *e));
+
+        // The tabs show the castes of the whole group
+        let mut castes = ant_q
+            .iter_many(if panel.caste.is_some() {
+                panel.group.iter()
+            } else {
+                selection.0.iter()
+            })
+            .filter(|(_, a)| a.health > 0.)
+            .fold(Vec::<(Ant, usize)>::new(), |mut acc, (_, a)| {
+                match acc.iter_mut().find(|(k, _)| *k == a.kind) {
+                    Some((_, n)) => *n += 1,
+                    None => acc.push((a.kind.clone(), 1)),
+                }
+                acc
+            });
+        castes.sort_by_key(|(k, _)| Ant::iter().position(|a| a == *k));
+
+        commands
+            .entity(panel_e)
+            .despawn_descendants()
+            .with_children(|parent| {
+                if castes.len() > 1 || panel.caste.is_some() {
+                    parent
+                        .spawn(Node {
+                            flex_wrap: FlexWrap::Wrap,
+                            margin: UiRect::bottom(Val::Percent(1.)),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            let total = castes.iter().map(|(_, n)| n).sum::<usize>();
+                            let tabs = std::iter::once((
+                                None,
+                                locale.fmt("ui.all", &[("n", total.to_string())]),
+                            ))
+                            .chain(castes.iter().map(|(k, n)| {
+                                (
+                                    Some(k.clone()),
+                                    format!(
+                                        "{} ({n})",
+                                        locale.get(&format!("ant.{}", k.to_snake()))
+                                    ),
+                                )
+                            }));
+
+                            for (caste, label) in tabs {
+                                let color = if caste == panel.caste {
+                                    Color::srgba_u8(140, 100, 70, 255)
+                                } else {
+                                    Color::srgba_u8(60, 40, 25, 255)
+                                };
+
+                                parent
+                                    .spawn((
+                                        Node {
+                                            padding: UiRect::axes(
+                                                Val::Percent(1.5),
+                                                Val::Percent(0.5),
+                                            ),
+                                            margin: UiRect::right(Val::Percent(1.)),
+                                            ..default()
+                                        },
+                                        BackgroundColor(color),
+                                        BorderRadius::all(Val::Px(5.)),
+                                        CasteTabCmp(caste),
+                                    ))
+                                    .observe(recolor::<Pointer<Over>>(Color::srgba_u8(
+                                        140, 100, 70, 255,
+                                    )))
+                                    .observe(recolor::<Pointer<Out>>(color))
+                                    .observe(on_click_caste_tab)
+                                    .with_children(|parent| {
+                                        parent.spawn((
+                                            add_text(
+                                                label,
+                                                "bold",
+                                                LABEL_TEXT_SIZE,
+                                                &assets,
+                                                &window,
+                                            ),
+                                            PickingBehavior::IGNORE,
+                                        ));
+                                    });
+                            }
+                        });
+                }
+
+                parent
+                    .spawn(Node {
+                        flex_wrap: FlexWrap::Wrap,
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for (ant_e, ant) in ants.iter().take(MAX_SELECTION_CARDS) {
+                            let atlas = assets.atlas(&ant.atlas(&Animation::Idle));
+
+                            parent
+                                .spawn((
+                                    Node {
+                                        width: Val::Percent(9.),
+                                        flex_direction: FlexDirection::Column,
+                                        align_items: AlignItems::Center,
+                                        padding: UiRect::all(Val::Percent(0.3)),
+                                        margin: UiRect::all(Val::Percent(0.5)),
+                                        ..default()
+                                    },
+                                    BackgroundColor(Color::srgba_u8(60, 40, 25, 255)),
+                                    BorderRadius::all(Val::Px(5.)),
+                                    SelectionCardCmp(*ant_e),
+                                ))
+                                .observe(recolor::<Pointer<Over>>(Color::srgba_u8(
+                                    140, 100, 70, 255,
+                                )))
+                                .observe(recolor::<Pointer<Out>>(Color::srgba_u8(60, 40, 25, 255)))
+                                .observe(on_click_selection_card)
+                                .with_children(|parent| {
+                                    parent.spawn((
+                                        Node {
+                                            width: Val::Percent(70.),
+                                            aspect_ratio: Some(1.),
+                                            ..default()
+                                        },
+                                        ImageNode {
+                                            image: atlas.image,
+                                            texture_atlas: Some(atlas.texture),
+                                            ..default()
+                                        },
+                                        PickingBehavior::IGNORE,
+                                    ));
+
+                                    parent
+                                        .spawn((
+                                            Node {
+                                                width: Val::Percent(90.),
+                                                height: Val::Px(3.),
+                                                margin: UiRect::vertical(Val::Px(2.)),
+                                                ..default()
+                                            },
+                                            BackgroundColor(Color::from(BLACK)),
+                                            PickingBehavior::IGNORE,
+                                        ))
+                                        .with_children(|parent| {
+                                            parent.spawn((
+                                                Node {
+                                                    width: Val::Percent(
+                                                        100. * ant.health / ant.max_health,
+                                                    ),
+                                                    height: Val::Percent(100.),
+                                                    ..default()
+                                                },
+                                                BackgroundColor(Color::from(LIME)),
+                                                PickingBehavior::IGNORE,
+                                                CardHealthCmp(*ant_e),
+                                            ));
+                                        });
+
+                                    parent.spawn((
+                                        add_text(
+                                            card_info(ant, &locale),
+                                            "medium",
+                                            6.,
+                                            &assets,
+                                            &window,
+                                        ),
+                                        PickingBehavior::IGNORE,
+                                        CardInfoCmp(*ant_e),
+                                    ));
+                                });
+                        }
+
+                        if ants.len() > MAX_SELECTION_CARDS {
+                            parent.spawn(add_text(
+                                format!("+{}", ants.len() - MAX_SELECTION_CARDS),
+                                "bold",
+                                LABEL_TEXT_SIZE,
+                                &assets,
+                                &window,
+                            ));
+                        }
+                    });
+            });
+    }
+
+    // Keep the health, action and carry of the cards up to date
+    for (mut node, CardHealthCmp(ant_e)) in &mut health_q {
+        if let Ok((_, ant)) = ant_q.get(*ant_e) {
+            node.width = Val::Percent(100. * ant.health / ant.max_health);
+        }
+    }
+
+    for (mut text, CardInfoCmp(ant_e)) in &mut info_q {
+        if let Ok((_, ant)) = ant_q.get(*ant_e) {
+            let info = card_info(ant, &locale);
+            if text.0 != info {
+                text.0 = info;
+            }
+        }
+    }
+}
@@ -1,12 +1,17 @@
-use crate::core::ants::components::{Animation, AnimationCmp, Ant, AntCmp};
+use crate::core::ants::components::{Animation, AnimationCmp, Ant, AntCmp, DamageType};
 use crate::core::ants::events::QueueAntEv;
 use crate::core::ants::selection::AntSelection;
 use crate::core::assets::WorldAssets;
 use crate::core::constants::{BUTTON_TEXT_SIZE, MAX_QUEUE_LENGTH, TITLE_TEXT_SIZE};
+use crate::core::keymap::{key_name, InputAction, Keymap};
+use crate::core::locale::{Locale, Localized};
 use crate::core::map::systems::MapCmp;
 use crate::core::map::ui::utils::{add_root_node, add_text, despawn_ui};
 use crate::core::menu::buttons::MenuCmp;
+use crate::core::network::{ClientMessage, ClientSendMessage};
 use crate::core::player::Players;
+use crate::core::spectator::Spectator;
+use crate::core::stats::GameStats;
 use crate::core::traits::{Trait, TraitCmp, TraitSelectedEv};
 use crate::utils::NameFromEnum;
 use bevy::prelude::*;
@@ -41,8 +46,20 @@ pub struct InfoPanelUi;
 pub fn ant_hover_info_panel(
     ant: AntCmp,
     total: usize,
-) -> impl FnMut(Trigger<Pointer<Over>>, Commands, Local<WorldAssets>, Single<&Window>) {
-    move |_, mut commands: Commands, assets: Local<WorldAssets>, window: Single<&Window>| {
+) -> impl FnMut(
+    Trigger<Pointer<Over>>,
+    Commands,
+    Res<Players>,
+    Res<Locale>,
+    Local<WorldAssets>,
+    Single<&Window>,
+) {
+    move |_,
+          mut commands: Commands,
+          players: Res<Players>,
+          locale: Res<Locale>,
+          assets: Local<WorldAssets>,
+          window: Single<&Window>| {
         commands
             .spawn((
                 Node {
@@ -66,7 +83,13 @@ pub fn ant_hover_info_panel(
                         ..default()
                     },))
                     .with_children(|parent| {
-                        parent.spawn(add_text(ant.kind.to_title(), "bold", 15., &assets, &window));
+                        parent.spawn(add_text(
+                            locale.get(&format!("ant.{}", ant.kind.to_snake())),
+                            "bold",
+                            15.,
+                            &assets,
+                            &window,
+                        ));
                     });
 
                 parent
@@ -78,13 +101,14 @@ pub fn ant_hover_info_panel(
                     })
                     .with_children(|parent| {
                         let attributes = [
-                            ("Leaves", ant.price.leaves),
-                            ("Nutrients", ant.price.nutrients),
-                            ("Health", ant.max_health),
-                            ("Speed", ant.speed),
-                            ("Damage", ant.damage),
-                            ("Hatch time", ant.hatch_time),
-                            ("Carry capacity", ant.max_carry.leaves),
+                            ("leaves", ant.price.leaves),
+                            ("nutrients", ant.price.nutrients),
+                            ("health", ant.max_health),
+                            ("speed", ant.speed),
+                            ("damage", ant.damage),
+                            ("range", ant.range),
+                            ("hatch_time", ant.hatch_time),
+                            ("carry_capacity", ant.max_carry.leaves),
                         ];
 
                         for (k, v) in attributes.iter() {
@@ -96,7 +120,10 @@ pub fn ant_hover_info_panel(
                                         ..default()
                                     },
                                     add_text(
-                                        format!("{k}: {:.0}", v),
+                                        locale.fmt(
+                                            &format!("attr.{k}"),
+                                            &[("value", format!("{v:.0}"))],
+                                        ),
                                         "bold",
                                         8.,
                                         &assets,
@@ -105,9 +132,71 @@ pub fn ant_hover_info_panel(
                                 ));
                             }
                         }
+
+                        // Damage type and the modifiers of the damage received per type
+                        let armor = DamageType::iter()
+                            .filter_map(|d| {
+                                let factor = ant.kind.resistance(&d);
+                                (factor != 1.).then(|| {
+                                    format!(
+                                        "{} {:+.0}%",
+                                        locale.get(&format!("damage.{}", d.to_snake())),
+                                        (factor - 1.) * 100.
+                                    )
+                                })
+                            })
+                            .collect::<Vec<_>>();
+
+                        let damage_type = ant.kind.damage_type().to_snake();
+                        let mut lines = vec![locale.fmt(
+                            "attr.damage_type",
+                            &[("type", locale.get(&format!("damage.{damage_type}")))],
+                        )];
+                        if !armor.is_empty() {
+                            lines.push(
+                                locale.fmt("attr.damage_taken", &[("types", armor.join(", "))]),
+                            );
+                        }
+
+                        // Multipliers of the damage dealt per enemy type from the player's traits
+                        let player = players.main();
+                        let bonus = Ant::iter()
+                            .filter_map(|a| {
+                                let factor = player.damage_factor(&a);
+                                (factor != 1.).then(|| {
+                                    format!(
+                                        "{} {:+.0}%",
+                                        locale.get(&format!("ant.{}", a.to_snake())),
+                                        (factor - 1.) * 100.
+                                    )
+                                })
+                            })
+                            .collect::<Vec<_>>();
+
+                        if !bonus.is_empty() {
+                            lines.push(
+                                locale.fmt("attr.damage_bonus", &[("types", bonus.join(", "))]),
+                            );
+                        }
+
+                        for text in lines {
+                            parent.spawn((
+                                Node {
+                                    margin: UiRect::ZERO.with_bottom(Val::Percent(2.)),
+                                    ..default()
+                                },
+                                add_text(text, "bold", 8., &assets, &window),
+                            ));
+                        }
                     });
 
-                parent.spawn(add_text(ant.description(), "medium", 8., &assets, &window));
+                parent.spawn(add_text(
+                    locale.get(&ant.description_id()),
+                    "medium",
+                    8.,
+                    &assets,
+                    &window,
+                ));
             });
     }
 }
@@ -115,8 +204,20 @@ pub fn ant_hover_info_panel(
 pub fn trait_hover_info_panel(
     t: TraitCmp,
     i: usize,
-) -> impl FnMut(Trigger<Pointer<Over>>, Commands, Local<WorldAssets>, Single<&Window>) {
-    move |_, mut commands: Commands, assets: Local<WorldAssets>, window: Single<&Window>| {
+) -> impl FnMut(
+    Trigger<Pointer<Over>>,
+    Commands,
+    Res<Players>,
+    Res<Locale>,
+    Local<WorldAssets>,
+    Single<&Window>,
+) {
+    move |_,
+          mut commands: Commands,
+          players: Res<Players>,
+          locale: Res<Locale>,
+          assets: Local<WorldAssets>,
+          window: Single<&Window>| {
         commands
             .spawn((
                 Node {
@@ -140,10 +241,22 @@ pub fn trait_hover_info_panel(
                         ..default()
                     },))
                     .with_children(|parent| {
-                        parent.spawn(add_text(t.kind.to_title(), "bold", 15., &assets, &window));
+                        parent.spawn(add_text(
+                            locale.get(&format!("trait.{}", t.kind.to_snake())),
+                            "bold",
+                            15.,
+                            &assets,
+                            &window,
+                        ));
                     });
 
-                parent.spawn(add_text(&t.description, "medium", 8., &assets, &window));
+                parent.spawn(add_text(
+                    locale.get(&t.description_id()),
+                    "medium",
+                    8.,
+                    &assets,
+                    &window,
+                ));
             });
     }
 }
@@ -151,9 +264,16 @@ pub fn trait_hover_info_panel(
 pub fn draw_ui(
     mut commands: Commands,
     players: Res<Players>,
+    keymap: Res<Keymap>,
+    spectator: Option<Res<Spectator>>,
     assets: Local<WorldAssets>,
     window: Single<&Window>,
 ) {
+    // Observers have no colony to manage
+    if spectator.is_some() {
+        return;
+    }
+
     let player = players.main();
 
     commands
@@ -288,7 +408,7 @@ pub fn draw_ui(
                                 ));
                             });
 
-                        if let Some(key) = ant_c.key {
+                        if ant_c.key.is_some() {
                             parent
                                 .spawn(Node {
                                     bottom: Val::Percent(10.),
@@ -298,7 +418,7 @@ pub fn draw_ui(
                                 })
                                 .with_children(|parent| {
                                     parent.spawn(add_text(
-                                        key.to_name().chars().last().unwrap().to_string(),
+                                        key_name(&keymap.key(&InputAction::QueueAnt(ant.clone()))),
                                         "bold",
                                         10.,
                                         &assets,
@@ -507,24 +627,30 @@ pub fn on_click_queue_button(
     trigger: Trigger<Pointer<Click>>,
     btn_q: Query<&QueueButtonCmp>,
     mut players: ResMut<Players>,
+    mut stats: ResMut<GameStats>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
 ) {
-    let player = players.main_mut();
-
     if trigger.event.button == PointerButton::Secondary {
         if let Ok(QueueButtonCmp(i, _)) = btn_q.get(trigger.entity()) {
-            if let Some(ant) = player.queue.get(*i) {
-                let price = AntCmp::new(ant, &player).price;
-                player.resources += price;
-                player.queue.remove(*i);
+            if let Some((ant, price)) = players.main_mut().dequeue(*i) {
+                stats.colony(players.main_id()).refund(&ant, &price);
             }
+            client_send_message.send(ClientSendMessage {
+                message: ClientMessage::DequeueAnt(*i),
+            });
         }
     }
 }
 
-pub fn select_trait(t: Trait) -> impl FnMut(Trigger<Pointer<Click>>, EventWriter<TraitSelectedEv>) {
-    move |trigger: Trigger<Pointer<Click>>, mut trait_selected_ev: EventWriter<TraitSelectedEv>| {
+pub fn select_trait(
+    t: Trait,
+) -> impl FnMut(Trigger<Pointer<Click>>, EventWriter<TraitSelectedEv>, Res<Players>) {
+    move |trigger: Trigger<Pointer<Click>>,
+          mut trait_selected_ev: EventWriter<TraitSelectedEv>,
+          players: Res<Players>| {
         if trigger.event.button == PointerButton::Primary {
             trait_selected_ev.send(TraitSelectedEv {
+                id: players.main_id(),
                 selected: t.clone(),
             });
         }
@@ -553,12 +679,9 @@ pub fn setup_trait_selection(
                     ..default()
                 })
                 .with_children(|parent| {
-                    parent.spawn(add_text(
-                        "Choose a trait",
-                        "bold",
-                        TITLE_TEXT_SIZE,
-                        &assets,
-                        &window,
+                    parent.spawn((
+                        add_text("", "bold", TITLE_TEXT_SIZE, &assets, &window),
+                        Localized::new("ui.choose_trait"),
                     ));
                 });
 
@@ -597,12 +720,12 @@ pub fn setup_trait_selection(
                                         ..default()
                                     })
                                     .with_children(|parent| {
-                                        parent.spawn(add_text(
-                                            trait_c.kind.to_title(),
-                                            "bold",
-                                            15.,
-                                            &assets,
-                                            &window,
+                                        parent.spawn((
+                                            add_text("", "bold", 15., &assets, &window),
+                                            Localized::new(format!(
+                                                "trait.{}",
+                                                trait_c.kind.to_snake()
+                                            )),
                                         ));
                                     });
 
@@ -642,13 +765,8 @@ pub fn setup_trait_selection(
                                                         margin: UiRect::all(Val::Percent(3.)),
                                                         ..default()
                                                     },
-                                                    add_text(
-                                                        &trait_c.description,
-                                                        "medium",
-                                                        8.,
-                                                        &assets,
-                                                        &window,
-                                                    ),
+                                                    add_text("", "medium", 8., &assets, &window),
+                                                    Localized::new(trait_c.description_id()),
                                                 ));
                                             });
                                     });
@@ -666,12 +784,9 @@ pub fn setup_after_trait(
     commands
         .spawn((add_root_node(), MenuCmp))
         .with_children(|parent| {
-            parent.spawn(add_text(
-                "Waiting for other players to select a trait...".to_string(),
-                "bold",
-                BUTTON_TEXT_SIZE,
-                &assets,
-                &window,
+            parent.spawn((
+                add_text("", "bold", BUTTON_TEXT_SIZE, &assets, &window),
+                Localized::new("ui.waiting_trait"),
             ));
         });
 }
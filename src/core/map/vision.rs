@@ -5,12 +5,13 @@ use crate::core::map::map::Map;
 use crate::core::map::tile::Tile;
 use crate::core::map::utils::reveal_tiles;
 use crate::core::menu::settings::FogOfWar;
-use crate::core::network::{ClientMessage, ClientSendMessage};
 use crate::core::player::Players;
+use crate::core::spectator::Spectator;
 use bevy::color::Color;
 use bevy::hierarchy::Children;
 use bevy::prelude::*;
 use bevy::utils::hashbrown::HashSet;
+use bevy_renet::renet::ClientId;
 use crate::core::constants::NO_VISION_COLOR;
 
 pub fn update_vision(
@@ -23,10 +24,27 @@ pub fn update_vision(
     game_settings: Res<GameSettings>,
     mut players: ResMut<Players>,
     mut map: ResMut<Map>,
-    mut client_send_message: EventWriter<ClientSendMessage>,
+    spectator: Option<Res<Spectator>>,
 ) {
+    // The host calculates the vision of every colony, clients only their own
     let id = players.main_id();
-    for player in players.0.iter_mut().filter(|p| p.id == id || p.is_npc()) {
+
+    // Observers see through the eyes of the selected colony or the whole map
+    let viewer = spectator.as_ref().and_then(|s| s.view).unwrap_or(id);
+    let full_vision = spectator
+        .as_ref()
+        .is_some_and(|s| s.view.is_none() || game_settings.fog_of_war == FogOfWar::None);
+    let fog_of_war = if full_vision {
+        FogOfWar::None
+    } else {
+        game_settings.fog_of_war
+    };
+
+    for player in players
+        .0
+        .iter_mut()
+        .filter(|p| p.id == viewer || (id == 0 && p.id != ClientId::MAX))
+    {
         player.visible_tiles = HashSet::new();
 
         // Calculate all tiles currently visible by the player
@@ -62,20 +80,12 @@ pub fn update_vision(
             .filter(|t| player.visible_tiles.contains(&(t.x, t.y)))
             .for_each(|t| {
                 t.explored.insert(player.id);
-
-                // Only the server must know the explored tiles since it manages monsters
-                client_send_message.send(ClientSendMessage {
-                    message: ClientMessage::TileExplored {
-                        tile: (t.x, t.y),
-                        client: player.id,
-                    },
-                });
             });
 
-        if player.is_human() {
-            if game_settings.fog_of_war != FogOfWar::Full {
+        if player.id == viewer {
+            if fog_of_war != FogOfWar::Full {
                 // Spawn all tiles to keep the map up to date
-                // (only changed tiles are actually spawned and send over the network)
+                // (only changed tiles are actually spawned)
                 map.tiles.iter().for_each(|tile| {
                     spawn_tile_ev.send(SpawnTileEv {
                         tile: tile.clone(),
@@ -95,10 +105,10 @@ pub fn update_vision(
                 });
             }
 
-            // Adjust the fog of war on the map
-            if game_settings.fog_of_war != FogOfWar::None {
+            // Adjust the fog of war on the map (observers also reset it when switching views)
+            if fog_of_war != FogOfWar::None || spectator.is_some() {
                 tile_q.iter_mut().for_each(|(tile_e, mut sprite, tile)| {
-                    let color = if player.visible_tiles.contains(&(tile.x, tile.y)) {
+                    let color = if full_vision || player.visible_tiles.contains(&(tile.x, tile.y)) {
                         Color::WHITE
                     } else {
                         NO_VISION_COLOR
@@ -118,8 +128,8 @@ pub fn update_vision(
 
                 // Show/hide enemies on the map
                 for (_, ant_t, mut ant_v, ant) in &mut ant_q {
-                    if ant.team != player.id {
-                        if map
+                    if ant.team != player.id || spectator.is_some() {
+                        if full_vision || map
                             .get_tile_from_coord(&ant_t.translation)
                             .map_or(false, |tile| {
                                 player.visible_tiles.contains(&(tile.x, tile.y))
@@ -135,8 +145,8 @@ pub fn update_vision(
                 }
 
                 for (_, egg_t, mut egg_v, egg) in &mut egg_q {
-                    if egg.team != player.id {
-                        if map
+                    if egg.team != player.id || spectator.is_some() {
+                        if full_vision || map
                             .get_tile_from_coord(&egg_t.translation)
                             .map_or(false, |tile| {
                                 player.visible_tiles.contains(&(tile.x, tile.y))
@@ -1,15 +1,25 @@
 use crate::core::assets::WorldAssets;
+use crate::core::audio::PlayAudioEv;
 use crate::core::constants::*;
 use crate::core::game_settings::{GameMode, GameSettings};
+use crate::core::keymap::Keymap;
+use crate::core::locale::{Locale, Localized};
 use crate::core::map::systems::create_map;
 use crate::core::map::ui::utils::{add_text, recolor};
-use crate::core::menu::systems::Ip;
-use crate::core::network::{new_renet_client, new_renet_server, ServerMessage, ServerSendMessage};
+use crate::core::menu::controls::Rebinding;
+use crate::core::menu::lobby::Lobby;
+use crate::core::menu::systems::{Ip, Password};
+use crate::core::multiplayer::start_multiplayer_game;
+use crate::core::network::{
+    new_renet_client, new_renet_server, request_connection, HostSettings, PendingConnection,
+    ReconnectToken, ServerSendMessage, TokenServer,
+};
 use crate::core::persistence::{LoadGameEv, SaveGameEv};
 use crate::core::player::{Player, Players};
 use crate::core::states::{AppState, GameState};
 use crate::utils::NameFromEnum;
 use bevy::prelude::*;
+use bevy::tasks::{block_on, futures_lite::future};
 use bevy_renet::netcode::{NetcodeClientTransport, NetcodeServerTransport};
 use bevy_renet::renet::{RenetClient, RenetServer};
 
@@ -24,10 +34,14 @@ pub enum MenuBtn {
     Multiplayer,
     HostGame,
     FindGame,
+    Spectate,
     Back,
     Continue,
     SaveGame,
     Settings,
+    Controls,
+    ResetControls,
+    Mixer,
     Quit,
 }
 
@@ -40,6 +54,66 @@ pub struct LobbyTextCmp;
 #[derive(Component)]
 pub struct IpTextCmp;
 
+#[derive(Component)]
+pub struct PasswordTextCmp;
+
+#[derive(Component)]
+pub struct ErrorTextCmp;
+
+fn set_menu_text(world: &mut World, message: String) {
+    let mut text_q = world.query_filtered::<&mut Text, With<ErrorTextCmp>>();
+    if let Ok(mut text) = text_q.get_single_mut(world) {
+        text.0 = message;
+    }
+}
+
+/// Show a message in the menu
+fn show_menu_text(commands: &mut Commands, id: &'static str, args: Vec<(&'static str, String)>) {
+    commands.queue(move |w: &mut World| {
+        let message = w.resource::<Locale>().fmt(id, &args);
+        set_menu_text(w, message);
+    });
+}
+
+/// Show an error message in the menu
+fn show_menu_error(commands: &mut Commands, err: String) {
+    commands.queue(move |w: &mut World| {
+        w.send_event(PlayAudioEv::new("error"));
+        set_menu_text(w, err);
+    });
+}
+
+/// Join the lobby once the host sent the connect token
+pub fn connect_to_host(
+    mut commands: Commands,
+    mut pending: ResMut<PendingConnection>,
+    app_state: Res<State<AppState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+) {
+    // Drop the attempt when the player left the menu
+    if *app_state.get() != AppState::MultiPlayerMenu {
+        commands.remove_resource::<PendingConnection>();
+        return;
+    }
+
+    let Some(result) = block_on(future::poll_once(&mut pending.0)) else {
+        return;
+    };
+
+    commands.remove_resource::<PendingConnection>();
+
+    match result.and_then(|(server_addr, token)| new_renet_client(server_addr, token)) {
+        Ok((client, transport)) => {
+            commands.insert_resource(client);
+            commands.insert_resource(transport);
+            commands.insert_resource(Lobby::default());
+
+            next_app_state.set(AppState::Lobby);
+        }
+        Err(err) => show_menu_error(&mut commands, err),
+    }
+}
+
 pub fn on_click_menu_button(
     trigger: Trigger<Pointer<Click>>,
     mut commands: Commands,
@@ -48,6 +122,9 @@ pub fn on_click_menu_button(
     mut client: Option<ResMut<RenetClient>>,
     mut game_settings: ResMut<GameSettings>,
     ip: Res<Ip>,
+    password: Res<Password>,
+    reconnect: Option<Res<ReconnectToken>>,
+    lobby: Res<Lobby>,
     mut load_game_ev: EventWriter<LoadGameEv>,
     mut save_game_ev: EventWriter<SaveGameEv>,
     mut server_send_message: EventWriter<ServerSendMessage>,
@@ -70,7 +147,8 @@ pub fn on_click_menu_button(
 
             // Add the NPCs
             (1..=game_settings.npcs)
-                .for_each(|id| players.push(Player::new(id, game_settings.color.inverse())));
+                .zip(game_settings.color.others())
+                .for_each(|(id, color)| players.push(Player::new(id, color)));
 
             let map = if *app_state.get() == AppState::SinglePlayerMenu {
                 game_settings.game_mode = GameMode::SinglePlayer;
@@ -80,36 +158,24 @@ pub fn on_click_menu_button(
 
                 // Add the NPCs to the resource
                 (1..=game_settings.npcs)
-                    .for_each(|id| players.push(Player::new(id, game_settings.color.inverse())));
+                    .zip(game_settings.color.others())
+                    .for_each(|(id, color)| players.push(Player::new(id, color)));
 
                 create_map(&players)
             } else {
-                game_settings.game_mode = GameMode::Multiplayer;
-
-                let server = server.unwrap();
-
-                // Add clients to the resource
-                server
-                    .clients_id()
-                    .iter()
-                    .for_each(|id| players.push(Player::new(*id, game_settings.color)));
-
-                let map = create_map(&players);
-
-                // Send the start game signal to all clients with their player id
-                for client in server.clients_id().iter() {
-                    server_send_message.send(ServerSendMessage {
-                        message: ServerMessage::StartGame {
-                            id: *client,
-                            background: game_settings.background,
-                            fog_of_war: game_settings.fog_of_war,
-                            map: map.clone(),
-                        },
-                        client: Some(*client),
-                    });
-                }
+                // The colonies and settings are the ones chosen in the lobby
+                players = lobby.to_players();
+                game_settings.apply_host_rules(lobby.background, lobby.fog_of_war, lobby.upkeep);
+                game_settings.npcs = lobby.npcs();
 
-                map
+                start_multiplayer_game(
+                    &mut commands,
+                    &server.unwrap(),
+                    &game_settings,
+                    &players,
+                    &mut server_send_message,
+                    false,
+                )
             };
 
             // Add the default player (used for monsters)
@@ -133,31 +199,52 @@ pub fn on_click_menu_button(
                 commands.remove_resource::<NetcodeClientTransport>();
             }
 
-            let (server, transport) = new_renet_server();
-            commands.insert_resource(server);
-            commands.insert_resource(transport);
+            let settings = HostSettings {
+                password: password.0.clone(),
+                ..default()
+            };
 
-            next_app_state.set(AppState::Lobby);
+            match new_renet_server(&settings) {
+                Ok((server, transport, token_server)) => {
+                    commands.insert_resource(server);
+                    commands.insert_resource(transport);
+                    commands.insert_resource(token_server);
+                    commands.insert_resource(Lobby::new(&game_settings, true));
+
+                    next_app_state.set(AppState::Lobby);
+                }
+                Err(err) => show_menu_error(&mut commands, err),
+            }
         }
-        MenuBtn::FindGame => {
-            let (server, transport) = new_renet_client(&ip.0);
-            commands.insert_resource(server);
-            commands.insert_resource(transport);
+        MenuBtn::FindGame | MenuBtn::Spectate => {
+            // Use the token of the last game (if any) to rejoin it (observers don't need it)
+            let observer = *btn == MenuBtn::Spectate;
+            let reconnect = reconnect.as_deref().filter(|_| !observer);
 
-            next_app_state.set(AppState::Lobby);
+            // A new attempt replaces the one in progress (if any)
+            match request_connection(&ip.0, &password.0, reconnect, observer) {
+                Ok(connection) => {
+                    commands.insert_resource(connection);
+                    show_menu_text(&mut commands, "menu.connecting", vec![("ip", ip.0.clone())]);
+                }
+                Err(err) => show_menu_error(&mut commands, err),
+            }
         }
         MenuBtn::Back => match *app_state.get() {
             AppState::SinglePlayerMenu | AppState::MultiPlayerMenu | AppState::Settings => {
                 next_app_state.set(AppState::MainMenu);
             }
-            AppState::Lobby => {
+            AppState::Controls | AppState::Mixer => next_app_state.set(AppState::Settings),
+            AppState::Lobby | AppState::ConnectedLobby => {
                 if let Some(client) = client.as_mut() {
                     client.disconnect();
                     commands.remove_resource::<RenetClient>();
+                    commands.remove_resource::<NetcodeClientTransport>();
                 } else if let Some(mut server) = server {
                     server.disconnect_all();
                     commands.remove_resource::<RenetServer>();
                     commands.remove_resource::<NetcodeServerTransport>();
+                    commands.remove_resource::<TokenServer>();
                 }
 
                 next_app_state.set(AppState::MultiPlayerMenu);
@@ -173,6 +260,18 @@ pub fn on_click_menu_button(
         MenuBtn::Settings => {
             next_app_state.set(AppState::Settings);
         }
+        MenuBtn::Controls => {
+            commands.insert_resource(Rebinding::default());
+            next_app_state.set(AppState::Controls);
+        }
+        MenuBtn::Mixer => {
+            next_app_state.set(AppState::Mixer);
+        }
+        MenuBtn::ResetControls => {
+            let keymap = Keymap::default();
+            keymap.save();
+            commands.insert_resource(keymap);
+        }
         MenuBtn::Quit => match *app_state.get() {
             AppState::Game => {
                 if let Some(client) = client.as_mut() {
@@ -182,6 +281,7 @@ pub fn on_click_menu_button(
                     server.disconnect_all();
                     commands.remove_resource::<RenetServer>();
                     commands.remove_resource::<NetcodeServerTransport>();
+                    commands.remove_resource::<TokenServer>();
                 }
 
                 next_game_state.set(GameState::default());
@@ -202,11 +302,13 @@ pub fn spawn_menu_button(
     parent
         .spawn((
             Node {
-                width: Val::Percent(25.),
+                // Grow with the label since some languages have much longer texts
+                min_width: Val::Percent(25.),
                 height: Val::Percent(10.),
                 align_items: AlignItems::Center,
                 justify_content: JustifyContent::Center,
                 margin: UiRect::all(Val::Percent(1.)),
+                padding: UiRect::horizontal(Val::Percent(1.)),
                 ..default()
             },
             BackgroundColor(NORMAL_BUTTON_COLOR),
@@ -218,12 +320,9 @@ pub fn spawn_menu_button(
         .observe(recolor::<Pointer<Up>>(HOVERED_BUTTON_COLOR))
         .observe(on_click_menu_button)
         .with_children(|parent| {
-            parent.spawn(add_text(
-                btn.to_title(),
-                "bold",
-                BUTTON_TEXT_SIZE,
-                assets,
-                window,
+            parent.spawn((
+                add_text("", "bold", BUTTON_TEXT_SIZE, assets, window),
+                Localized::new(format!("menu.{}", btn.to_snake())),
             ));
         });
 }
@@ -0,0 +1,204 @@
+use crate::core::assets::WorldAssets;
+use crate::core::audio::PlayAudioEv;
+use crate::core::constants::*;
+use crate::core::keymap::{key_name, InputAction, Keymap};
+use crate::core::locale::Locale;
+use crate::core::map::ui::utils::{add_text, recolor};
+use bevy::hierarchy::{ChildBuild, ChildBuilder};
+use bevy::prelude::*;
+
+/// Button to change the key bound to an action
+#[derive(Component)]
+pub struct KeyBindingBtn(pub InputAction);
+
+#[derive(Component)]
+pub struct KeyBindingTextCmp(pub InputAction);
+
+#[derive(Component)]
+pub struct ControlsTextCmp;
+
+/// Action waiting for the player to press its new key
+#[derive(Resource, Default)]
+pub struct Rebinding {
+    pub action: Option<InputAction>,
+    pub error: String,
+}
+
+fn on_click_key_binding(
+    trigger: Trigger<Pointer<Click>>,
+    btn_q: Query<&KeyBindingBtn>,
+    mut rebinding: ResMut<Rebinding>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+) {
+    if let Ok(KeyBindingBtn(action)) = btn_q.get(trigger.entity()) {
+        rebinding.action = Some(action.clone());
+        rebinding.error = String::new();
+        play_audio_ev.send(PlayAudioEv::new("button"));
+    }
+}
+
+pub fn spawn_controls(
+    parent: &mut ChildBuilder,
+    keymap: &Keymap,
+    locale: &Locale,
+    assets: &WorldAssets,
+    window: &Window,
+) {
+    parent.spawn((
+        add_text(
+            locale.get("controls.hint"),
+            "medium",
+            SUBTITLE_TEXT_SIZE,
+            assets,
+            window,
+        ),
+        ControlsTextCmp,
+    ));
+
+    parent
+        .spawn(Node {
+            width: Val::Percent(80.),
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::Wrap,
+            justify_content: JustifyContent::Center,
+            margin: UiRect::vertical(Val::Percent(1.)),
+            ..default()
+        })
+        .with_children(|parent| {
+            for action in InputAction::all() {
+                parent
+                    .spawn(Node {
+                        width: Val::Percent(30.),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::SpaceBetween,
+                        flex_direction: FlexDirection::Row,
+                        margin: UiRect::axes(Val::Percent(1.), Val::Percent(0.3)),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(add_text(
+                            action.to_label(locale),
+                            "bold",
+                            LABEL_TEXT_SIZE,
+                            assets,
+                            window,
+                        ));
+
+                        parent
+                            .spawn((
+                                Node {
+                                    width: Val::Percent(35.),
+                                    align_items: AlignItems::Center,
+                                    justify_content: JustifyContent::Center,
+                                    padding: UiRect::all(Val::Percent(2.)),
+                                    ..default()
+                                },
+                                BackgroundColor(NORMAL_BUTTON_COLOR),
+                                KeyBindingBtn(action.clone()),
+                                Button,
+                            ))
+                            .observe(recolor::<Pointer<Over>>(HOVERED_BUTTON_COLOR))
+                            .observe(recolor::<Pointer<Out>>(NORMAL_BUTTON_COLOR))
+                            .observe(recolor::<Pointer<Down>>(PRESSED_BUTTON_COLOR))
+                            .observe(recolor::<Pointer<Up>>(HOVERED_BUTTON_COLOR))
+                            .observe(on_click_key_binding)
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    add_text(
+                                        key_name(&keymap.key(&action)),
+                                        "bold",
+                                        LABEL_TEXT_SIZE,
+                                        assets,
+                                        window,
+                                    ),
+                                    KeyBindingTextCmp(action),
+                                ));
+                            });
+                    });
+            }
+        });
+}
+
+pub fn rebind_key(
+    mut keymap: ResMut<Keymap>,
+    mut rebinding: ResMut<Rebinding>,
+    mut keyboard: ResMut<ButtonInput<KeyCode>>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    locale: Res<Locale>,
+) {
+    let Some(action) = rebinding.action.clone() else {
+        return;
+    };
+
+    if let Some(key) = keyboard.get_just_pressed().next().copied() {
+        if key == KeyCode::Escape {
+            rebinding.action = None;
+        } else if Keymap::is_reserved(&key) {
+            rebinding.error = locale.fmt("controls.reserved", &[("key", key_name(&key))]);
+            play_audio_ev.send(PlayAudioEv::new("error"));
+        } else {
+            keymap.bind(&action, key);
+            keymap.save();
+            rebinding.action = None;
+        }
+
+        // Don't trigger the menu's shortcuts with the new key
+        keyboard.reset_all();
+    }
+}
+
+pub fn update_controls(
+    mut text_q: Query<(&mut Text, &mut TextColor, &KeyBindingTextCmp)>,
+    mut info_q: Query<&mut Text, (With<ControlsTextCmp>, Without<KeyBindingTextCmp>)>,
+    keymap: Res<Keymap>,
+    rebinding: Res<Rebinding>,
+    locale: Res<Locale>,
+) {
+    let mut conflicts = vec![];
+    for (mut text, mut color, KeyBindingTextCmp(action)) in &mut text_q {
+        let key = keymap.key(action);
+
+        text.0 = if rebinding.action.as_ref() == Some(action) {
+            "...".to_string()
+        } else {
+            key_name(&key)
+        };
+
+        // Mark the keys that are bound to more than one action
+        color.0 = if keymap.conflicts(action).is_empty() {
+            Color::WHITE
+        } else {
+            if !conflicts.contains(&key) {
+                conflicts.push(key);
+            }
+            DISABLED_BUTTON_COLOR
+        };
+    }
+
+    if let Ok(mut text) = info_q.get_single_mut() {
+        text.0 = if let Some(action) = &rebinding.action {
+            format!(
+                "{} {}",
+                locale.fmt(
+                    "controls.press_key",
+                    &[("action", action.to_label(&locale))]
+                ),
+                rebinding.error
+            )
+        } else if !conflicts.is_empty() {
+            locale.fmt(
+                "controls.conflicts",
+                &[(
+                    "keys",
+                    conflicts
+                        .iter()
+                        .map(key_name)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                )],
+            )
+        } else {
+            locale.get("controls.hint")
+        };
+    }
+}
@@ -0,0 +1,593 @@
+use crate::core::assets::WorldAssets;
+use crate::core::constants::*;
+use crate::core::game_settings::GameSettings;
+use crate::core::locale::Locale;
+use crate::core::map::ui::utils::{add_text, recolor};
+use crate::core::menu::buttons::{DisabledButton, LobbyTextCmp, MenuBtn};
+use crate::core::menu::settings::{AntColor, Background, FogOfWar};
+use crate::core::network::{ClientMessage, ClientSendMessage};
+use crate::core::player::Player;
+use crate::utils::{get_local_ip, NameFromEnum};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy_renet::netcode::NetcodeClientTransport;
+use bevy_renet::renet::{ClientId, RenetServer};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+/// Name of the player shown to the others
+#[derive(Resource)]
+pub struct Nickname(pub String);
+
+impl Default for Nickname {
+    fn default() -> Self {
+        Self("Player".to_string())
+    }
+}
+
+/// A colony in the lobby (human or npc)
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct LobbyPlayer {
+    pub id: ClientId,
+    pub name: String,
+    pub color: AntColor,
+    pub alliance: Option<u8>,
+    pub ready: bool,
+}
+
+impl LobbyPlayer {
+    pub fn is_npc(&self) -> bool {
+        self.id > 0 && self.id < 1000
+    }
+}
+
+/// Colonies and settings of the next game, controlled by the host
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct Lobby {
+    pub players: Vec<LobbyPlayer>,
+    pub background: Background,
+    pub fog_of_war: FogOfWar,
+    pub upkeep: bool,
+    pub observers: Vec<ClientId>, // Clients that watch the game without colony
+    pub max_humans: usize,        // Human players that can join, the rest observes
+}
+
+impl Lobby {
+    /// Create the lobby with the host's settings (a dedicated server has no player)
+    pub fn new(game_settings: &GameSettings, host: bool) -> Self {
+        let mut lobby = Self {
+            players: host
+                .then(|| LobbyPlayer {
+                    id: 0,
+                    name: Nickname::default().0,
+                    color: game_settings.color,
+                    alliance: None,
+                    ready: true,
+                })
+                .into_iter()
+                .collect(),
+            background: game_settings.background,
+            fog_of_war: game_settings.fog_of_war,
+            upkeep: game_settings.upkeep,
+            observers: vec![],
+            max_humans: MAX_COLONIES,
+        };
+
+        (0..game_settings.npcs).for_each(|_| lobby.add_npc());
+
+        lobby
+    }
+
+    pub fn get(&self, id: ClientId) -> Option<&LobbyPlayer> {
+        self.players.iter().find(|p| p.id == id)
+    }
+
+    pub fn npcs(&self) -> u64 {
+        self.players.iter().filter(|p| p.is_npc()).count() as u64
+    }
+
+    /// Number of human players in the lobby
+    pub fn humans(&self) -> usize {
+        self.players.iter().filter(|p| !p.is_npc()).count()
+    }
+
+    /// Whether every human player is ready to start
+    pub fn all_ready(&self) -> bool {
+        self.players.iter().all(|p| p.is_npc() || p.ready)
+    }
+
+    /// Whether a human player other than `id` claimed the color
+    fn is_claimed(&self, color: &AntColor, id: ClientId) -> bool {
+        self.players
+            .iter()
+            .any(|p| !p.is_npc() && p.id != id && p.color == *color)
+    }
+
+    /// First color no other colony has (else the first not claimed by a human player)
+    fn free_color(&self) -> AntColor {
+        AntColor::iter()
+            .find(|c| self.players.iter().all(|p| p.color != *c))
+            .or_else(|| AntColor::iter().find(|c| !self.is_claimed(c, ClientId::MAX)))
+            .unwrap_or_default()
+    }
+
+    /// Next color in the list that the player can claim
+    fn next_color(&self, player: &LobbyPlayer) -> AntColor {
+        let mut color = cycle(&player.color);
+        while !player.is_npc() && color != player.color && self.is_claimed(&color, player.id) {
+            color = cycle(&color);
+        }
+
+        color
+    }
+
+    /// Add a player to the lobby (clients joining a full lobby become observers)
+    pub fn join(&mut self, id: ClientId) {
+        if self.humans() >= self.max_humans || self.players.len() >= MAX_COLONIES {
+            self.observe(id);
+            return;
+        }
+
+        let player = LobbyPlayer {
+            id,
+            name: format!("Player {}", self.humans() + 1),
+            color: self.free_color(),
+            alliance: None,
+            ready: false,
+        };
+
+        self.players.push(player);
+    }
+
+    pub fn observe(&mut self, id: ClientId) {
+        self.observers.push(id);
+    }
+
+    pub fn leave(&mut self, id: ClientId) {
+        self.players.retain(|p| p.id != id);
+        self.observers.retain(|o| *o != id);
+    }
+
+    pub fn add_npc(&mut self) {
+        if self.players.len() < MAX_COLONIES {
+            let npc = LobbyPlayer {
+                id: self.npcs() + 1,
+                name: format!("Npc {}", self.npcs() + 1),
+                color: self.free_color(),
+                alliance: None,
+                ready: true,
+            };
+
+            // Npcs go after the host, before the clients
+            let index = self.players.iter().take_while(|p| p.id < 1000).count();
+            self.players.insert(index, npc);
+        }
+    }
+
+    pub fn remove_npc(&mut self, id: ClientId) {
+        self.leave(id);
+
+        // Keep the ids of the npcs consecutive
+        for (i, p) in self.players.iter_mut().filter(|p| p.is_npc()).enumerate() {
+            p.id = i as u64 + 1;
+            p.name = format!("Npc {}", i + 1);
+        }
+    }
+
+    /// Apply the changes a player made to its own (or, for the host, an npc's) entry
+    pub fn update(&mut self, mut player: LobbyPlayer) {
+        player.name = player.name.chars().take(MAX_NAME_LENGTH).collect();
+        player.alliance = player.alliance.filter(|a| (1..=MAX_ALLIANCES).contains(a));
+
+        // Colors are unique among human players
+        let claimed = !player.is_npc() && self.is_claimed(&player.color, player.id);
+
+        if let Some(p) = self.players.iter_mut().find(|p| p.id == player.id) {
+            if claimed {
+                player.color = p.color;
+            }
+            *p = player;
+        }
+    }
+
+    /// Create the players of the game
+    pub fn to_players(&self) -> Vec<Player> {
+        self.players
+            .iter()
+            .map(|p| Player {
+                name: p.name.clone(),
+                alliance: p.alliance,
+                ..Player::new(p.id, p.color)
+            })
+            .collect()
+    }
+}
+
+#[derive(Component)]
+pub struct LobbyListCmp;
+
+#[derive(Component, Clone, Debug, PartialEq)]
+pub enum LobbyBtn {
+    Color(ClientId),
+    Alliance(ClientId),
+    Ready(ClientId),
+    RemoveNpc(ClientId),
+    AddNpc,
+    Background,
+    FogOfWar,
+    Upkeep,
+}
+
+/// Return the variant that follows `value`
+fn cycle<T: IntoEnumIterator + PartialEq>(value: &T) -> T {
+    let mut iter = T::iter().cycle().skip_while(|v| v != value);
+    iter.nth(1).unwrap()
+}
+
+/// Id of this machine's player in the lobby
+fn local_id(transport: Option<&NetcodeClientTransport>) -> ClientId {
+    transport.map_or(0, |t| t.client_id())
+}
+
+pub fn on_click_lobby_button(
+    trigger: Trigger<Pointer<Click>>,
+    btn_q: Query<(Option<&DisabledButton>, &LobbyBtn)>,
+    server: Option<Res<RenetServer>>,
+    mut lobby: ResMut<Lobby>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
+) {
+    let (disabled, btn) = btn_q.get(trigger.entity()).unwrap();
+
+    if disabled.is_some() {
+        return;
+    }
+
+    let player = match btn {
+        LobbyBtn::Color(id) => lobby.get(*id).map(|p| LobbyPlayer {
+            color: lobby.next_color(p),
+            ..p.clone()
+        }),
+        LobbyBtn::Alliance(id) => lobby.get(*id).map(|p| LobbyPlayer {
+            alliance: match p.alliance {
+                None => Some(1),
+                Some(a) if a < MAX_ALLIANCES => Some(a + 1),
+                Some(_) => None,
+            },
+            ..p.clone()
+        }),
+        LobbyBtn::Ready(id) => lobby.get(*id).map(|p| LobbyPlayer {
+            ready: !p.ready,
+            ..p.clone()
+        }),
+        LobbyBtn::RemoveNpc(id) => {
+            lobby.remove_npc(*id);
+            None
+        }
+        LobbyBtn::AddNpc => {
+            lobby.add_npc();
+            None
+        }
+        LobbyBtn::Background => {
+            lobby.background = cycle(&lobby.background);
+            None
+        }
+        LobbyBtn::FogOfWar => {
+            lobby.fog_of_war = cycle(&lobby.fog_of_war);
+            None
+        }
+        LobbyBtn::Upkeep => {
+            lobby.upkeep = !lobby.upkeep;
+            None
+        }
+    };
+
+    // Clients ask the host to apply the changes to their entry
+    if let Some(player) = player {
+        if server.is_some() {
+            lobby.update(player);
+        } else {
+            client_send_message.send(ClientSendMessage {
+                message: ClientMessage::Lobby(player),
+            });
+        }
+    }
+}
+
+fn spawn_lobby_button(
+    parent: &mut ChildBuilder,
+    text: impl Into<String>,
+    btn: LobbyBtn,
+    enabled: bool,
+    assets: &WorldAssets,
+    window: &Window,
+) {
+    let mut button = parent.spawn((
+        Node {
+            width: Val::Percent(20.),
+            height: Val::Percent(100.),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            margin: UiRect::all(Val::Percent(0.5)),
+            ..default()
+        },
+        BackgroundColor(NORMAL_BUTTON_COLOR),
+        btn,
+    ));
+
+    if enabled {
+        button
+            .observe(recolor::<Pointer<Over>>(HOVERED_BUTTON_COLOR))
+            .observe(recolor::<Pointer<Out>>(NORMAL_BUTTON_COLOR))
+            .observe(recolor::<Pointer<Down>>(PRESSED_BUTTON_COLOR))
+            .observe(recolor::<Pointer<Up>>(HOVERED_BUTTON_COLOR))
+            .observe(on_click_lobby_button);
+    } else {
+        button.insert(DisabledButton);
+    }
+
+    button.with_children(|parent| {
+        parent.spawn(add_text(text, "bold", LABEL_TEXT_SIZE, assets, window));
+    });
+}
+
+pub fn update_lobby(
+    mut commands: Commands,
+    list_q: Query<Entity, With<LobbyListCmp>>,
+    added_q: Query<Entity, Added<LobbyListCmp>>,
+    mut text_q: Query<&mut Text, With<LobbyTextCmp>>,
+    mut btn_q: Query<(Entity, &mut BackgroundColor, &MenuBtn, Has<DisabledButton>)>,
+    server: Option<Res<RenetServer>>,
+    transport: Option<Res<NetcodeClientTransport>>,
+    mut lobby: ResMut<Lobby>,
+    mut nickname: ResMut<Nickname>,
+    mut sent_name: Local<Option<String>>,
+    mut keyboard_ev: EventReader<KeyboardInput>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
+    locale: Res<Locale>,
+    assets: Local<WorldAssets>,
+    window: Single<&Window>,
+) {
+    let id = local_id(transport.as_deref());
+
+    // Type the nickname
+    for ev in keyboard_ev
+        .read()
+        .filter(|ev| ev.state == ButtonState::Pressed)
+    {
+        match &ev.logical_key {
+            Key::Character(c) if nickname.0.len() < MAX_NAME_LENGTH => {
+                nickname
+                    .0
+                    .extend(c.chars().filter(|c| c.is_ascii_graphic() || *c == ' '));
+            }
+            Key::Space if nickname.0.len() < MAX_NAME_LENGTH => nickname.0.push(' '),
+            Key::Backspace => {
+                nickname.0.pop();
+            }
+            _ => (),
+        }
+    }
+
+    // Share the nickname with the other players
+    if let Some(player) = lobby.get(id).filter(|p| p.name != nickname.0).cloned() {
+        let player = LobbyPlayer {
+            name: nickname.0.clone(),
+            ..player
+        };
+
+        if server.is_some() {
+            lobby.update(player);
+        } else if sent_name.as_ref() != Some(&nickname.0) {
+            *sent_name = Some(nickname.0.clone());
+            client_send_message.send(ClientSendMessage {
+                message: ClientMessage::Lobby(player),
+            });
+        }
+    }
+
+    // The host can only start when every player is ready
+    for (button_e, mut bgcolor, btn, disabled) in &mut btn_q {
+        if *btn == MenuBtn::NewGame {
+            if lobby.all_ready() {
+                if disabled {
+                    bgcolor.0 = NORMAL_BUTTON_COLOR;
+                    commands.entity(button_e).remove::<DisabledButton>();
+                }
+            } else {
+                commands.entity(button_e).insert(DisabledButton);
+                bgcolor.0 = DISABLED_BUTTON_COLOR;
+            }
+        }
+    }
+
+    if !lobby.is_changed() && !locale.is_changed() && added_q.is_empty() {
+        return;
+    }
+
+    if let Ok(mut text) = text_q.get_single_mut() {
+        text.0 = if lobby.players.is_empty() {
+            locale.get("lobby.searching")
+        } else if server.is_none() {
+            locale.get("lobby.waiting_host")
+        } else if lobby.humans() == 1 {
+            locale.fmt(
+                "lobby.waiting_players",
+                &[("ip", get_local_ip().to_string())],
+            )
+        } else if !lobby.all_ready() {
+            locale.get("lobby.waiting_ready")
+        } else {
+            locale.get("lobby.all_ready")
+        };
+
+        if !lobby.observers.is_empty() {
+            text.0.push('\n');
+            text.0.push_str(&locale.fmt(
+                "lobby.observers",
+                &[("n", lobby.observers.len().to_string())],
+            ));
+        }
+    }
+
+    let Ok(list_e) = list_q.get_single() else {
+        return;
+    };
+
+    let host = server.is_some();
+    commands
+        .entity(list_e)
+        .despawn_descendants()
+        .with_children(|parent| {
+            for player in &lobby.players {
+                // Players edit their own entry, the host also the npcs
+                let editable = player.id == id || (host && player.is_npc());
+
+                parent
+                    .spawn(Node {
+                        width: Val::Percent(100.),
+                        height: Val::Percent(12.),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        flex_direction: FlexDirection::Row,
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent
+                            .spawn(Node {
+                                width: Val::Percent(30.),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                let name = if player.id == id {
+                                    format!("{}_", player.name)
+                                } else {
+                                    player.name.clone()
+                                };
+
+                                parent.spawn(add_text(
+                                    name,
+                                    "bold",
+                                    SUBTITLE_TEXT_SIZE,
+                                    &assets,
+                                    &window,
+                                ));
+                            });
+
+                        spawn_lobby_button(
+                            parent,
+                            locale.get(&format!("color.{}", player.color.to_snake())),
+                            LobbyBtn::Color(player.id),
+                            editable,
+                            &assets,
+                            &window,
+                        );
+                        spawn_lobby_button(
+                            parent,
+                            player.alliance.map_or(locale.get("lobby.no_team"), |a| {
+                                locale.fmt("lobby.team", &[("team", a.to_string())])
+                            }),
+                            LobbyBtn::Alliance(player.id),
+                            editable,
+                            &assets,
+                            &window,
+                        );
+
+                        if player.is_npc() {
+                            spawn_lobby_button(
+                                parent,
+                                locale.get("lobby.remove"),
+                                LobbyBtn::RemoveNpc(player.id),
+                                host,
+                                &assets,
+                                &window,
+                            );
+                        } else {
+                            spawn_lobby_button(
+                                parent,
+                                locale.get(if player.ready {
+                                    "lobby.ready"
+                                } else {
+                                    "lobby.not_ready"
+                                }),
+                                LobbyBtn::Ready(player.id),
+                                player.id == id && player.id != 0,
+                                &assets,
+                                &window,
+                            );
+                        }
+                    });
+            }
+
+            // Settings of the game (only the host can change them)
+            parent
+                .spawn(Node {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(12.),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    flex_direction: FlexDirection::Row,
+                    margin: UiRect::ZERO.with_top(Val::Percent(2.)),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_lobby_button(
+                        parent,
+                        locale.fmt(
+                            "lobby.background",
+                            &[(
+                                "value",
+                                locale.get(&format!("settings.{}", lobby.background.to_snake())),
+                            )],
+                        ),
+                        LobbyBtn::Background,
+                        host,
+                        &assets,
+                        &window,
+                    );
+                    spawn_lobby_button(
+                        parent,
+                        locale.fmt(
+                            "lobby.fog_of_war",
+                            &[(
+                                "value",
+                                locale.get(&format!("settings.{}", lobby.fog_of_war.to_snake())),
+                            )],
+                        ),
+                        LobbyBtn::FogOfWar,
+                        host,
+                        &assets,
+                        &window,
+                    );
+                    spawn_lobby_button(
+                        parent,
+                        locale.fmt(
+                            "lobby.upkeep",
+                            &[(
+                                "value",
+                                locale.get(if lobby.upkeep {
+                                    "settings.on"
+                                } else {
+                                    "settings.off"
+                                }),
+                            )],
+                        ),
+                        LobbyBtn::Upkeep,
+                        host,
+                        &assets,
+                        &window,
+                    );
+
+                    if host && lobby.players.len() < MAX_COLONIES {
+                        spawn_lobby_button(
+                            parent,
+                            locale.get("lobby.add_npc"),
+                            LobbyBtn::AddNpc,
+                            true,
+                            &assets,
+                            &window,
+                        );
+                    }
+                });
+        });
+}
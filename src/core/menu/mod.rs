@@ -1,3 +1,5 @@
 pub mod buttons;
+pub mod controls;
+pub mod lobby;
 pub mod settings;
 pub mod systems;
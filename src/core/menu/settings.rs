@@ -1,7 +1,8 @@
 use crate::core::assets::WorldAssets;
-use crate::core::audio::ChangeAudioEv;
+use crate::core::audio::{AudioCategory, ChangeAudioEv};
 use crate::core::constants::*;
 use crate::core::game_settings::GameSettings;
+use crate::core::locale::{Language, Localized};
 use crate::core::map::ui::utils::add_text;
 use crate::core::states::AudioState;
 use crate::utils::NameFromEnum;
@@ -9,12 +10,14 @@ use bevy::hierarchy::{ChildBuild, ChildBuilder};
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 
 #[derive(Component, Clone, Debug, PartialEq)]
 pub enum SettingsBtn {
-    Black,
-    Red,
+    Color(AntColor),
+    Standard,
+    Colorblind,
     Soil,
     Rock,
     None,
@@ -24,27 +27,76 @@ pub enum SettingsBtn {
     One,
     Two,
     Three,
+    Off,
+    On,
     Mute,
     NoMusic,
     Sound,
+    EdgeScrolling(bool),
+    Volume(AudioCategory, u8),
+    Language(Language),
 }
 
-#[derive(EnumIter, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[derive(EnumIter, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AntColor {
     #[default]
     Black,
     Red,
+    Blue,
+    Green,
+    Yellow,
+    Purple,
+    Orange,
+    Cyan,
 }
 
 impl AntColor {
-    pub fn inverse(&self) -> Self {
-        match self {
-            AntColor::Black => AntColor::Red,
-            AntColor::Red => AntColor::Black,
+    /// The other colors in order, to give every colony its own color
+    pub fn others(&self) -> impl Iterator<Item = Self> {
+        let color = *self;
+        AntColor::iter().filter(move |c| *c != color).cycle()
+    }
+
+    /// Whether the colony has sprites of its own (else they are swapped from the red ones)
+    pub fn has_sprites(&self) -> bool {
+        matches!(self, AntColor::Black | AntColor::Red)
+    }
+
+    /// Color used to draw the colony's sprites, pings, chat messages, selection rings and health bars
+    pub fn to_color(&self, palette: &Palette) -> Color {
+        match palette {
+            Palette::Standard => match self {
+                AntColor::Black => Color::srgb(0.2, 0.2, 0.2),
+                AntColor::Red => Color::srgb(0.85, 0.15, 0.1),
+                AntColor::Blue => Color::srgb(0.15, 0.35, 0.9),
+                AntColor::Green => Color::srgb(0.2, 0.7, 0.15),
+                AntColor::Yellow => Color::srgb(0.9, 0.8, 0.1),
+                AntColor::Purple => Color::srgb(0.55, 0.2, 0.75),
+                AntColor::Orange => Color::srgb(0.95, 0.5, 0.1),
+                AntColor::Cyan => Color::srgb(0.1, 0.75, 0.8),
+            },
+            // Okabe-Ito palette, distinguishable with every type of color blindness
+            Palette::Colorblind => match self {
+                AntColor::Black => Color::srgb(0.2, 0.2, 0.2),
+                AntColor::Red => Color::srgb_u8(213, 94, 0),
+                AntColor::Blue => Color::srgb_u8(0, 114, 178),
+                AntColor::Green => Color::srgb_u8(0, 158, 115),
+                AntColor::Yellow => Color::srgb_u8(240, 228, 66),
+                AntColor::Purple => Color::srgb_u8(204, 121, 167),
+                AntColor::Orange => Color::srgb_u8(230, 159, 0),
+                AntColor::Cyan => Color::srgb_u8(86, 180, 233),
+            },
         }
     }
 }
 
+#[derive(EnumIter, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Standard,
+    Colorblind,
+}
+
 #[derive(EnumIter, Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
 pub enum Background {
     #[default]
@@ -60,10 +112,14 @@ pub enum FogOfWar {
     Full,
 }
 
+#[derive(Component)]
+pub struct ColorSwatchCmp(pub AntColor);
+
 fn match_setting(setting: &SettingsBtn, game_settings: &GameSettings) -> bool {
     match setting {
-        SettingsBtn::Black => game_settings.color == AntColor::Black,
-        SettingsBtn::Red => game_settings.color == AntColor::Red,
+        SettingsBtn::Color(color) => game_settings.color == *color,
+        SettingsBtn::Standard => game_settings.palette == Palette::Standard,
+        SettingsBtn::Colorblind => game_settings.palette == Palette::Colorblind,
         SettingsBtn::Soil => game_settings.background == Background::Soil,
         SettingsBtn::Rock => game_settings.background == Background::Rock,
         SettingsBtn::None => game_settings.fog_of_war == FogOfWar::None,
@@ -73,9 +129,14 @@ fn match_setting(setting: &SettingsBtn, game_settings: &GameSettings) -> bool {
         SettingsBtn::One => game_settings.npcs == 1,
         SettingsBtn::Two => game_settings.npcs == 2,
         SettingsBtn::Three => game_settings.npcs == 3,
+        SettingsBtn::Off => !game_settings.upkeep,
+        SettingsBtn::On => game_settings.upkeep,
         SettingsBtn::Mute => game_settings.audio == AudioState::Mute,
         SettingsBtn::NoMusic => game_settings.audio == AudioState::NoMusic,
         SettingsBtn::Sound => game_settings.audio == AudioState::Sound,
+        SettingsBtn::EdgeScrolling(enabled) => game_settings.edge_scrolling == *enabled,
+        SettingsBtn::Volume(category, level) => game_settings.mixer.level(category) == *level,
+        SettingsBtn::Language(language) => game_settings.language == *language,
     }
 }
 
@@ -95,12 +156,14 @@ pub fn recolor_label<E: Debug + Clone + Reflect>(
 pub fn on_click_label_button(
     trigger: Trigger<Pointer<Click>>,
     mut btn_q: Query<(&mut BackgroundColor, &SettingsBtn)>,
+    mut swatch_q: Query<(&mut BackgroundColor, &ColorSwatchCmp), Without<SettingsBtn>>,
     mut game_settings: ResMut<GameSettings>,
     mut change_audio_ev: EventWriter<ChangeAudioEv>,
 ) {
     match btn_q.get(trigger.entity()).unwrap().1 {
-        SettingsBtn::Black => game_settings.color = AntColor::Black,
-        SettingsBtn::Red => game_settings.color = AntColor::Red,
+        SettingsBtn::Color(color) => game_settings.color = *color,
+        SettingsBtn::Standard => game_settings.palette = Palette::Standard,
+        SettingsBtn::Colorblind => game_settings.palette = Palette::Colorblind,
         SettingsBtn::Soil => game_settings.background = Background::Soil,
         SettingsBtn::Rock => game_settings.background = Background::Rock,
         SettingsBtn::None => game_settings.fog_of_war = FogOfWar::None,
@@ -110,6 +173,8 @@ pub fn on_click_label_button(
         SettingsBtn::One => game_settings.npcs = 1,
         SettingsBtn::Two => game_settings.npcs = 2,
         SettingsBtn::Three => game_settings.npcs = 3,
+        SettingsBtn::Off => game_settings.upkeep = false,
+        SettingsBtn::On => game_settings.upkeep = true,
         SettingsBtn::Mute => {
             game_settings.audio = AudioState::Mute;
             change_audio_ev.send(ChangeAudioEv(Some(AudioState::Mute)));
@@ -122,6 +187,11 @@ pub fn on_click_label_button(
             game_settings.audio = AudioState::Sound;
             change_audio_ev.send(ChangeAudioEv(Some(AudioState::Sound)));
         }
+        SettingsBtn::EdgeScrolling(enabled) => game_settings.edge_scrolling = *enabled,
+        SettingsBtn::Volume(category, level) => {
+            game_settings.mixer.0.insert(*category, *level);
+        }
+        SettingsBtn::Language(language) => game_settings.language = *language,
     }
 
     // Reset the color of the other buttons
@@ -130,8 +200,13 @@ pub fn on_click_label_button(
             bgcolor.0 = NORMAL_BUTTON_COLOR;
         }
     }
+
+    for (mut bgcolor, ColorSwatchCmp(color)) in &mut swatch_q {
+        bgcolor.0 = color.to_color(&game_settings.palette);
+    }
 }
 
+/// Spawn a title (id in the string tables) with a row of setting buttons below
 pub fn spawn_label(
     parent: &mut ChildBuilder,
     title: &str,
@@ -140,12 +215,9 @@ pub fn spawn_label(
     assets: &WorldAssets,
     window: &Window,
 ) {
-    parent.spawn(add_text(
-        title,
-        "bold",
-        SUBTITLE_TEXT_SIZE,
-        &assets,
-        &window,
+    parent.spawn((
+        add_text("", "bold", SUBTITLE_TEXT_SIZE, &assets, &window),
+        Localized::new(title),
     ));
 
     parent
@@ -168,7 +240,11 @@ pub fn spawn_label(
                 parent
                     .spawn((
                         Node {
-                            width: Val::Percent(30.),
+                            width: Val::Percent(if matches!(item, SettingsBtn::Color(_)) {
+                                9.
+                            } else {
+                                30.
+                            }),
                             height: Val::Percent(100.),
                             align_items: AlignItems::Center,
                             justify_content: JustifyContent::Center,
@@ -189,13 +265,57 @@ pub fn spawn_label(
                     .observe(recolor_label::<Pointer<Up>>(HOVERED_BUTTON_COLOR))
                     .observe(on_click_label_button)
                     .with_children(|parent| {
-                        parent.spawn(add_text(
-                            item.to_title(),
-                            "bold",
-                            LABEL_TEXT_SIZE,
-                            assets,
-                            window,
-                        ));
+                        match item {
+                            SettingsBtn::Color(color) => {
+                                // Colors are shown as a swatch instead of their name
+                                parent.spawn((
+                                    Node {
+                                        width: Val::Percent(60.),
+                                        height: Val::Percent(60.),
+                                        ..default()
+                                    },
+                                    BackgroundColor(color.to_color(&game_settings.palette)),
+                                    BorderRadius::all(Val::Px(3.)),
+                                    PickingBehavior::IGNORE,
+                                    ColorSwatchCmp(*color),
+                                ));
+                            }
+                            SettingsBtn::Volume(_, level) => {
+                                parent.spawn(add_text(
+                                    format!("{level}%"),
+                                    "bold",
+                                    LABEL_TEXT_SIZE,
+                                    assets,
+                                    window,
+                                ));
+                            }
+                            SettingsBtn::EdgeScrolling(enabled) => {
+                                parent.spawn((
+                                    add_text("", "bold", LABEL_TEXT_SIZE, assets, window),
+                                    Localized::new(if *enabled {
+                                        "settings.enabled"
+                                    } else {
+                                        "settings.disabled"
+                                    }),
+                                ));
+                            }
+                            // Languages are always shown in their own language
+                            SettingsBtn::Language(language) => {
+                                parent.spawn(add_text(
+                                    language.native_name(),
+                                    "bold",
+                                    LABEL_TEXT_SIZE,
+                                    assets,
+                                    window,
+                                ));
+                            }
+                            _ => {
+                                parent.spawn((
+                                    add_text("", "bold", LABEL_TEXT_SIZE, assets, window),
+                                    Localized::new(format!("settings.{}", item.to_snake())),
+                                ));
+                            }
+                        }
                     });
             }
         });
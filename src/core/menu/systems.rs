@@ -1,26 +1,41 @@
 use crate::core::ants::components::{Ant, AntCmp};
 use crate::core::assets::WorldAssets;
+use crate::core::audio::AudioCategory;
 use crate::core::constants::{
-    BUTTON_TEXT_SIZE, DISABLED_BUTTON_COLOR, NORMAL_BUTTON_COLOR, SUBTITLE_TEXT_SIZE,
+    BUTTON_TEXT_SIZE, DISABLED_BUTTON_COLOR, MAX_PASSWORD_LENGTH, NORMAL_BUTTON_COLOR,
+    SUBTITLE_TEXT_SIZE, VOLUME_LEVELS,
 };
 use crate::core::game_settings::GameSettings;
+use crate::core::keymap::Keymap;
+use crate::core::locale::{Language, Locale};
 use crate::core::map::events::TileCmp;
 use crate::core::map::ui::utils::{add_root_node, add_text};
 use crate::core::menu::buttons::{
-    spawn_menu_button, DisabledButton, IpTextCmp, LobbyTextCmp, MenuBtn, MenuCmp,
+    spawn_menu_button, DisabledButton, ErrorTextCmp, IpTextCmp, LobbyTextCmp, MenuBtn, MenuCmp,
+    PasswordTextCmp,
 };
-use crate::core::menu::settings::{spawn_label, SettingsBtn};
+use crate::core::menu::controls::spawn_controls;
+use crate::core::menu::lobby::LobbyListCmp;
+use crate::core::menu::settings::{spawn_label, AntColor, SettingsBtn};
 use crate::core::player::Players;
 use crate::core::states::AppState;
-use crate::utils::get_local_ip;
+use crate::core::stats::{spawn_stats, GameStats};
+use crate::utils::{get_local_ip, NameFromEnum};
 use crate::TITLE;
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
 use bevy::prelude::*;
 use bevy_renet::renet::RenetServer;
-use std::net::IpAddr;
+use std::net::{IpAddr, SocketAddr};
+use strum::IntoEnumIterator;
 
 #[derive(Resource)]
 pub struct Ip(pub String);
 
+/// Password of the lobby to host or join (empty for no password)
+#[derive(Resource, Default)]
+pub struct Password(pub String);
+
 impl Default for Ip {
     fn default() -> Self {
         Self(get_local_ip().to_string())
@@ -32,7 +47,10 @@ pub fn setup_menu(
     app_state: Res<State<AppState>>,
     server: Option<Res<RenetServer>>,
     game_settings: Res<GameSettings>,
+    keymap: Res<Keymap>,
     ip: Res<Ip>,
+    password: Res<Password>,
+    locale: Res<Locale>,
     assets: Local<WorldAssets>,
     window: Single<&Window>,
 ) {
@@ -79,7 +97,7 @@ pub fn setup_menu(
                     AppState::MultiPlayerMenu => {
                         parent.spawn((
                             add_text(
-                                format!("Ip: {}", ip.0),
+                                locale.fmt("menu.ip", &[("ip", ip.0.clone())]),
                                 "bold",
                                 BUTTON_TEXT_SIZE,
                                 &assets,
@@ -87,44 +105,50 @@ pub fn setup_menu(
                             ),
                             IpTextCmp,
                         ));
+                        parent.spawn((
+                            add_text(
+                                locale.fmt(
+                                    "menu.password",
+                                    &[("password", "*".repeat(password.0.len()))],
+                                ),
+                                "bold",
+                                BUTTON_TEXT_SIZE,
+                                &assets,
+                                &window,
+                            ),
+                            PasswordTextCmp,
+                        ));
                         spawn_menu_button(parent, MenuBtn::HostGame, &assets, &window);
                         spawn_menu_button(parent, MenuBtn::FindGame, &assets, &window);
+                        spawn_menu_button(parent, MenuBtn::Spectate, &assets, &window);
                         spawn_menu_button(parent, MenuBtn::Back, &assets, &window);
+                        parent.spawn((
+                            add_text("", "medium", SUBTITLE_TEXT_SIZE, &assets, &window),
+                            ErrorTextCmp,
+                        ));
                     }
                     AppState::Lobby | AppState::ConnectedLobby => {
-                        if let Some(server) = server {
-                            let n_players = server.clients_id().len() + 1;
+                        parent.spawn((
+                            add_text("", "bold", BUTTON_TEXT_SIZE, &assets, &window),
+                            LobbyTextCmp,
+                        ));
 
-                            parent.spawn((
-                                add_text(
-                                    if n_players == 1 {
-                                        format!("Waiting for other players to join {}...", get_local_ip())
-                                    } else {
-                                        format!("There are {n_players} players in the lobby.\nWaiting for other players to join {}...", get_local_ip())
-                                    },
-                                    "bold",
-                                    BUTTON_TEXT_SIZE,
-                                    &assets,
-                                    &window,
-                                ),
-                                LobbyTextCmp,
-                            ));
+                        parent.spawn((
+                            Node {
+                                width: Val::Percent(70.),
+                                height: Val::Percent(45.),
+                                flex_direction: FlexDirection::Column,
+                                align_items: AlignItems::Center,
+                                margin: UiRect::vertical(Val::Percent(1.)),
+                                ..default()
+                            },
+                            LobbyListCmp,
+                        ));
 
-                            if n_players > 1 {
-                                spawn_menu_button(parent, MenuBtn::NewGame, &assets, &window);
-                                spawn_menu_button(parent, MenuBtn::LoadGame, &assets, &window);
-                            }
-                        } else {
-                            parent.spawn((
-                                add_text(
-                                    "Searching for a game...",
-                                    "bold",
-                                    BUTTON_TEXT_SIZE,
-                                    &assets,
-                                    &window,
-                                ),
-                                LobbyTextCmp,
-                            ));
+                        // The host can start once other players joined
+                        if server.is_some_and(|s| !s.clients_id().is_empty()) {
+                            spawn_menu_button(parent, MenuBtn::NewGame, &assets, &window);
+                            spawn_menu_button(parent, MenuBtn::LoadGame, &assets, &window);
                         }
 
                         spawn_menu_button(parent, MenuBtn::Back, &assets, &window);
@@ -146,15 +170,23 @@ pub fn setup_menu(
                             .with_children(|parent| {
                                 spawn_label(
                                     parent,
-                                    "Color",
-                                    vec![SettingsBtn::Black, SettingsBtn::Red],
+                                    "settings.color",
+                                    AntColor::iter().map(SettingsBtn::Color).collect(),
                                     &game_settings,
                                     &assets,
                                     &window,
                                 );
                                 spawn_label(
                                     parent,
-                                    "Background",
+                                    "settings.palette",
+                                    vec![SettingsBtn::Standard, SettingsBtn::Colorblind],
+                                    &game_settings,
+                                    &assets,
+                                    &window,
+                                );
+                                spawn_label(
+                                    parent,
+                                    "settings.background",
                                     vec![SettingsBtn::Soil, SettingsBtn::Rock],
                                     &game_settings,
                                     &assets,
@@ -162,7 +194,7 @@ pub fn setup_menu(
                                 );
                                 spawn_label(
                                     parent,
-                                    "Fog of war",
+                                    "settings.fog_of_war",
                                     vec![SettingsBtn::None, SettingsBtn::Half, SettingsBtn::Full],
                                     &game_settings,
                                     &assets,
@@ -170,7 +202,7 @@ pub fn setup_menu(
                                 );
                                 spawn_label(
                                     parent,
-                                    "Number of opponents",
+                                    "settings.opponents",
                                     vec![
                                         SettingsBtn::Zero,
                                         SettingsBtn::One,
@@ -183,7 +215,15 @@ pub fn setup_menu(
                                 );
                                 spawn_label(
                                     parent,
-                                    "Audio",
+                                    "settings.upkeep",
+                                    vec![SettingsBtn::Off, SettingsBtn::On],
+                                    &game_settings,
+                                    &assets,
+                                    &window,
+                                );
+                                spawn_label(
+                                    parent,
+                                    "settings.audio",
                                     vec![
                                         SettingsBtn::Mute,
                                         SettingsBtn::NoMusic,
@@ -193,6 +233,67 @@ pub fn setup_menu(
                                     &assets,
                                     &window,
                                 );
+                                spawn_label(
+                                    parent,
+                                    "settings.language",
+                                    Language::iter().map(SettingsBtn::Language).collect(),
+                                    &game_settings,
+                                    &assets,
+                                    &window,
+                                );
+                            });
+
+                        spawn_menu_button(parent, MenuBtn::Controls, &assets, &window);
+                        spawn_menu_button(parent, MenuBtn::Mixer, &assets, &window);
+                        spawn_menu_button(parent, MenuBtn::Back, &assets, &window);
+                    }
+                    AppState::Controls => {
+                        spawn_controls(parent, &keymap, &locale, &assets, &window);
+
+                        parent
+                            .spawn(Node {
+                                width: Val::Percent(40.),
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                spawn_label(
+                                    parent,
+                                    "settings.edge_scrolling",
+                                    vec![
+                                        SettingsBtn::EdgeScrolling(false),
+                                        SettingsBtn::EdgeScrolling(true),
+                                    ],
+                                    &game_settings,
+                                    &assets,
+                                    &window,
+                                );
+                            });
+
+                        spawn_menu_button(parent, MenuBtn::ResetControls, &assets, &window);
+                        spawn_menu_button(parent, MenuBtn::Back, &assets, &window);
+                    }
+                    AppState::Mixer => {
+                        parent
+                            .spawn(Node {
+                                width: Val::Percent(40.),
+                                flex_direction: FlexDirection::Column,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for category in AudioCategory::iter() {
+                                    spawn_label(
+                                        parent,
+                                        &format!("mixer.{}", category.to_snake()),
+                                        VOLUME_LEVELS
+                                            .iter()
+                                            .map(|v| SettingsBtn::Volume(category, *v))
+                                            .collect(),
+                                        &game_settings,
+                                        &assets,
+                                        &window,
+                                    );
+                                }
                             });
 
                         spawn_menu_button(parent, MenuBtn::Back, &assets, &window);
@@ -216,13 +317,41 @@ pub fn setup_menu(
 pub fn update_ip(
     mut commands: Commands,
     mut btn_q: Query<(Entity, &mut BackgroundColor, &MenuBtn)>,
-    mut text_q: Query<&mut Text, With<IpTextCmp>>,
+    mut text_q: Query<&mut Text, (With<IpTextCmp>, Without<PasswordTextCmp>)>,
+    mut password_q: Query<&mut Text, (With<PasswordTextCmp>, Without<IpTextCmp>)>,
     mut ip: ResMut<Ip>,
+    mut password: ResMut<Password>,
+    mut editing_password: Local<bool>,
     mut not_local_ip: Local<bool>,
     mut invalid_ip: Local<bool>,
+    mut keyboard_ev: EventReader<KeyboardInput>,
     keyboard: Res<ButtonInput<KeyCode>>,
+    locale: Res<Locale>,
 ) {
-    for key in keyboard.get_just_released() {
+    // Tab switches between the ip and the password fields
+    if keyboard.just_pressed(KeyCode::Tab) {
+        *editing_password = !*editing_password;
+    }
+
+    for ev in keyboard_ev
+        .read()
+        .filter(|ev| *editing_password && ev.state == ButtonState::Pressed)
+    {
+        match &ev.logical_key {
+            Key::Character(c) if password.0.len() < MAX_PASSWORD_LENGTH => {
+                password
+                    .0
+                    .extend(c.chars().filter(|c| c.is_ascii_graphic()));
+            }
+            Key::Backspace => {
+                password.0.pop();
+            }
+            Key::Escape => password.0.clear(),
+            _ => (),
+        }
+    }
+
+    for key in keyboard.get_just_released().filter(|_| !*editing_password) {
         match key {
             KeyCode::Digit0 => ip.0.push('0'),
             KeyCode::Digit1 => ip.0.push('1'),
@@ -235,6 +364,11 @@ pub fn update_ip(
             KeyCode::Digit8 => ip.0.push('8'),
             KeyCode::Digit9 => ip.0.push('9'),
             KeyCode::Period => ip.0.push('.'),
+            KeyCode::Semicolon
+                if keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) =>
+            {
+                ip.0.push(':')
+            }
             KeyCode::Backspace => {
                 ip.0.pop();
             }
@@ -262,7 +396,7 @@ pub fn update_ip(
                 }
             }
             MenuBtn::FindGame => {
-                if ip.0.parse::<IpAddr>().is_ok() {
+                if ip.0.parse::<IpAddr>().is_ok() || ip.0.parse::<SocketAddr>().is_ok() {
                     // Only enable once when the ip becomes valid
                     if *invalid_ip {
                         bgcolor.0 = NORMAL_BUTTON_COLOR;
@@ -279,8 +413,28 @@ pub fn update_ip(
         }
     }
 
+    // Show the cursor at the end of the field being edited
+    let cursor = |active: bool| if active { "_" } else { "" };
+
     if let Ok(mut text) = text_q.get_single_mut() {
-        text.0 = format!("Ip: {}", ip.0);
+        text.0 = locale.fmt(
+            "menu.ip",
+            &[("ip", format!("{}{}", ip.0, cursor(!*editing_password)))],
+        );
+    }
+
+    if let Ok(mut text) = password_q.get_single_mut() {
+        text.0 = locale.fmt(
+            "menu.password",
+            &[(
+                "password",
+                format!(
+                    "{}{}",
+                    "*".repeat(password.0.len()),
+                    cursor(*editing_password)
+                ),
+            )],
+        );
     }
 }
 
@@ -310,6 +464,8 @@ pub fn setup_end_game(
     mut ant_q: Query<(&mut Visibility, &AntCmp)>,
     mut tile_q: Query<&mut Sprite, With<TileCmp>>,
     players: Res<Players>,
+    stats: Res<GameStats>,
+    locale: Res<Locale>,
     assets: Local<WorldAssets>,
     window: Single<&Window>,
 ) {
@@ -327,7 +483,14 @@ pub fn setup_end_game(
     commands
         .spawn((add_root_node(), MenuCmp))
         .with_children(|parent| {
-            parent.spawn(ImageNode::new(assets.image(image)));
+            parent.spawn((
+                Node {
+                    height: Val::Percent(25.),
+                    ..default()
+                },
+                ImageNode::new(assets.image(image)),
+            ));
+            spawn_stats(parent, &stats, &players, &locale, &assets, &window);
             spawn_menu_button(parent, MenuBtn::Quit, &assets, &window);
         });
 
@@ -1,54 +1,94 @@
+mod alerts;
 mod ants;
 mod assets;
 mod audio;
 mod camera;
+mod chat;
+mod config;
 mod constants;
+mod dedicated;
+mod desync;
 mod game_settings;
+mod keymap;
+mod locale;
 mod map;
 mod menu;
 mod multiplayer;
 mod network;
+mod palette;
 mod pause;
 mod persistence;
 mod player;
 mod resources;
+mod snapshot;
+mod spectator;
 mod states;
+mod stats;
 mod systems;
 mod traits;
 mod utils;
 
+use crate::core::alerts::{alert_event, detect_enemies, spawn_alerts, update_alerts, AlertEv};
 use crate::core::ants::events::*;
+use crate::core::ants::formation::{cycle_formation_keyboard, Formation};
+use crate::core::ants::projectile::{
+    resolve_projectiles, spawn_projectile_event, SpawnProjectileEv,
+};
 use crate::core::ants::selection::*;
 use crate::core::ants::systems::*;
 use crate::core::audio::*;
 use crate::core::camera::*;
-use crate::core::constants::{ENEMY_TIMER, NETWORK_TIMER};
-use crate::core::game_settings::GameSettings;
+use crate::core::chat::{
+    chat_event, despawn_pings, place_ping, spawn_chat, type_chat, update_chat, ChatEv,
+};
+use crate::core::constants::{DESYNC_TIMER, ENEMY_TIMER, NETWORK_TIMER};
+use crate::core::dedicated::*;
+use crate::core::desync::{check_desync, server_send_checksum, ChecksumEv};
+use crate::core::game_settings::{save_settings, GameSettings, UserSettings};
+use crate::core::keymap::Keymap;
+use crate::core::locale::{localize_texts, update_locale, LangLoader, LangTable, Locale};
 use crate::core::map::events::{spawn_tile_event, SpawnTileEv};
 use crate::core::map::holes::{resolve_expeditions, spawn_enemies};
 use crate::core::map::systems::*;
+use crate::core::map::ui::selection::{spawn_selection_panel, update_selection_panel};
 use crate::core::map::ui::systems::{animate_ui, draw_ui, setup_after_trait, update_ui, UiCmp};
 use crate::core::map::vision::update_vision;
-use crate::core::menu::buttons::MenuCmp;
-use crate::core::menu::systems::{setup_end_game, setup_in_game_menu, setup_menu, update_ip, Ip};
+use crate::core::menu::buttons::{connect_to_host, MenuCmp};
+use crate::core::menu::controls::{rebind_key, update_controls, Rebinding};
+use crate::core::menu::lobby::{update_lobby, Lobby, Nickname};
+use crate::core::menu::systems::{
+    setup_end_game, setup_in_game_menu, setup_menu, update_ip, Ip, Password,
+};
 use crate::core::multiplayer::*;
 use crate::core::network::*;
+use crate::core::palette::swap_palettes;
 use crate::core::pause::*;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::core::persistence::{load_game, save_game};
 use crate::core::persistence::{LoadGameEv, SaveGameEv};
+use crate::core::snapshot::{spawn_network_overlay, update_network_overlay};
+use crate::core::spectator::{
+    cycle_spectator_view, spawn_spectator_overlay, update_spectator_overlay, Spectator,
+};
 use crate::core::states::{AppState, AudioState, GameState};
+use crate::core::stats::{end_stats, update_stats};
 use crate::core::systems::*;
-use crate::core::traits::{after_trait_check, select_trait_event, TraitSelectedEv};
+use crate::core::traits::{
+    after_trait_check, dedicated_trait_selection, select_trait_event, TraitSelectedEv,
+};
 use crate::core::utils::{despawn, update_transform_no_rotation};
 use ants::selection::{select_ants_from_rect, select_ants_to_res, SelectAntEv};
+use bevy::input::InputSystem;
 use bevy::prelude::*;
 use bevy::time::common_conditions::on_timer;
+use bevy::ui::UiSystem;
 use bevy_renet::renet::{RenetClient, RenetServer};
 use map::ui::systems::setup_trait_selection;
 use std::time::Duration;
 use strum::IntoEnumIterator;
 
+pub use crate::core::dedicated::{DedicatedServer, USAGE};
+
 pub struct GamePlugin;
 
 #[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
@@ -62,6 +102,13 @@ struct InRunningOrPausedGameSet;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
+        // Restore the settings of the last session before the menu is drawn
+        if let Some(settings) = UserSettings::load() {
+            app.insert_resource(settings.game_settings())
+                .insert_resource(Ip(settings.ip))
+                .insert_resource(Nickname(settings.nickname));
+        }
+
         app
             // States
             .init_state::<AppState>()
@@ -77,16 +124,32 @@ impl Plugin for GamePlugin {
             .add_event::<SpawnEggEv>()
             .add_event::<SpawnAntEv>()
             .add_event::<PinEv>()
+            .add_event::<AntCommandEv>()
             .add_event::<DespawnAntEv>()
             .add_event::<DamageAntEv>()
+            .add_event::<SpawnProjectileEv>()
             .add_event::<SelectAntEv>()
             .add_event::<TraitSelectedEv>()
             .add_event::<ServerSendMessage>()
             .add_event::<ClientSendMessage>()
             .add_event::<UpdatePopulationEv>()
+            .add_event::<ChecksumEv>()
+            .add_event::<ChatEv>()
+            .add_event::<AlertEv>()
             // Resources
             .init_resource::<Ip>()
+            .init_resource::<Password>()
+            .init_resource::<Nickname>()
+            .init_resource::<Lobby>()
             .init_resource::<GameSettings>()
+            .init_resource::<Formation>()
+            .init_resource::<Rebinding>()
+            .init_resource::<MusicIntensity>()
+            .init_resource::<AudioPool>()
+            .init_resource::<Locale>()
+            .init_asset::<LangTable>()
+            .init_asset_loader::<LangLoader>()
+            .insert_resource(Keymap::load())
             // Sets
             .configure_sets(PreUpdate, InGameSet.run_if(in_state(AppState::Game)))
             .configure_sets(Update, InGameSet.run_if(in_state(AppState::Game)))
@@ -131,18 +194,46 @@ impl Plugin for GamePlugin {
             .add_systems(Startup, (setup_camera, initialize_game, draw_map).chain())
             .add_systems(
                 Update,
-                (move_camera, move_camera_keyboard)
+                (camera_shortcuts, move_camera, move_camera_keyboard)
+                    .chain()
                     .run_if(not(
                         in_state(GameState::TraitSelection).or(in_state(GameState::InGameMenu))
                     ))
                     .in_set(InGameSet),
             )
             // Audio
-            .add_systems(Startup, setup_music_btn)
-            .add_systems(OnEnter(AudioState::Sound), play_music)
+            .add_systems(
+                Startup,
+                (setup_music_btn, restore_audio)
+                    .chain()
+                    .run_if(not(resource_exists::<DedicatedServer>)),
+            )
+            .add_systems(
+                OnEnter(AudioState::Sound),
+                play_music.run_if(not(resource_exists::<DedicatedServer>)),
+            )
+            .add_systems(
+                Update,
+                (
+                    change_audio_event,
+                    toggle_music_keyboard,
+                    play_audio_event,
+                    play_ant_sounds.in_set(InRunningGameSet),
+                    (update_music_intensity, update_music).chain(),
+                )
+                    .run_if(not(resource_exists::<DedicatedServer>)),
+            )
+            // Palette
             .add_systems(
                 Update,
-                (change_audio_event, toggle_music_keyboard, play_audio_event),
+                swap_palettes.run_if(not(resource_exists::<DedicatedServer>)),
+            )
+            // Localization
+            .add_systems(
+                PostUpdate,
+                (update_locale, localize_texts)
+                    .chain()
+                    .before(UiSystem::Prepare),
             )
             //Networking
             .add_systems(
@@ -154,24 +245,121 @@ impl Plugin for GamePlugin {
                     .in_set(InGameSet),
             )
             .add_systems(PreUpdate, update_population_event.in_set(InGameSet))
+            .add_systems(Update, update_network_overlay.in_set(InGameSet))
+            .add_systems(
+                Update,
+                (interpolate_ants, check_desync)
+                    .run_if(resource_exists::<RenetClient>)
+                    .in_set(InGameSet),
+            )
+            .add_systems(
+                Update,
+                (
+                    server_issue_tokens.run_if(resource_exists::<TokenServer>),
+                    connect_to_host.run_if(resource_exists::<PendingConnection>),
+                ),
+            )
             .add_systems(
                 Update,
                 server_update
                     .run_if(resource_exists::<RenetServer>)
                     .run_if(not(in_state(AppState::Game))),
             )
+            .add_systems(
+                Update,
+                (
+                    server_game_update.run_if(resource_exists::<RenetServer>),
+                    client_update.run_if(resource_exists::<RenetClient>),
+                )
+                    .in_set(InGameSet),
+            )
+            .add_systems(
+                Update,
+                client_lobby_update
+                    .run_if(resource_exists::<RenetClient>)
+                    .run_if(not(in_state(AppState::Game))),
+            )
+            // Chat
+            .add_systems(
+                PreUpdate,
+                type_chat
+                    .after(InputSystem)
+                    .run_if(resource_exists::<RenetServer>.or(resource_exists::<RenetClient>))
+                    .run_if(not(resource_exists::<Spectator>))
+                    .in_set(InGameSet),
+            )
+            .add_systems(
+                Update,
+                (
+                    place_ping
+                        .run_if(not(resource_exists::<Spectator>))
+                        .in_set(InRunningOrPausedGameSet),
+                    (update_chat, despawn_pings).in_set(InGameSet),
+                )
+                    .run_if(resource_exists::<RenetServer>.or(resource_exists::<RenetClient>)),
+            )
+            .add_systems(PostUpdate, chat_event.in_set(InGameSet))
+            // Alerts
+            .add_systems(
+                Update,
+                (
+                    detect_enemies.in_set(InRunningGameSet),
+                    update_alerts.in_set(InGameSet),
+                ),
+            )
+            .add_systems(PostUpdate, alert_event.in_set(InGameSet))
+            // Statistics
+            .add_systems(
+                Update,
+                update_stats
+                    .run_if(not(resource_exists::<RenetClient>))
+                    .in_set(InRunningGameSet),
+            )
+            // Spectator
+            .add_systems(
+                Update,
+                (cycle_spectator_view, update_spectator_overlay)
+                    .run_if(resource_exists::<Spectator>)
+                    .in_set(InGameSet),
+            )
+            // Dedicated server
+            .add_systems(
+                Startup,
+                start_dedicated_server
+                    .after(initialize_game)
+                    .run_if(resource_exists::<DedicatedServer>),
+            )
+            .add_systems(
+                Update,
+                (
+                    dedicated_lobby_update
+                        .after(server_update)
+                        .run_if(not(in_state(AppState::Game))),
+                    dedicated_game_update.in_set(InRunningGameSet),
+                    dedicated_end_game
+                        .run_if(in_state(GameState::EndGame))
+                        .in_set(InGameSet),
+                )
+                    .run_if(resource_exists::<DedicatedServer>)
+                    .run_if(resource_exists::<RenetServer>),
+            )
+            .add_systems(
+                OnEnter(GameState::TraitSelection),
+                dedicated_trait_selection.run_if(resource_exists::<DedicatedServer>),
+            )
             .add_systems(
                 Last,
                 (
                     (
-                        server_send_status.run_if(on_timer(Duration::from_millis(NETWORK_TIMER))),
+                        (server_send_status, server_send_tiles)
+                            .run_if(on_timer(Duration::from_millis(NETWORK_TIMER))),
+                        server_send_checksum.run_if(on_timer(Duration::from_millis(DESYNC_TIMER))),
                         server_send_message,
                     )
+                        .chain()
                         .run_if(resource_exists::<RenetServer>),
-                    (
-                        client_send_status.run_if(on_timer(Duration::from_millis(NETWORK_TIMER))),
-                        client_send_message,
-                    )
+                    (client_send_commands, client_send_message)
+                        .chain()
                         .run_if(resource_exists::<RenetClient>),
                 )
                     .in_set(InGameSet),
@@ -184,11 +372,26 @@ impl Plugin for GamePlugin {
         }
         app.add_systems(
             Update,
-            update_ip.run_if(in_state(AppState::MultiPlayerMenu)),
+            (
+                update_ip.run_if(in_state(AppState::MultiPlayerMenu)),
+                update_lobby
+                    .run_if(in_state(AppState::Lobby).or(in_state(AppState::ConnectedLobby))),
+                update_controls.run_if(in_state(AppState::Controls)),
+            ),
+        )
+        .add_systems(
+            PreUpdate,
+            rebind_key
+                .after(InputSystem)
+                .run_if(in_state(AppState::Controls)),
         );
 
         // Utilities
         app.add_systems(Update, check_keys.in_set(InGameSet))
+            .add_systems(
+                Last,
+                save_settings.run_if(not(resource_exists::<DedicatedServer>)),
+            )
             .add_systems(
                 PostUpdate,
                 (
@@ -199,9 +402,21 @@ impl Plugin for GamePlugin {
             // Map
             .add_systems(
                 OnEnter(AppState::Game),
-                (despawn::<MapCmp>, draw_map, draw_ui),
+                (
+                    despawn::<MapCmp>,
+                    draw_map,
+                    draw_ui,
+                    spawn_network_overlay,
+                    spawn_chat,
+                    spawn_alerts,
+                    spawn_selection_panel,
+                    spawn_spectator_overlay,
+                ),
+            )
+            .add_systems(
+                Update,
+                (animate_ui, update_ui, update_selection_panel).in_set(InGameSet),
             )
-            .add_systems(Update, (animate_ui, update_ui).in_set(InGameSet))
             .add_systems(
                 OnExit(AppState::Game),
                 (despawn::<MapCmp>, reset_camera, initialize_game, draw_map).chain(),
@@ -212,6 +427,16 @@ impl Plugin for GamePlugin {
                 select_ants_from_rect.in_set(InRunningOrPausedGameSet),
             )
             .add_systems(Update, remove_command_from_selection)
+            .add_systems(
+                Update,
+                resolve_ant_commands
+                    .run_if(not(resource_exists::<RenetClient>))
+                    .in_set(InRunningOrPausedGameSet),
+            )
+            .add_systems(
+                Update,
+                cycle_formation_keyboard.in_set(InRunningOrPausedGameSet),
+            )
             .add_systems(
                 PostUpdate,
                 select_ants_to_res.in_set(InRunningOrPausedGameSet),
@@ -245,7 +470,14 @@ impl Plugin for GamePlugin {
                     .run_if(in_state(GameState::AfterTraitSelection)),
             )
             .add_systems(OnExit(GameState::AfterTraitSelection), despawn::<MenuCmp>)
-            .add_systems(OnEnter(GameState::EndGame), setup_end_game)
+            .add_systems(
+                OnEnter(GameState::EndGame),
+                (
+                    end_stats.run_if(not(resource_exists::<RenetClient>)),
+                    setup_end_game,
+                )
+                    .chain(),
+            )
             .add_systems(OnExit(GameState::EndGame), despawn::<MenuCmp>)
             .add_systems(Update, toggle_pause_keyboard.in_set(InGameSet))
             // Ants
@@ -257,7 +489,13 @@ impl Plugin for GamePlugin {
             )
             .add_systems(
                 Update,
-                (animate_pin, update_ant_components, update_selection_icons)
+                (
+                    animate_pin,
+                    update_ant_components,
+                    update_team_colors,
+                    update_rank_insignia,
+                    update_selection_icons,
+                )
                     .in_set(InRunningOrPausedGameSet),
             )
             .add_systems(
@@ -273,11 +511,14 @@ impl Plugin for GamePlugin {
                         resolve_harvesting_corpse,
                         resolve_healing,
                         resolve_attack_action,
+                        resolve_projectiles,
                         resolve_die_action,
                         resolve_brood_action,
                         resolve_idle_action,
                         resolve_targeted_walk_action,
                         resolve_walk_action,
+                        resolve_aging,
+                        resolve_upkeep,
                         npc_buy_ants.run_if(on_timer(Duration::from_millis(ENEMY_TIMER))),
                         spawn_enemies.run_if(on_timer(Duration::from_millis(ENEMY_TIMER))),
                         resolve_expeditions.run_if(on_timer(Duration::from_millis(ENEMY_TIMER))),
@@ -296,6 +537,7 @@ impl Plugin for GamePlugin {
                         spawn_egg_event,
                         spawn_ant_event,
                         despawn_ant_event,
+                        spawn_projectile_event,
                         damage_event,
                     )
                         .in_set(InGameSet),
@@ -1,22 +1,90 @@
 use crate::core::ants::components::{Action, AntCmp, Egg, Owned};
 use crate::core::ants::events::{DespawnAntEv, SpawnAntEv, SpawnEggEv};
+use crate::core::ants::selection::{AntCommandEv, CommandTarget};
+use crate::core::audio::PlayAudioEv;
+use crate::core::constants::{INTERPOLATION_DELAY, MAX_EXTRAPOLATION};
+use crate::core::dedicated::DedicatedServer;
 use crate::core::game_settings::GameSettings;
-use crate::core::network::{ClientMessage, ClientSendMessage, ServerMessage, ServerSendMessage};
+use crate::core::map::map::Map;
+use crate::core::map::systems::create_map;
+use crate::core::menu::lobby::Lobby;
+use crate::core::network::{
+    is_observer, ClientMessage, ClientSendMessage, ReconnectTokens, ServerMessage,
+    ServerSendMessage,
+};
 use crate::core::persistence::Population;
-use crate::core::player::Players;
+use crate::core::player::{Player, Players};
+use crate::core::snapshot::{NetworkStats, Snapshot, SnapshotDelta, SnapshotHistory};
 use crate::core::states::GameState;
+use crate::core::stats::GameStats;
 use bevy::prelude::*;
-use bevy_renet::renet::{ClientId, RenetServer};
+use bevy_renet::netcode::NetcodeServerTransport;
+use bevy_renet::renet::{ClientId, RenetServer, ServerEvent};
 use bimap::BiMap;
-use std::collections::HashSet;
+use rand::{rng, Rng};
+use std::collections::VecDeque;
 
 #[derive(Resource, Default)]
 pub struct EntityMap(pub BiMap<Entity, Entity>);
 
+/// Positions and rotations of a remote ant with the (real) time they were received
+#[derive(Component, Default)]
+pub struct SnapshotBuffer {
+    pub states: VecDeque<(f64, Vec2, Quat)>,
+    /// Next location on the path of a walking ant, computed when the last state arrived
+    pub next_pos: Option<Vec2>,
+}
+
 #[derive(Event)]
 pub struct UpdatePopulationEv {
     pub population: Population,
-    pub id: ClientId,
+}
+
+/// Create the map and send the start signal to every client
+pub fn start_multiplayer_game(
+    commands: &mut Commands,
+    server: &RenetServer,
+    game_settings: &GameSettings,
+    players: &[Player],
+    server_send_message: &mut EventWriter<ServerSendMessage>,
+    spectator: bool,
+) -> Map {
+    // A spectating host (dedicated server) gets no base on the map
+    let map = create_map(
+        &players
+            .iter()
+            .filter(|p| !spectator || p.id != 0)
+            .cloned()
+            .collect::<Vec<_>>(),
+    );
+
+    // Send the start game signal to all clients with their player id
+    let mut tokens = ReconnectTokens::default();
+    for client in server.clients_id().iter() {
+        let token = rng().random::<u64>();
+        tokens.0.insert(*client, token);
+
+        server_send_message.send(ServerSendMessage {
+            message: ServerMessage::StartGame {
+                id: *client,
+                background: game_settings.background,
+                fog_of_war: game_settings.fog_of_war,
+                upkeep: game_settings.upkeep,
+                token,
+                colonies: players
+                    .iter()
+                    .filter(|p| p.id != *client && (!spectator || p.id != 0))
+                    .map(Player::roster)
+                    .collect(),
+                map: map.clone(),
+            },
+            client: Some(*client),
+        });
+    }
+
+    commands.insert_resource(tokens);
+
+    map
 }
 
 pub fn update_game_state(
@@ -34,142 +102,378 @@ pub fn update_game_state(
     });
 }
 
+pub fn server_game_update(
+    mut server: ResMut<RenetServer>,
+    transport: Res<NetcodeServerTransport>,
+    mut server_ev: EventReader<ServerEvent>,
+    mut players: ResMut<Players>,
+    mut lobby: ResMut<Lobby>,
+    tokens: Option<Res<ReconnectTokens>>,
+    mut history: ResMut<SnapshotHistory>,
+    game_settings: Res<GameSettings>,
+    map: Res<Map>,
+    stats: Res<GameStats>,
+    mut server_send_message: EventWriter<ServerSendMessage>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    game_state: Res<State<GameState>>,
+) {
+    for ev in server_ev.read() {
+        match ev {
+            ServerEvent::ClientDisconnected { client_id, reason }
+                if lobby.observers.contains(client_id) =>
+            {
+                println!("Observer {client_id} left: {reason}");
+                history.acked.remove(client_id);
+                lobby.leave(*client_id);
+            }
+            ServerEvent::ClientDisconnected { client_id, reason } => {
+                println!("Client {client_id} disconnected: {reason}");
+                play_audio_ev.send(PlayAudioEv::new("error"));
+                history.acked.remove(client_id);
+
+                // The host plays the colony until the player rejoins
+                if let Some(player) = players.0.iter_mut().find(|p| p.id == *client_id) {
+                    player.disconnected = true;
+                }
+            }
+            ServerEvent::ClientConnected { client_id } if is_observer(&transport, *client_id) => {
+                println!("Observer {client_id} joined");
+                lobby.observe(*client_id);
+
+                // Observers can join at any moment and receive the colonies with every status
+                server_send_message.send(ServerSendMessage {
+                    message: ServerMessage::Observe {
+                        id: *client_id,
+                        background: game_settings.background,
+                        fog_of_war: game_settings.fog_of_war,
+                        upkeep: game_settings.upkeep,
+                        map: map.clone(),
+                    },
+                    client: Some(*client_id),
+                });
+
+                server_send_message.send(ServerSendMessage {
+                    message: ServerMessage::State(*game_state.get()),
+                    client: Some(*client_id),
+                });
+
+                // The statistics are updated incrementally from here on
+                server_send_message.send(ServerSendMessage {
+                    message: ServerMessage::Stats(stats.clone()),
+                    client: Some(*client_id),
+                });
+            }
+            ServerEvent::ClientConnected { client_id } => {
+                let token = transport
+                    .user_data(*client_id)
+                    .map(|data| u64::from_le_bytes(data[..8].try_into().unwrap()));
+
+                let valid = tokens
+                    .as_ref()
+                    .and_then(|t| t.0.get(client_id))
+                    .is_some_and(|t| Some(*t) == token);
+
+                let colonies = players
+                    .0
+                    .iter()
+                    .filter(|p| p.id != *client_id && p.id != ClientId::MAX)
+                    .map(Player::roster)
+                    .collect();
+
+                let Some(player) = players
+                    .0
+                    .iter_mut()
+                    .find(|p| p.id == *client_id && p.disconnected)
+                    .filter(|_| valid)
+                else {
+                    // Only players that left the game can join it
+                    println!("Client {client_id} rejected: invalid reconnect token");
+                    server.disconnect(*client_id);
+                    continue;
+                };
+
+                println!("Client {client_id} reconnected");
+                player.disconnected = false;
+
+                // The client lost its baselines, so it starts with a full snapshot
+                history.acked.remove(client_id);
+
+                // The colony reaches the player with the next status
+                server_send_message.send(ServerSendMessage {
+                    message: ServerMessage::LoadGame {
+                        background: game_settings.background,
+                        fog_of_war: game_settings.fog_of_war,
+                        upkeep: game_settings.upkeep,
+                        player: player.clone(),
+                        colonies,
+                        map: map.clone(),
+                    },
+                    client: Some(*client_id),
+                });
+
+                server_send_message.send(ServerSendMessage {
+                    message: ServerMessage::State(*game_state.get()),
+                    client: Some(*client_id),
+                });
+
+                server_send_message.send(ServerSendMessage {
+                    message: ServerMessage::Stats(stats.clone()),
+                    client: Some(*client_id),
+                });
+            }
+        }
+    }
+}
+
 pub fn server_send_status(
     mut server_send_message: EventWriter<ServerSendMessage>,
     server: Res<RenetServer>,
     ant_q: Query<(Entity, &Transform, &AntCmp)>,
     egg_q: Query<(Entity, &Transform, &Egg)>,
     game_settings: Res<GameSettings>,
-    entity_map: Res<EntityMap>,
+    players: Res<Players>,
+    lobby: Res<Lobby>,
+    dedicated: Option<Res<DedicatedServer>>,
+    mut history: ResMut<SnapshotHistory>,
+    mut stats: ResMut<NetworkStats>,
 ) {
+    let snapshot = Snapshot::new(ant_q.iter(), egg_q.iter());
+
+    // Observers see every colony (except the dedicated server's own player)
+    let colonies = players
+        .0
+        .iter()
+        .filter(|p| p.id != ClientId::MAX && (dedicated.is_none() || p.id != 0))
+        .map(|p| Player {
+            visible_tiles: default(),
+            ..p.clone()
+        })
+        .collect::<Vec<_>>();
+    let seq = history.seq + 1;
+
+    let mut size = 0;
+    let mut changed = 0;
+    let mut delta = false;
     for id in server.clients_id().iter() {
-        server_send_message.send(ServerSendMessage {
-            message: ServerMessage::Status {
-                speed: game_settings.speed,
-                population: Population {
-                    ants: ant_q
-                        .iter()
-                        .filter(|(_, _, a)| a.team != *id)
-                        .map(|(e, t, a)| {
-                            let mut a = a.clone();
-
-                            // Map attacking entity to the entity on the server
-                            if let Action::Attack(e) = &mut a.action {
-                                *e = *entity_map.0.get_by_right(e).unwrap_or(e);
-                            }
-
-                            (e, (t.clone(), a))
-                        })
-                        .collect(),
-                    eggs: egg_q
-                        .iter()
-                        .filter_map(|(e, t, a)| {
-                            (a.team != *id).then_some((e, (t.clone(), a.clone())))
-                        })
-                        .collect(),
-                },
+        // Send only the changes since the last snapshot the client received
+        let baseline = history
+            .acked
+            .get(id)
+            .and_then(|s| history.get(*s).map(|b| (*s, b)));
+
+        let message = ServerMessage::Status {
+            speed: game_settings.speed,
+            player: Player {
+                visible_tiles: default(), // Clients calculate their own vision
+                ..players.get(*id).clone()
             },
+            snapshot: SnapshotDelta::new(seq, baseline, &snapshot),
+        };
+
+        if let ServerMessage::Status { snapshot, .. } = &message {
+            size += bincode::serialized_size(&message).unwrap_or_default();
+            changed += snapshot.len();
+            delta |= snapshot.baseline.is_some();
+        }
+
+        server_send_message.send(ServerSendMessage {
+            message,
             client: Some(*id),
         });
+
+        if lobby.observers.contains(id) {
+            server_send_message.send(ServerSendMessage {
+                message: ServerMessage::Colonies(colonies.clone()),
+                client: Some(*id),
+            });
+        }
     }
+
+    *stats = NetworkStats {
+        size,
+        entities: snapshot.ants.len() + snapshot.eggs.len(),
+        changed,
+        delta,
+    };
+
+    history.push(seq, snapshot);
 }
 
-pub fn client_send_status(
-    mut client_send_message: EventWriter<ClientSendMessage>,
-    ant_q: Query<(Entity, &Transform, &AntCmp), With<Owned>>,
-    egg_q: Query<(Entity, &Transform, &Egg), With<Owned>>,
-    players: Res<Players>,
+pub fn server_send_tiles(
+    mut server_send_message: EventWriter<ServerSendMessage>,
+    mut map: ResMut<Map>,
+) {
+    for (x, y) in std::mem::take(&mut map.changed) {
+        server_send_message.send(ServerSendMessage {
+            message: ServerMessage::TileUpdate(map.get_tile(x, y).unwrap().clone()),
+            client: None,
+        });
+    }
+}
+
+pub fn client_send_commands(
+    mut ant_command_ev: EventReader<AntCommandEv>,
     entity_map: Res<EntityMap>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
 ) {
-    client_send_message.send(ClientSendMessage {
-        message: ClientMessage::Status {
-            player: players.main().clone(),
-            population: Population {
-                ants: ant_q
-                    .iter()
-                    .map(|(e, t, a)| {
-                        let mut a = a.clone();
+    for AntCommandEv {
+        ants,
+        target,
+        formation,
+        ..
+    } in ant_command_ev.read()
+    {
+        // Map the entities to the ones on the host
+        let to_host = |e: &Entity| entity_map.0.get_by_right(e).copied();
 
-                        // Map attacking entity to the entity on the server
-                        if let Action::Attack(e) = &mut a.action {
-                            *e = *entity_map.0.get_by_right(e).unwrap_or(e);
-                        }
+        let target = match *target {
+            CommandTarget::Egg(e) => to_host(&e).map(CommandTarget::Egg),
+            CommandTarget::Ant(e) => to_host(&e).map(CommandTarget::Ant),
+            t => Some(t),
+        };
 
-                        (e, (t.clone(), a))
-                    })
-                    .collect(),
-                eggs: egg_q
-                    .iter()
-                    .map(|(e, t, egg)| (e, (t.clone(), egg.clone())))
-                    .collect(),
-            },
-        },
-    });
+        let Some(target) = target else {
+            continue;
+        };
+
+        let ants = ants.iter().filter_map(to_host).collect::<Vec<_>>();
+
+        if !ants.is_empty() {
+            client_send_message.send(ClientSendMessage {
+                message: ClientMessage::Command {
+                    ants,
+                    target,
+                    formation: *formation,
+                },
+            });
+        }
+    }
 }
 
 pub fn update_population_event(
     mut update_population_ev: EventReader<UpdatePopulationEv>,
-    mut ant_q: Query<(Entity, &mut Transform, &mut AntCmp), Without<Owned>>,
+    mut ant_q: Query<(Entity, &mut SnapshotBuffer, &mut AntCmp), Without<Owned>>,
     mut egg_q: Query<(Entity, &mut Transform, &mut Egg), (Without<Owned>, Without<AntCmp>)>,
     entity_map: Res<EntityMap>,
+    mut map: ResMut<Map>,
+    time: Res<Time<Real>>,
     mut spawn_ant_ev: EventWriter<SpawnAntEv>,
     mut spawn_egg_ev: EventWriter<SpawnEggEv>,
     mut despawn_ant_ev: EventWriter<DespawnAntEv>,
 ) {
-    let mut seen = HashSet::new();
-    for UpdatePopulationEv { population, id } in update_population_ev.read() {
-        if !seen.insert(id) {
-            continue;
+    // Only the most recent status is relevant
+    let Some(UpdatePopulationEv { population }) = update_population_ev.read().last() else {
+        return;
+    };
+
+    // Despawn all that are not in the new population
+    for (ant_e, _, _) in &ant_q {
+        if entity_map
+            .0
+            .get_by_right(&ant_e)
+            .is_none_or(|e| !population.ants.contains_key(e))
+        {
+            despawn_ant_ev.send(DespawnAntEv { entity: ant_e });
         }
+    }
 
-        // Despawn all that are not in the new population
-        for (ant_e, _, _) in &ant_q {
-            if !population
-                .ants
-                .contains_key(entity_map.0.get_by_right(&ant_e).unwrap())
-            {
-                despawn_ant_ev.send(DespawnAntEv { entity: ant_e });
-            }
+    for (egg_e, _, _) in &egg_q {
+        if entity_map
+            .0
+            .get_by_right(&egg_e)
+            .is_none_or(|e| !population.eggs.contains_key(e))
+        {
+            despawn_ant_ev.send(DespawnAntEv { entity: egg_e });
         }
+    }
 
-        for (egg_e, _, _) in &egg_q {
-            if !population
-                .eggs
-                .contains_key(entity_map.0.get_by_right(&egg_e).unwrap())
-            {
-                despawn_ant_ev.send(DespawnAntEv { entity: egg_e });
+    // Update the current population
+    for (entity, (t, a)) in population.ants.iter() {
+        let mut a = a.clone();
+
+        // Map the entities from the host to the local ones
+        a.map_entities(|e| *entity_map.0.get_by_left(&e).unwrap_or(&e));
+
+        if let Some(ant_e) = entity_map.0.get_by_left(entity) {
+            if let Ok((_, mut buffer, mut ant)) = ant_q.get_mut(*ant_e) {
+                // The movement is smoothed by `interpolate_ants`
+                buffer.states.push_back((
+                    time.elapsed_secs_f64(),
+                    t.translation.truncate(),
+                    t.rotation,
+                ));
+
+                // Resolve the path once per state instead of every frame
+                buffer.next_pos = match &a.action {
+                    Action::Walk(target_loc) => {
+                        let current_loc = map.get_loc(&t.translation);
+                        map.shortest_path_option(&current_loc, target_loc)
+                            .and_then(|path| path.get(1).map(Map::get_coord_from_loc))
+                    }
+                    _ => None,
+                };
+
+                *ant = a;
             }
+        } else {
+            spawn_ant_ev.send(SpawnAntEv {
+                ant: a,
+                transform: *t,
+                entity: Some(*entity),
+            });
         }
+    }
 
-        // Update the current population
-        for (entity, (t, a)) in population.ants.iter() {
-            if let Some(ant_e) = entity_map.0.get_by_left(entity) {
-                if let Ok((_, mut ant_t, mut ant)) = ant_q.get_mut(*ant_e) {
-                    *ant_t = *t;
-                    *ant = a.clone();
-                }
-            } else {
-                spawn_ant_ev.send(SpawnAntEv {
-                    ant: a.clone(),
-                    transform: *t,
-                    entity: Some(*entity),
-                });
+    for (entity, (t, e)) in population.eggs.iter() {
+        if let Some(egg_e) = entity_map.0.get_by_left(entity) {
+            if let Ok((_, mut egg_t, mut egg)) = egg_q.get_mut(*egg_e) {
+                egg_t.translation = t.translation.with_z(egg_t.translation.z);
+                *egg = e.clone();
             }
+        } else {
+            spawn_egg_ev.send(SpawnEggEv {
+                ant: e.ant.clone(),
+                transform: *t,
+                entity: Some(*entity),
+            });
         }
+    }
+}
 
-        for (entity, (t, e)) in population.eggs.iter() {
-            if let Some(egg_e) = entity_map.0.get_by_left(entity) {
-                if let Ok((_, mut egg_t, mut egg)) = egg_q.get_mut(*egg_e) {
-                    *egg_t = *t;
-                    *egg = e.clone();
-                }
-            } else {
-                spawn_egg_ev.send(SpawnEggEv {
-                    ant: e.ant.clone(),
-                    transform: *t,
-                    entity: Some(*entity),
-                });
-            }
+/// Show remote ants slightly in the past, moving them smoothly between the states
+/// received from the host. When packets are missing, ants keep walking along their path
+pub fn interpolate_ants(
+    mut ant_q: Query<(&mut Transform, &mut SnapshotBuffer), Without<Owned>>,
+    time: Res<Time<Real>>,
+) {
+    let render_time = time.elapsed_secs_f64() - INTERPOLATION_DELAY as f64 / 1e3;
+
+    for (mut ant_t, mut buffer) in &mut ant_q {
+        // Drop the states that are too old to interpolate from
+        while buffer.states.len() > 2 && buffer.states[1].0 <= render_time {
+            buffer.states.pop_front();
         }
+
+        let (pos, rotation) = match (buffer.states.front(), buffer.states.get(1)) {
+            (Some(&(t1, pos1, r1)), Some(&(t2, pos2, r2))) if render_time < t2 => {
+                let s = ((render_time - t1) / (t2 - t1)).clamp(0., 1.) as f32;
+                (pos1.lerp(pos2, s), r1.slerp(r2, s))
+            }
+            (Some(&(t1, pos1, _)), Some(&(t2, pos2, r2))) => match buffer.next_pos {
+                Some(next_pos) => {
+                    // Continue at the last known speed towards the next location on the path
+                    let dt = (render_time - t2).min(MAX_EXTRAPOLATION as f64 / 1e3);
+                    let distance = pos1.distance(pos2) / (t2 - t1) as f32 * dt as f32;
+
+                    (pos2 + (next_pos - pos2).clamp_length_max(distance), r2)
+                }
+                None => (pos2, r2),
+            },
+            (Some(&(_, pos, rotation)), None) => (pos, rotation),
+            _ => continue,
+        };
+
+        ant_t.translation = pos.extend(ant_t.translation.z);
+        ant_t.rotation = rotation;
     }
 }
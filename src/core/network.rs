@@ -1,24 +1,80 @@
-use crate::core::ants::events::{SpawnAntEv, SpawnEggEv};
+use crate::core::alerts::{Alert, AlertEv};
+use crate::core::ants::components::Ant;
+use crate::core::ants::events::QueueAntEv;
+use crate::core::ants::formation::Formation;
+use crate::core::ants::selection::{AntCommandEv, CommandTarget};
 use crate::core::audio::PlayAudioEv;
-use crate::core::game_settings::{GameMode, GameSettings};
+use crate::core::chat::{ChatEntry, ChatEv};
+use crate::core::desync::{resync_tiles, Checksum, ChecksumEv};
+use crate::core::game_settings::GameSettings;
+use crate::core::locale::Locale;
 use crate::core::map::map::Map;
 use crate::core::map::tile::Tile;
 use crate::core::menu::buttons::LobbyTextCmp;
+use crate::core::menu::lobby::{Lobby, LobbyPlayer};
 use crate::core::menu::settings::{Background, FogOfWar};
 use crate::core::multiplayer::UpdatePopulationEv;
-use crate::core::persistence::{GameLoaded, Population};
+use crate::core::persistence::GameLoaded;
 use crate::core::player::{Player, Players};
+use crate::core::snapshot::{NetworkStats, SnapshotDelta, SnapshotHistory};
+use crate::core::spectator::Spectator;
 use crate::core::states::{AppState, GameState};
-use crate::core::traits::AfterTraitCount;
+use crate::core::stats::GameStats;
+use crate::core::traits::{AfterTraitCount, Trait, TraitSelectedEv};
 use crate::utils::get_local_ip;
 use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task};
+use bevy::utils::hashbrown::{HashMap, HashSet};
 use bevy_renet::netcode::*;
 use bevy_renet::renet::*;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::{rng, Rng};
 use serde::{Deserialize, Serialize};
-use std::net::UdpSocket;
-use std::time::SystemTime;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::ops::Range;
+use std::time::{Duration, Instant, SystemTime};
 
 const PROTOCOL_ID: u64 = 7;
+const TOKEN_EXPIRE_SECONDS: u64 = 300;
+const TIMEOUT_SECONDS: i32 = 15;
+const CLIENT_ID_RANGE: Range<ClientId> = 1001..ClientId::MAX; // Ids reserved for human clients
+const TOKEN_REQUEST_SECONDS: u64 = 5;
+const MAX_TOKEN_REQUEST_BYTES: usize = 256;
+const PASSWORD_CONTEXT: &str = "marabunta 2025-05 lobby password";
+pub const DEFAULT_PORT: u16 = 5000;
+
+/// Address, capacity and password of the server
+#[derive(Clone)]
+pub struct HostSettings {
+    pub address: IpAddr,
+    pub port: u16,
+    pub max_clients: usize,
+    pub password: String,
+}
+
+impl Default for HostSettings {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: DEFAULT_PORT,
+            max_clients: 4,
+            password: String::new(),
+        }
+    }
+}
+
+/// Tokens (per client) that allow players to rejoin a game in progress
+#[derive(Resource, Default)]
+pub struct ReconnectTokens(pub HashMap<ClientId, u64>);
+
+/// Credentials of the client to rejoin the last game it played
+#[derive(Resource, Clone, Copy, Serialize, Deserialize)]
+pub struct ReconnectToken {
+    pub id: ClientId,
+    pub token: u64,
+}
 
 #[derive(Event)]
 pub struct ServerSendMessage {
@@ -36,144 +92,497 @@ pub enum ServerMessage {
     LoadGame {
         background: Background,
         fog_of_war: FogOfWar,
+        upkeep: bool,
         player: Player,
+        colonies: Vec<Player>, // Roster of the other colonies
+        map: Map,
+    },
+    Lobby(Lobby),
+    Observe {
+        id: ClientId,
+        background: Background,
+        fog_of_war: FogOfWar,
+        upkeep: bool,
         map: Map,
-        population: Population,
     },
-    NPlayers(usize),
     StartGame {
         id: ClientId,
         background: Background,
         fog_of_war: FogOfWar,
+        upkeep: bool,
+        token: u64,
+        colonies: Vec<Player>, // Roster of the other colonies
         map: Map,
     },
     State(GameState),
     Status {
         speed: f32,
-        population: Population,
+        player: Player,
+        snapshot: SnapshotDelta,
     },
+    Colonies(Vec<Player>), // Sent to observers, that have no colony of their own
     TileUpdate(Tile),
+    Checksum(Checksum),
+    Resync {
+        tiles: Vec<Tile>,
+        replace: bool, // Whether the client replaces its diverging tiles
+    },
+    Chat(ChatEntry),
+    Stats(GameStats), // Complete statistics, sent at the end and to clients that join
+    StatsUpdate(GameStats), // Counters with only the last sample of the timelines
+    Alert(Alert),
 }
 
 impl ServerMessage {
     pub fn channel(&self) -> DefaultChannel {
         match self {
             ServerMessage::LoadGame { .. }
-            | ServerMessage::NPlayers(_)
+            | ServerMessage::Lobby(_)
+            | ServerMessage::Observe { .. }
             | ServerMessage::StartGame { .. }
-            | ServerMessage::State(_) => DefaultChannel::ReliableOrdered,
-            ServerMessage::Status { .. } => DefaultChannel::Unreliable,
+            | ServerMessage::State(_)
+            | ServerMessage::Checksum(_)
+            | ServerMessage::Resync { .. }
+            | ServerMessage::Chat(_)
+            | ServerMessage::Stats(_)
+            | ServerMessage::StatsUpdate(_)
+            | ServerMessage::Alert(_) => DefaultChannel::ReliableOrdered,
+            ServerMessage::Status { .. } | ServerMessage::Colonies(_) => DefaultChannel::Unreliable,
             ServerMessage::TileUpdate(_) => DefaultChannel::ReliableUnordered,
         }
     }
 }
 
+/// Clients only send their intents, the host simulates the game
 #[derive(Serialize, Deserialize)]
 pub enum ClientMessage {
     State(GameState),
-    Status {
-        player: Player,
-        population: Population,
-    },
-    TileUpdate(Tile),
-    TileExplored {
-        tile: (u32, u32),
-        client: ClientId,
+    QueueAnt(Ant),
+    DequeueAnt(usize),
+    Command {
+        ants: Vec<Entity>,
+        target: CommandTarget,
+        formation: Formation,
     },
+    ChooseTrait(Trait),
+    Lobby(LobbyPlayer),
+    Chat(ChatEntry),
+    Ack(u32),         // Sequence number of the last snapshot received
+    Resync(Vec<u32>), // Rows of tiles that diverge from the host
 }
 
 impl ClientMessage {
     pub fn channel(&self) -> DefaultChannel {
         match self {
-            ClientMessage::State(_) => DefaultChannel::ReliableOrdered,
-            ClientMessage::Status { .. } => DefaultChannel::Unreliable,
-            ClientMessage::TileUpdate(_) | ClientMessage::TileExplored { .. } => {
-                DefaultChannel::ReliableUnordered
-            }
+            ClientMessage::Ack(_) => DefaultChannel::Unreliable,
+            _ => DefaultChannel::ReliableOrdered,
+        }
+    }
+}
+
+/// Whether the client connected to watch the game instead of playing
+pub fn is_observer(transport: &NetcodeServerTransport, id: ClientId) -> bool {
+    transport.user_data(id).is_some_and(|data| data[8] == 1)
+}
+
+/// Request of a client to the host for a connect token
+#[derive(Serialize, Deserialize)]
+struct TokenRequest {
+    proof: [u8; 32], // Answer to the challenge of the host
+    reconnect: Option<ReconnectToken>,
+    observer: bool,
+}
+
+/// Keys of a single token exchange, derived from the lobby password and the host's challenge
+struct ExchangeKeys {
+    proof: blake3::Hash,
+    cipher: ChaCha20Poly1305,
+}
+
+impl ExchangeKeys {
+    fn new(password_key: &[u8; 32], challenge: &[u8; 32]) -> Self {
+        // The answer to the challenge proves the client knows the password without sending
+        // it, and can't be replayed since every request gets a new challenge
+        let proof = blake3::keyed_hash(password_key, challenge);
+
+        // The token carries the session keys of the client, so only send it encrypted
+        let secret = blake3::Hasher::new_keyed(password_key)
+            .update(b"connect token")
+            .update(challenge)
+            .finalize();
+
+        Self {
+            proof,
+            cipher: ChaCha20Poly1305::new(Key::from_slice(secret.as_bytes())),
+        }
+    }
+
+    // Every key encrypts a single message, so the nonce can be constant
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        self.cipher
+            .encrypt(&Nonce::default(), data)
+            .map_err(|_| "Failed to encrypt the connect token.".to_string())
+    }
+
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
+        self.cipher
+            .decrypt(&Nonce::default(), data)
+            .map_err(|_| "Failed to decrypt the connect token.".to_string())
+    }
+}
+
+/// Key derived from the lobby password (the password itself is never sent)
+fn password_key(password: &str) -> [u8; 32] {
+    blake3::derive_key(PASSWORD_CONTEXT, password.as_bytes())
+}
+
+/// Token request that is being received by the host
+struct PendingRequest {
+    stream: TcpStream,
+    challenge: [u8; 32],
+    buffer: Vec<u8>,
+    started: Instant,
+}
+
+/// Issues the connect tokens of the server
+///
+/// The tokens are signed with a random key that never leaves the host, and every client
+/// gets a unique id chosen by the host. The lobby password only authorizes the request.
+#[derive(Resource)]
+pub struct TokenServer {
+    listener: TcpListener,
+    private_key: [u8; NETCODE_KEY_BYTES],
+    password_key: [u8; 32],
+    issued: HashSet<ClientId>,
+    pending: Vec<PendingRequest>,
+}
+
+impl TokenServer {
+    /// Answer a token request, returning the encrypted connect token
+    fn issue(
+        &mut self,
+        request: TokenRequest,
+        challenge: &[u8; 32],
+        server_addr: SocketAddr,
+        tokens: Option<&ReconnectTokens>,
+    ) -> Result<Vec<u8>, String> {
+        let keys = ExchangeKeys::new(&self.password_key, challenge);
+
+        // Hashes are compared in constant time
+        if blake3::Hash::from(request.proof) != keys.proof {
+            return Err("Invalid password.".to_string());
         }
+
+        // Rejoining players keep their id when they have the token of the game
+        let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+        let reconnect = request.reconnect.filter(|r| {
+            !request.observer && tokens.is_some_and(|t| t.0.get(&r.id) == Some(&r.token))
+        });
+
+        let client_id = if let Some(reconnect) = reconnect {
+            user_data[..8].copy_from_slice(&reconnect.token.to_le_bytes());
+            reconnect.id
+        } else {
+            let mut id = rng().random_range(CLIENT_ID_RANGE);
+            while self.issued.contains(&id) || tokens.is_some_and(|t| t.0.contains_key(&id)) {
+                id = rng().random_range(CLIENT_ID_RANGE);
+            }
+            id
+        };
+        self.issued.insert(client_id);
+
+        // Observers watch the game without colony
+        user_data[8] = request.observer as u8;
+
+        let current_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        let connect_token = ConnectToken::generate(
+            current_time,
+            PROTOCOL_ID,
+            TOKEN_EXPIRE_SECONDS,
+            client_id,
+            TIMEOUT_SECONDS,
+            vec![server_addr],
+            Some(&user_data),
+            &self.private_key,
+        )
+        .map_err(|err| format!("Failed to generate the connect token: {err}."))?;
+
+        let mut bytes = vec![];
+        connect_token
+            .write(&mut bytes)
+            .map_err(|err| format!("Failed to write the connect token: {err}."))?;
+
+        keys.encrypt(&bytes)
     }
 }
 
-pub fn new_renet_client(ip: &String) -> (RenetClient, NetcodeClientTransport) {
-    let server_addr = format!("{ip}:5000").parse().unwrap();
-    let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// Ask the host for a connect token (blocks until the host answers)
+fn request_connect_token(
+    server_addr: SocketAddr,
+    password: &str,
+    reconnect: Option<ReconnectToken>,
+    observer: bool,
+) -> Result<ConnectToken, String> {
+    let timeout = Duration::from_secs(TOKEN_REQUEST_SECONDS);
+    let mut stream = TcpStream::connect_timeout(&server_addr, timeout)
+        .map_err(|err| format!("Failed to connect to {server_addr}: {err}."))?;
+    stream.set_read_timeout(Some(timeout)).unwrap();
+
+    let challenge: [u8; 32] = bincode::deserialize_from(&mut stream)
+        .map_err(|err| format!("No response from {server_addr}: {err}."))?;
+    let keys = ExchangeKeys::new(&password_key(password), &challenge);
+
+    let request = TokenRequest {
+        proof: *keys.proof.as_bytes(),
+        reconnect,
+        observer,
+    };
+
+    bincode::serialize_into(&mut stream, &request)
+        .map_err(|err| format!("Failed to send the token request: {err}."))?;
+
+    let response: Result<Vec<u8>, String> = bincode::deserialize_from(&mut stream)
+        .map_err(|err| format!("No response from {server_addr}: {err}."))?;
+
+    ConnectToken::read(&mut keys.decrypt(&response?)?.as_slice())
+        .map_err(|err| format!("Received an invalid connect token: {err}."))
+}
+
+/// Connect token that is being requested from the host in the background
+#[derive(Resource)]
+pub struct PendingConnection(pub Task<Result<(SocketAddr, ConnectToken), String>>);
+
+/// Start requesting a connect token from the host without blocking the frame
+pub fn request_connection(
+    ip: &String,
+    password: &str,
+    reconnect: Option<&ReconnectToken>,
+    observer: bool,
+) -> Result<PendingConnection, String> {
+    // The ip can contain the port of the server, else use the default one
+    let server_addr = ip
+        .parse::<SocketAddr>()
+        .or_else(|_| {
+            ip.parse::<IpAddr>()
+                .map(|ip| SocketAddr::new(ip, DEFAULT_PORT))
+        })
+        .map_err(|_| format!("Invalid server address {ip}."))?;
+
+    // Rejoining players send the token of the last game to get their id back
+    let password = password.to_string();
+    let reconnect = reconnect.copied();
+    let task = IoTaskPool::get().spawn(async move {
+        request_connect_token(server_addr, &password, reconnect, observer)
+            .map(|token| (server_addr, token))
+    });
+
+    Ok(PendingConnection(task))
+}
+
+pub fn new_renet_client(
+    server_addr: SocketAddr,
+    connect_token: ConnectToken,
+) -> Result<(RenetClient, NetcodeClientTransport), String> {
+    let socket = UdpSocket::bind("0.0.0.0:0")
+        .map_err(|err| format!("Failed to open a network socket: {err}."))?;
+
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
-    let client_id = current_time.as_millis() as u64;
-    let authentication = ClientAuthentication::Unsecure {
-        client_id,
-        protocol_id: PROTOCOL_ID,
-        server_addr,
-        user_data: None,
-    };
 
-    let transport = NetcodeClientTransport::new(current_time, authentication, socket).unwrap();
+    let transport = NetcodeClientTransport::new(
+        current_time,
+        ClientAuthentication::Secure { connect_token },
+        socket,
+    )
+    .map_err(|err| format!("Failed to connect to {server_addr}: {err}."))?;
+
     let client = RenetClient::new(ConnectionConfig::default());
 
-    (client, transport)
+    Ok((client, transport))
 }
 
-pub fn new_renet_server() -> (RenetServer, NetcodeServerTransport) {
-    let public_addr = "0.0.0.0:5000".parse().unwrap();
-    let socket = UdpSocket::bind(public_addr).expect("Socket already in use.");
+pub fn new_renet_server(
+    settings: &HostSettings,
+) -> Result<(RenetServer, NetcodeServerTransport, TokenServer), String> {
+    let bind_addr = SocketAddr::new(settings.address, settings.port);
+    let socket = UdpSocket::bind(bind_addr)
+        .map_err(|err| format!("Failed to bind the server to {bind_addr}: {err}."))?;
+
+    // The connect tokens are requested over tcp on the same port
+    let listener = TcpListener::bind(bind_addr)
+        .map_err(|err| format!("Failed to bind the server to {bind_addr}: {err}."))?;
+    listener.set_nonblocking(true).unwrap();
+
+    let mut private_key = [0u8; NETCODE_KEY_BYTES];
+    rng().fill(&mut private_key);
+
     let current_time = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap();
+
+    // Connect tokens are only accepted for the addresses the clients can use to reach us
+    let mut public_addresses = vec![bind_addr];
+    for ip in [get_local_ip(), IpAddr::V4(Ipv4Addr::LOCALHOST)] {
+        let addr = SocketAddr::new(ip, settings.port);
+        if !public_addresses.contains(&addr) {
+            public_addresses.push(addr);
+        }
+    }
+
     let server_config = ServerConfig {
         current_time,
-        max_clients: 4,
+        max_clients: settings.max_clients,
         protocol_id: PROTOCOL_ID,
-        public_addresses: vec![public_addr],
-        authentication: ServerAuthentication::Unsecure,
+        public_addresses,
+        authentication: ServerAuthentication::Secure { private_key },
     };
 
-    let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+    let transport = NetcodeServerTransport::new(server_config, socket)
+        .map_err(|err| format!("Failed to start the server: {err}."))?;
+
     let server = RenetServer::new(ConnectionConfig::default());
 
-    (server, transport)
+    let token_server = TokenServer {
+        listener,
+        private_key,
+        password_key: password_key(&settings.password),
+        issued: HashSet::new(),
+        pending: vec![],
+    };
+
+    Ok((server, transport, token_server))
+}
+
+pub fn server_issue_tokens(
+    mut token_server: ResMut<TokenServer>,
+    tokens: Option<Res<ReconnectTokens>>,
+) {
+    while let Ok((mut stream, _)) = token_server.listener.accept() {
+        // Challenge every new connection with a random nonce
+        let mut challenge = [0u8; 32];
+        rng().fill(&mut challenge);
+
+        if stream.set_nonblocking(true).is_ok() && stream.write_all(&challenge).is_ok() {
+            token_server.pending.push(PendingRequest {
+                stream,
+                challenge,
+                buffer: vec![],
+                started: Instant::now(),
+            });
+        }
+    }
+
+    // Read the requests without blocking the frame, dropping slow or oversized ones
+    for mut pending in std::mem::take(&mut token_server.pending) {
+        let mut chunk = [0u8; MAX_TOKEN_REQUEST_BYTES];
+        let closed = match pending.stream.read(&mut chunk) {
+            Ok(0) => true,
+            Ok(n) => {
+                pending.buffer.extend_from_slice(&chunk[..n]);
+                false
+            }
+            Err(err) => err.kind() != std::io::ErrorKind::WouldBlock,
+        };
+
+        if let Ok(request) = bincode::deserialize::<TokenRequest>(&pending.buffer) {
+            let response = pending
+                .stream
+                .local_addr()
+                .map_err(|err| err.to_string())
+                .and_then(|addr| {
+                    token_server.issue(request, &pending.challenge, addr, tokens.as_deref())
+                });
+
+            let _ = pending.stream.set_nonblocking(false);
+            let _ = pending
+                .stream
+                .write_all(&bincode::serialize(&response).unwrap());
+        } else if !closed
+            && pending.buffer.len() <= MAX_TOKEN_REQUEST_BYTES
+            && pending.started.elapsed().as_secs() < TOKEN_REQUEST_SECONDS
+        {
+            token_server.pending.push(pending);
+        }
+    }
 }
 
 pub fn server_update(
-    mut n_players_q: Query<&mut Text, With<LobbyTextCmp>>,
     mut server: ResMut<RenetServer>,
+    transport: Res<NetcodeServerTransport>,
     mut server_ev: EventReader<ServerEvent>,
-    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    mut lobby: ResMut<Lobby>,
     app_state: Res<State<AppState>>,
     mut next_app_state: ResMut<NextState<AppState>>,
-    mut next_game_state: ResMut<NextState<GameState>>,
 ) {
     for ev in server_ev.read() {
-        if *app_state != AppState::Game {
-            let n_players = server.clients_id().len() + 1;
-
-            // Update the number of players in the lobby
-            let message = bincode::serialize(&ServerMessage::NPlayers(n_players)).unwrap();
-            server.broadcast_message(DefaultChannel::ReliableOrdered, message);
-
-            if let Ok(mut text) = n_players_q.get_single_mut() {
-                if n_players == 1 {
-                    text.0 = format!("Waiting for other players to join {}...", get_local_ip());
-                    next_app_state.set(AppState::Lobby);
+        match ev {
+            ServerEvent::ClientConnected { client_id } => {
+                if is_observer(&transport, *client_id) {
+                    lobby.observe(*client_id);
                 } else {
-                    text.0 = format!("There are {n_players} players in the lobby.\nWaiting for other players to join {}...", get_local_ip());
-                    next_app_state.set(AppState::ConnectedLobby);
+                    lobby.join(*client_id);
                 }
             }
+            ServerEvent::ClientDisconnected { client_id, .. } => lobby.leave(*client_id),
+        }
+    }
+
+    if lobby.is_changed() {
+        // Share the lobby with every client
+        let message = bincode::serialize(&ServerMessage::Lobby(lobby.clone())).unwrap();
+        server.broadcast_message(DefaultChannel::ReliableOrdered, message);
+
+        let state = if server.clients_id().is_empty() {
+            AppState::Lobby
         } else {
-            match ev {
-                ServerEvent::ClientConnected { client_id } => {
-                    println!("Client {client_id} connected");
-                }
-                ServerEvent::ClientDisconnected { client_id, reason } => {
-                    println!("Client {client_id} disconnected: {reason}");
-                    play_audio_ev.send(PlayAudioEv {
-                        name: "error",
-                        volume: 0.5,
-                    });
-                    next_game_state.set(GameState::InGameMenu);
-                }
-            }
+            AppState::ConnectedLobby
+        };
+
+        if *app_state.get() != state {
+            next_app_state.set(state);
+        }
+    }
+}
+
+pub fn client_update(
+    mut commands: Commands,
+    client: Res<RenetClient>,
+    mut play_audio_ev: EventWriter<PlayAudioEv>,
+    app_state: Res<State<AppState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+) {
+    if client.is_disconnected() && *app_state.get() == AppState::Game {
+        // Lost the connection with the server -> go back to the menu to rejoin
+        play_audio_ev.send(PlayAudioEv::new("error"));
+
+        commands.remove_resource::<RenetClient>();
+        commands.remove_resource::<NetcodeClientTransport>();
+
+        next_game_state.set(GameState::default());
+        next_app_state.set(AppState::MultiPlayerMenu);
+    }
+}
+
+pub fn client_lobby_update(
+    mut n_players_q: Query<&mut Text, With<LobbyTextCmp>>,
+    client: Res<RenetClient>,
+    transport: Res<NetcodeClientTransport>,
+    locale: Res<Locale>,
+) {
+    if client.is_disconnected() {
+        if let Ok(mut text) = n_players_q.get_single_mut() {
+            let reason = transport
+                .disconnect_reason()
+                .map(|r| r.to_string())
+                .or_else(|| client.disconnect_reason().map(|r| r.to_string()))
+                .unwrap_or_else(|| locale.get("lobby.unknown_reason"));
+
+            text.0 = locale.fmt("lobby.connection_failed", &[("reason", reason)]);
         }
     }
 }
@@ -195,15 +604,33 @@ pub fn server_send_message(
 pub fn server_receive_message(
     mut server: ResMut<RenetServer>,
     mut players: ResMut<Players>,
-    mut map: ResMut<Map>,
+    map: Res<Map>,
+    game_settings: Res<GameSettings>,
     mut trait_count: ResMut<AfterTraitCount>,
-    mut update_population_ev: EventWriter<UpdatePopulationEv>,
+    mut history: ResMut<SnapshotHistory>,
+    mut lobby: ResMut<Lobby>,
+    mut queue_ant_ev: EventWriter<QueueAntEv>,
+    mut trait_selected_ev: EventWriter<TraitSelectedEv>,
+    mut chat_ev: EventWriter<ChatEv>,
+    mut ant_command_ev: EventWriter<AntCommandEv>,
+    mut stats: ResMut<GameStats>,
     game_state: Res<State<GameState>>,
     mut next_game_state: ResMut<NextState<GameState>>,
 ) {
-    for id in server.clients_id() {
+    'client: for id in server.clients_id() {
         while let Some(message) = server.receive_message(id, DefaultChannel::ReliableOrdered) {
-            match bincode::deserialize(&message).unwrap() {
+            // Clients can't be trusted, drop the ones that send invalid messages
+            let Ok(message) = bincode::deserialize(&message) else {
+                server.disconnect(id);
+                continue 'client;
+            };
+
+            // Observers can't interfere with the game, nor chat since they have no name
+            if lobby.observers.contains(&id) && !matches!(message, ClientMessage::Resync(_)) {
+                continue;
+            }
+
+            match message {
                 ClientMessage::State(state) => match state {
                     GameState::InGameMenu | GameState::Paused
                         if *game_state.get() == GameState::Running =>
@@ -218,34 +645,88 @@ pub fn server_receive_message(
                     }
                     _ => (),
                 },
-                _ => unreachable!(),
-            }
-        }
+                ClientMessage::QueueAnt(ant) => {
+                    queue_ant_ev.send(QueueAntEv { id, ant });
+                }
+                ClientMessage::DequeueAnt(index) => {
+                    if let Some((ant, price)) = players.get_mut(id).dequeue(index) {
+                        stats.colony(id).refund(&ant, &price);
+                    }
+                }
+                ClientMessage::Command {
+                    ants,
+                    target,
+                    formation,
+                } => {
+                    // The host derives (and validates) the actions of the ants itself
+                    ant_command_ev.send(AntCommandEv {
+                        team: id,
+                        ants,
+                        target,
+                        formation,
+                    });
+                }
+                ClientMessage::ChooseTrait(selected) => {
+                    if !players.get(id).has_trait(&selected) {
+                        trait_selected_ev.send(TraitSelectedEv { id, selected });
+                    }
+                }
+                ClientMessage::Lobby(player) => {
+                    // Players can only change their own entry
+                    if player.id == id {
+                        lobby.update(player);
+                    }
+                }
+                ClientMessage::Chat(entry) => {
+                    // Players can only speak for themselves, with their own name and color
+                    if let Some(entry) = entry.sanitize(&players).filter(|e| e.sender() == id) {
+                        for client in entry.recipients(&players, &lobby.observers) {
+                            server.send_message(
+                                client,
+                                DefaultChannel::ReliableOrdered,
+                                bincode::serialize(&ServerMessage::Chat(entry.clone())).unwrap(),
+                            );
+                        }
 
-        while let Some(message) = server.receive_message(id, DefaultChannel::ReliableUnordered) {
-            match bincode::deserialize(&message).unwrap() {
-                ClientMessage::TileUpdate(tile) => {
-                    map.replace_tile(&tile);
+                        if entry.is_visible(&players, players.main_id()) {
+                            chat_ev.send(ChatEv(entry));
+                        }
+                    }
                 }
-                ClientMessage::TileExplored { tile, client } => {
-                    map.get_tile_mut(tile.0, tile.1)
-                        .unwrap()
-                        .explored
-                        .insert(client);
+                ClientMessage::Resync(rows) => {
+                    let tiles = map
+                        .tiles
+                        .iter()
+                        .filter(|t| rows.contains(&t.y))
+                        .cloned()
+                        .collect();
+
+                    server.send_message(
+                        id,
+                        DefaultChannel::ReliableOrdered,
+                        bincode::serialize(&ServerMessage::Resync {
+                            tiles,
+                            replace: game_settings.resync,
+                        })
+                        .unwrap(),
+                    );
                 }
-                _ => unreachable!(),
+                // Acks are only expected on the unreliable channel
+                ClientMessage::Ack(_) => (),
             }
         }
 
         while let Some(message) = server.receive_message(id, DefaultChannel::Unreliable) {
-            match bincode::deserialize(&message).unwrap() {
-                ClientMessage::Status { player, population } => {
-                    if let Some(p) = players.0.iter_mut().find(|e| e.id == player.id) {
-                        *p = player;
-                    }
-                    update_population_ev.send(UpdatePopulationEv { population, id });
+            match bincode::deserialize(&message) {
+                Ok(ClientMessage::Ack(seq)) => {
+                    // Acks can arrive out of order, keep the most recent
+                    let acked = history.acked.entry(id).or_default();
+                    *acked = (*acked).max(seq);
+                }
+                _ => {
+                    server.disconnect(id);
+                    continue 'client;
                 }
-                _ => unreachable!(),
             }
         }
     }
@@ -263,50 +744,42 @@ pub fn client_send_message(
 
 pub fn client_receive_message(
     mut commands: Commands,
-    mut n_players_q: Query<&mut Text, With<LobbyTextCmp>>,
+    mut lobby: ResMut<Lobby>,
     mut client: ResMut<RenetClient>,
     mut game_settings: ResMut<GameSettings>,
+    mut players: ResMut<Players>,
     mut map: ResMut<Map>,
-    mut spawn_ant_ev: EventWriter<SpawnAntEv>,
-    mut spawn_egg_ev: EventWriter<SpawnEggEv>,
     game_state: Res<State<GameState>>,
     mut next_app_state: ResMut<NextState<AppState>>,
     mut next_game_state: ResMut<NextState<GameState>>,
+    mut history: ResMut<SnapshotHistory>,
+    mut stats: ResMut<NetworkStats>,
     mut update_population_ev: EventWriter<UpdatePopulationEv>,
+    mut checksum_ev: EventWriter<ChecksumEv>,
+    mut chat_ev: EventWriter<ChatEv>,
+    mut alert_ev: EventWriter<AlertEv>,
+    spectator: Option<Res<Spectator>>,
 ) {
     while let Some(message) = client.receive_message(DefaultChannel::ReliableOrdered) {
         match bincode::deserialize(&message).unwrap() {
             ServerMessage::LoadGame {
                 background,
                 fog_of_war,
+                upkeep,
                 player,
+                colonies,
                 map,
-                population,
             } => {
-                *game_settings = GameSettings {
-                    game_mode: GameMode::Multiplayer,
-                    background,
-                    fog_of_war,
-                    ..game_settings.clone()
-                };
-
-                commands.insert_resource(Players(Vec::from([player, Player::default()])));
-
-                for (_, (transform, ant)) in population.ants {
-                    spawn_ant_ev.send(SpawnAntEv {
-                        ant,
-                        transform,
-                        entity: None,
-                    });
-                }
-                for (_, (transform, egg)) in population.eggs {
-                    spawn_egg_ev.send(SpawnEggEv {
-                        ant: egg.ant,
-                        transform,
-                        entity: None,
-                    });
-                }
+                game_settings.apply_host_rules(background, fog_of_war, upkeep);
 
+                // The colony arrives with the next status from the host
+                commands.insert_resource(Players(
+                    [player]
+                        .into_iter()
+                        .chain(colonies)
+                        .chain([Player::default()])
+                        .collect(),
+                ));
                 commands.insert_resource(map);
 
                 // Indicate the draw_map system to not load the starting queen
@@ -314,28 +787,59 @@ pub fn client_receive_message(
 
                 next_app_state.set(AppState::Game);
             }
-            ServerMessage::NPlayers(i) => {
-                if let Ok(mut text) = n_players_q.get_single_mut() {
-                    text.0 = format!("There are {i} players in the lobby.\nWaiting for the host to start the game...");
-                }
+            ServerMessage::Lobby(new_lobby) => {
+                *lobby = new_lobby;
+            }
+            ServerMessage::Observe {
+                id,
+                background,
+                fog_of_war,
+                upkeep,
+                map,
+            } => {
+                game_settings.apply_host_rules(background, fog_of_war, upkeep);
+
+                // The colonies arrive with the next status from the host
+                let player = Player::new(id, game_settings.color);
+                commands.insert_resource(Players(Vec::from([player, Player::default()])));
+                commands.insert_resource(map);
+                commands.insert_resource(Spectator::default());
+                commands.insert_resource(GameLoaded);
+
+                next_app_state.set(AppState::Game);
             }
             ServerMessage::StartGame {
                 id,
                 background,
                 fog_of_war,
+                upkeep,
+                token,
+                colonies,
                 map,
             } => {
-                *game_settings = GameSettings {
-                    game_mode: GameMode::Multiplayer,
-                    background,
-                    fog_of_war,
-                    ..game_settings.clone()
-                };
+                game_settings.apply_host_rules(background, fog_of_war, upkeep);
+
+                // Store the credentials to be able to rejoin the game
+                commands.insert_resource(ReconnectToken { id, token });
+
+                let player = lobby
+                    .to_players()
+                    .into_iter()
+                    .find(|p| p.id == id)
+                    .unwrap_or_else(|| Player::new(id, game_settings.color));
+
+                // The alliances of the other colonies are needed to tell friend from foe
+                commands.insert_resource(Players(
+                    [player]
+                        .into_iter()
+                        .chain(colonies)
+                        .chain([Player::default()])
+                        .collect(),
+                ));
 
-                commands.insert_resource(Players(Vec::from([
-                    Player::new(id, game_settings.color),
-                    Player::default(),
-                ])));
+                if lobby.observers.contains(&id) {
+                    commands.insert_resource(Spectator::default());
+                }
 
                 commands.insert_resource(map);
                 next_app_state.set(AppState::Game);
@@ -346,9 +850,29 @@ pub fn client_receive_message(
                 {
                     next_game_state.set(GameState::Paused)
                 }
+                // Observers have no traits to choose
+                GameState::TraitSelection if spectator.is_some() => (),
                 s @ GameState::Running | s @ GameState::TraitSelection => next_game_state.set(s),
                 _ => (),
             },
+            ServerMessage::Checksum(checksum) => {
+                checksum_ev.send(ChecksumEv(checksum));
+            }
+            ServerMessage::Resync { tiles, replace } => resync_tiles(&mut map, tiles, replace),
+            ServerMessage::Chat(entry) => {
+                chat_ev.send(ChatEv(entry));
+            }
+            ServerMessage::Stats(game_stats) => {
+                commands.insert_resource(game_stats);
+            }
+            ServerMessage::StatsUpdate(update) => {
+                commands.queue(move |world: &mut World| {
+                    world.resource_mut::<GameStats>().merge(update);
+                });
+            }
+            ServerMessage::Alert(alert) => {
+                alert_ev.send(AlertEv(alert));
+            }
             _ => unreachable!(),
         }
     }
@@ -364,9 +888,56 @@ pub fn client_receive_message(
 
     while let Some(message) = client.receive_message(DefaultChannel::Unreliable) {
         match bincode::deserialize(&message).unwrap() {
-            ServerMessage::Status { speed, population } => {
+            ServerMessage::Status {
+                speed,
+                player,
+                snapshot,
+            } => {
+                // Skip outdated snapshots and deltas against a baseline that is no longer stored
+                let baseline = snapshot.baseline.map(|seq| history.get(seq));
+                if snapshot.seq <= history.seq || baseline.is_some_and(|b| b.is_none()) {
+                    continue;
+                }
+
                 game_settings.speed = speed;
-                update_population_ev.send(UpdatePopulationEv { population, id: 0 });
+
+                // The vision is calculated locally every frame
+                if player.id == players.main_id() {
+                    let main = players.main_mut();
+                    *main = Player {
+                        visible_tiles: std::mem::take(&mut main.visible_tiles),
+                        ..player
+                    };
+                }
+
+                *stats = NetworkStats {
+                    size: message.len() as u64,
+                    changed: snapshot.len(),
+                    delta: snapshot.baseline.is_some(),
+                    ..default()
+                };
+
+                let seq = snapshot.seq;
+                let snapshot = snapshot.apply(baseline.flatten());
+                stats.entities = snapshot.ants.len() + snapshot.eggs.len();
+
+                update_population_ev.send(UpdatePopulationEv {
+                    population: snapshot.to_population(),
+                });
+
+                history.push(seq, snapshot);
+                client.send_message(
+                    DefaultChannel::Unreliable,
+                    bincode::serialize(&ClientMessage::Ack(seq)).unwrap(),
+                );
+            }
+            ServerMessage::Colonies(colonies) => {
+                let main = players.main().clone();
+                players.0 = [main]
+                    .into_iter()
+                    .chain(colonies)
+                    .chain([Player::default()])
+                    .collect();
             }
             _ => unreachable!(),
         }
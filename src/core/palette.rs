@@ -0,0 +1,84 @@
+use crate::core::ants::components::{Ant, AntCmp};
+use crate::core::assets::WorldAssets;
+use crate::core::constants::MAX_PALETTE_SWAPS;
+use crate::core::game_settings::GameSettings;
+use crate::core::menu::settings::{AntColor, Palette};
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use strum::IntoEnumIterator;
+
+/// Handle of a sprite that is swapped from the red sprites at runtime
+pub fn swapped_image(name: &str) -> Handle<Image> {
+    let mut hasher = DefaultHasher::new();
+    name.hash(&mut hasher);
+
+    // Prefix the hash to not collide with other assets with fixed ids
+    Handle::weak_from_u128((0x6d61_7261_6275_6e74 << 64) | hasher.finish() as u128)
+}
+
+/// Recolor a red sprite to the given color, keeping its shading and highlights
+fn swap_palette(image: &Image, color: Color) -> Image {
+    let base = AntColor::Red.to_color(&Palette::Standard).to_srgba();
+    let target = color.to_srgba();
+
+    let mut swapped = image.clone();
+    for pixel in swapped.data.chunks_exact_mut(4).filter(|p| p[3] > 0) {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.);
+
+        // The gray part of the pixel is the shading, the rest is the colony's color
+        let gray = g.min(b);
+        let chroma = (r - gray).max(0.) / base.red;
+
+        for (c, t) in pixel
+            .iter_mut()
+            .zip([target.red, target.green, target.blue])
+        {
+            *c = ((gray + chroma * t).min(1.) * 255.) as u8;
+        }
+    }
+
+    swapped
+}
+
+/// Create the sprites of the colors without sprites of their own once the red ones are loaded
+pub fn swap_palettes(
+    mut images: ResMut<Assets<Image>>,
+    game_settings: Res<GameSettings>,
+    mut swapped: Local<HashMap<String, Palette>>,
+    assets: Local<WorldAssets>,
+) {
+    let mut n = 0;
+    for ant in Ant::iter().filter(|a| a.colors().count() > 1) {
+        for animation in ant.all_animations() {
+            let red = AntCmp::base(&ant).with_color(&AntColor::Red);
+            let name = red.atlas(&animation);
+
+            // Redo the sprites when the player chooses another palette
+            if n == MAX_PALETTE_SWAPS || swapped.get(&name) == Some(&game_settings.palette) {
+                continue;
+            }
+
+            let Some(image) = images.get(&assets.image(&name)) else {
+                continue;
+            };
+
+            let sprites = AntColor::iter()
+                .filter(|c| !c.has_sprites())
+                .map(|c| {
+                    (
+                        assets.image(&red.clone().with_color(&c).atlas(&animation)),
+                        swap_palette(image, c.to_color(&game_settings.palette)),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            for (handle, sprite) in sprites {
+                images.insert(handle.id(), sprite);
+            }
+
+            swapped.insert(name, game_settings.palette);
+            n += 1;
+        }
+    }
+}
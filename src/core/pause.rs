@@ -1,6 +1,8 @@
 use crate::core::assets::WorldAssets;
 use crate::core::constants::{GAME_SPEED_STEP, MAX_GAME_SPEED, MAX_Z_SCORE};
 use crate::core::game_settings::GameSettings;
+use crate::core::keymap::{InputAction, Keymap};
+use crate::core::locale::Localized;
 use crate::core::map::ui::utils::add_root_node;
 use crate::core::map::ui::utils::add_text;
 use crate::core::states::{AppState, GameState};
@@ -20,7 +22,8 @@ pub fn spawn_pause_banner(
         .spawn((add_root_node(), Visibility::Hidden, PauseCmp))
         .with_children(|parent| {
             parent.spawn((
-                add_text("Paused", "bold", 35., &assets, &window),
+                add_text("", "bold", 35., &assets, &window),
+                Localized::new("ui.paused"),
                 TextColor(Color::from(WHITE)),
                 TextLayout::new_with_justify(JustifyText::Center),
                 Transform::from_xyz(0., 0., MAX_Z_SCORE),
@@ -28,15 +31,19 @@ pub fn spawn_pause_banner(
         });
 }
 
-pub fn pause_game(mut vis_q: Query<&mut Visibility, With<PauseCmp>>, audio: Res<Audio>) {
+pub fn pause_game(mut vis_q: Query<&mut Visibility, With<PauseCmp>>, audio: Option<Res<Audio>>) {
     *vis_q.single_mut() = Visibility::Visible;
-    audio.pause();
+
+    // The dedicated server runs without audio
+    if let Some(audio) = audio {
+        audio.pause();
+    }
 }
 
 pub fn unpause_game(
     mut vis_q: Query<&mut Visibility, With<PauseCmp>>,
     mut game_settings: ResMut<GameSettings>,
-    audio: Res<Audio>,
+    audio: Option<Res<Audio>>,
 ) {
     // PauseWrapper not yet spawned at first iteration
     if let Ok(mut e) = vis_q.get_single_mut() {
@@ -44,12 +51,15 @@ pub fn unpause_game(
             game_settings.speed = 1.;
         }
         *e = Visibility::Hidden;
-        audio.resume();
+        if let Some(audio) = audio {
+            audio.resume();
+        }
     }
 }
 
 pub fn toggle_pause_keyboard(
     keyboard: Res<ButtonInput<KeyCode>>,
+    keymap: Res<Keymap>,
     app_state: Res<State<AppState>>,
     game_state: Res<State<GameState>>,
     mut next_app_state: ResMut<NextState<AppState>>,
@@ -63,6 +73,7 @@ pub fn toggle_pause_keyboard(
             | AppState::Lobby
             | AppState::ConnectedLobby
             | AppState::Settings => next_app_state.set(AppState::MainMenu),
+            AppState::Controls | AppState::Mixer => next_app_state.set(AppState::Settings),
             AppState::Game => match game_state.get() {
                 GameState::Running => next_game_state.set(GameState::InGameMenu),
                 GameState::Paused => next_game_state.set(GameState::InGameMenu),
@@ -74,7 +85,7 @@ pub fn toggle_pause_keyboard(
         }
     }
 
-    if keyboard.just_pressed(KeyCode::Space) {
+    if keymap.just_pressed(&keyboard, &InputAction::Pause) {
         match game_state.get() {
             GameState::Running => next_game_state.set(GameState::Paused),
             GameState::Paused => next_game_state.set(GameState::Running),
@@ -3,11 +3,11 @@ use crate::core::ants::events::{SpawnAntEv, SpawnEggEv};
 use crate::core::game_settings::GameSettings;
 use crate::core::map::map::Map;
 use crate::core::network::{ServerMessage, ServerSendMessage};
-use crate::core::player::Players;
+use crate::core::player::{Player, Players};
 use crate::core::states::{AppState, AudioState};
 use bevy::prelude::*;
 use bevy::utils::hashbrown::HashMap;
-use bevy_renet::renet::RenetServer;
+use bevy_renet::renet::{ClientId, RenetServer};
 #[cfg(not(target_arch = "wasm32"))]
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
@@ -112,31 +112,25 @@ pub fn load_game(
                                     e.ant.team = *new_id;
                                 }
                             });
+                        }
 
+                        // Send the colonies once every player has its new id
+                        for id in server.clients_id() {
+                            let players = &data.players.0;
                             server_send_message.send(ServerSendMessage {
                                 message: ServerMessage::LoadGame {
                                     background: data.game_settings.background,
                                     fog_of_war: data.game_settings.fog_of_war,
-                                    player: player.clone(),
+                                    upkeep: data.game_settings.upkeep,
+                                    player: players.iter().find(|p| p.id == id).unwrap().clone(),
+                                    colonies: players
+                                        .iter()
+                                        .filter(|p| p.id != id && p.id != ClientId::MAX)
+                                        .map(Player::roster)
+                                        .collect(),
                                     map: data.map.clone(),
-                                    population: Population {
-                                        ants: data
-                                            .population
-                                            .ants
-                                            .clone()
-                                            .into_iter()
-                                            .filter(|(_, (_, a))| a.team == *new_id)
-                                            .collect(),
-                                        eggs: data
-                                            .population
-                                            .eggs
-                                            .clone()
-                                            .into_iter()
-                                            .filter(|(_, (_, e))| e.team == *new_id)
-                                            .collect(),
-                                    },
                                 },
-                                client: Some(*new_id),
+                                client: Some(id),
                             });
                         }
                     }
@@ -151,24 +145,15 @@ pub fn load_game(
             commands.insert_resource(data.players);
             commands.insert_resource(data.map);
 
-            for (_, (transform, ant)) in data
-                .population
-                .ants
-                .into_iter()
-                .filter(|(_, (_, a))| a.team == 0 || !a.kind.is_ant())
-            {
+            // The host simulates every colony, the clients receive them with the status
+            for (_, (transform, ant)) in data.population.ants {
                 spawn_ant_ev.send(SpawnAntEv {
                     ant,
                     transform,
                     entity: None,
                 });
             }
-            for (_, (transform, egg)) in data
-                .population
-                .eggs
-                .into_iter()
-                .filter(|(_, (_, e))| e.team == 0)
-            {
+            for (_, (transform, egg)) in data.population.eggs {
                 spawn_egg_ev.send(SpawnEggEv {
                     ant: egg.ant,
                     transform,
@@ -1,4 +1,4 @@
-use crate::core::ants::components::Ant;
+use crate::core::ants::components::{Ant, AntCmp};
 use crate::core::menu::settings::AntColor;
 use crate::core::resources::Resources;
 use crate::core::traits::Trait;
@@ -43,23 +43,37 @@ impl Players {
     pub fn main_id(&self) -> ClientId {
         self.main().id
     }
+
+    /// Whether two colonies are on the same side (a colony is always its own ally)
+    pub fn are_allies(&self, id1: ClientId, id2: ClientId) -> bool {
+        id1 == id2
+            || matches!(
+                (self.get(id1).alliance, self.get(id2).alliance),
+                (Some(a1), Some(a2)) if a1 == a2
+            )
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub id: ClientId,
+    pub name: String,
     pub color: AntColor,
+    pub alliance: Option<u8>, // Team chosen in the lobby (None fights everyone)
     pub resources: Resources,
     pub visible_tiles: HashSet<(u32, u32)>,
     pub queue: VecDeque<Ant>,
     pub traits: Vec<Trait>,
+    pub disconnected: bool,
 }
 
 impl Default for Player {
     fn default() -> Self {
         Self {
             id: ClientId::MAX,
+            name: String::new(),
             color: AntColor::Black,
+            alliance: None,
             resources: Resources {
                 leaves: 150.,
                 nutrients: 0.,
@@ -67,6 +81,7 @@ impl Default for Player {
             visible_tiles: HashSet::new(),
             queue: VecDeque::from([Ant::Worker, Ant::Worker, Ant::Worker]),
             traits: vec![],
+            disconnected: false,
         }
     }
 }
@@ -80,6 +95,15 @@ impl Player {
         }
     }
 
+    /// What other players know about the colony: its name, color and alliance
+    pub fn roster(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            alliance: self.alliance,
+            ..Self::new(self.id, self.color)
+        }
+    }
+
     /// Whether the player is human
     pub fn is_human(&self) -> bool {
         self.id == 0 || (self.id > 1000 && self.id < ClientId::MAX)
@@ -90,16 +114,36 @@ impl Player {
         self.id > 0 && self.id < 1000
     }
 
+    /// Whether the player is controlled by the computer (NPCs and disconnected players)
+    pub fn is_ai(&self) -> bool {
+        self.is_npc() || (self.is_human() && self.disconnected)
+    }
+
     /// Whether the player has the specified trait
     pub fn has_trait(&self, t: &Trait) -> bool {
         self.traits.contains(t)
     }
 
+    /// Factor applied to the damage the player's ants deal against the given ant type
+    pub fn damage_factor(&self, kind: &Ant) -> f32 {
+        self.traits.iter().map(|t| t.damage_factor(kind)).product()
+    }
+
+    /// Remove an ant from the queue and refund its price (returns the ant and the refund)
+    pub fn dequeue(&mut self, index: usize) -> Option<(Ant, Resources)> {
+        let ant = self.queue.get(index)?.clone();
+        let price = AntCmp::new(&ant, self).price;
+        self.resources += price;
+        self.queue.remove(index);
+        Some((ant, price))
+    }
+
     /// Whether the player can breed this ant type
     pub fn has_ant(&self, ant: &Ant) -> bool {
         match ant {
             Ant::Alate => self.has_trait(&Trait::Alate),
             Ant::Mastodon => self.has_trait(&Trait::Mastodon),
+            Ant::Spitter => self.has_trait(&Trait::Spitter),
             a if a.is_ant() => true,
             _ => false,
         }
@@ -0,0 +1,469 @@
+use crate::core::ants::components::{Action, AntCmp, Behavior, Egg};
+use crate::core::assets::WorldAssets;
+use crate::core::constants::{MAX_Z_SCORE, POSITION_PRECISION, SNAPSHOT_HISTORY};
+use crate::core::map::systems::MapCmp;
+use crate::core::map::ui::utils::add_text;
+use crate::core::persistence::Population;
+use crate::core::resources::Resources;
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use bevy_renet::renet::{ClientId, RenetClient, RenetServer};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+/// Position and rotation of an entity quantized to reduce the size of the snapshots
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuantizedTransform {
+    pub x: i16,
+    pub y: i16,
+    pub rotation: u16,
+}
+
+impl QuantizedTransform {
+    pub fn new(transform: &Transform) -> Self {
+        let (_, _, angle) = transform.rotation.to_euler(EulerRot::XYZ);
+
+        Self {
+            x: (transform.translation.x * POSITION_PRECISION).round() as i16,
+            y: (transform.translation.y * POSITION_PRECISION).round() as i16,
+            rotation: (angle.rem_euclid(2. * PI) / (2. * PI) * u16::MAX as f32).round() as u16,
+        }
+    }
+
+    pub fn to_transform(&self) -> Transform {
+        Transform {
+            translation: Vec3::new(
+                self.x as f32 / POSITION_PRECISION,
+                self.y as f32 / POSITION_PRECISION,
+                0.,
+            ),
+            rotation: Quat::from_rotation_z(self.rotation as f32 / u16::MAX as f32 * 2. * PI),
+            ..default()
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct AntState {
+    pub transform: QuantizedTransform,
+    pub ant: AntCmp,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct EggState {
+    pub transform: QuantizedTransform,
+    pub egg: Egg,
+}
+
+/// State of the population at one moment in time
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    pub ants: HashMap<Entity, AntState>,
+    pub eggs: HashMap<Entity, EggState>,
+}
+
+impl Snapshot {
+    pub fn new<'a>(
+        ants: impl Iterator<Item = (Entity, &'a Transform, &'a AntCmp)>,
+        eggs: impl Iterator<Item = (Entity, &'a Transform, &'a Egg)>,
+    ) -> Self {
+        Self {
+            ants: ants
+                .map(|(e, t, a)| {
+                    (
+                        e,
+                        AntState {
+                            transform: QuantizedTransform::new(t),
+                            ant: a.clone(),
+                        },
+                    )
+                })
+                .collect(),
+            eggs: eggs
+                .map(|(e, t, egg)| {
+                    (
+                        e,
+                        EggState {
+                            transform: QuantizedTransform::new(t),
+                            egg: egg.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_population(&self) -> Population {
+        Population {
+            ants: self
+                .ants
+                .iter()
+                .map(|(e, s)| (*e, (s.transform.to_transform(), s.ant.clone())))
+                .collect(),
+            eggs: self
+                .eggs
+                .iter()
+                .map(|(e, s)| (*e, (s.transform.to_transform(), s.egg.clone())))
+                .collect(),
+        }
+    }
+}
+
+/// Return the new value only if it differs from the old one
+fn changed<T: PartialEq + Clone>(old: &T, new: &T) -> Option<T> {
+    (old != new).then(|| new.clone())
+}
+
+/// Fields of an ant that change during the game (None when unchanged)
+#[derive(Default, PartialEq, Serialize, Deserialize)]
+pub struct AntFields {
+    pub transform: Option<QuantizedTransform>,
+    pub health: Option<f32>,
+    pub carry: Option<Resources>,
+    pub command: Option<Option<Behavior>>,
+    pub action: Option<Action>,
+    pub formation_speed: Option<Option<f32>>,
+    pub formation_offset: Option<Option<Vec2>>,
+    pub experience: Option<f32>,
+    pub kills: Option<u32>,
+    pub age: Option<f32>,
+    pub starving: Option<bool>,
+}
+
+impl AntFields {
+    /// Changed fields between two states. None if any other field changed
+    fn diff(old: &AntState, new: &AntState) -> Option<Self> {
+        let rest = AntCmp {
+            health: old.ant.health,
+            carry: old.ant.carry,
+            command: old.ant.command.clone(),
+            action: old.ant.action.clone(),
+            formation_speed: old.ant.formation_speed,
+            formation_offset: old.ant.formation_offset,
+            experience: old.ant.experience,
+            kills: old.ant.kills,
+            age: old.ant.age,
+            starving: old.ant.starving,
+            ..new.ant.clone()
+        };
+
+        (rest == old.ant).then(|| Self {
+            transform: changed(&old.transform, &new.transform),
+            health: changed(&old.ant.health, &new.ant.health),
+            carry: changed(&old.ant.carry, &new.ant.carry),
+            command: changed(&old.ant.command, &new.ant.command),
+            action: changed(&old.ant.action, &new.ant.action),
+            formation_speed: changed(&old.ant.formation_speed, &new.ant.formation_speed),
+            formation_offset: changed(&old.ant.formation_offset, &new.ant.formation_offset),
+            experience: changed(&old.ant.experience, &new.ant.experience),
+            kills: changed(&old.ant.kills, &new.ant.kills),
+            age: changed(&old.ant.age, &new.ant.age),
+            starving: changed(&old.ant.starving, &new.ant.starving),
+        })
+    }
+
+    fn apply(self, state: &mut AntState) {
+        if let Some(v) = self.transform {
+            state.transform = v;
+        }
+        if let Some(v) = self.health {
+            state.ant.health = v;
+        }
+        if let Some(v) = self.carry {
+            state.ant.carry = v;
+        }
+        if let Some(v) = self.command {
+            state.ant.command = v;
+        }
+        if let Some(v) = self.action {
+            state.ant.action = v;
+        }
+        if let Some(v) = self.formation_speed {
+            state.ant.formation_speed = v;
+        }
+        if let Some(v) = self.formation_offset {
+            state.ant.formation_offset = v;
+        }
+        if let Some(v) = self.experience {
+            state.ant.experience = v;
+        }
+        if let Some(v) = self.kills {
+            state.ant.kills = v;
+        }
+        if let Some(v) = self.age {
+            state.ant.age = v;
+        }
+        if let Some(v) = self.starving {
+            state.ant.starving = v;
+        }
+    }
+}
+
+/// Fields of an egg that change during the game (None when unchanged)
+#[derive(Default, PartialEq, Serialize, Deserialize)]
+pub struct EggFields {
+    pub health: Option<f32>,
+    pub timer: Option<Timer>,
+}
+
+impl EggFields {
+    /// Changed fields between two states. None if any other field changed
+    fn diff(old: &EggState, new: &EggState) -> Option<Self> {
+        let rest = Egg {
+            health: old.egg.health,
+            timer: old.egg.timer.clone(),
+            ..new.egg.clone()
+        };
+
+        (rest == old.egg && old.transform == new.transform).then(|| Self {
+            health: changed(&old.egg.health, &new.egg.health),
+            timer: changed(&old.egg.timer, &new.egg.timer),
+        })
+    }
+
+    fn apply(self, state: &mut EggState) {
+        if let Some(v) = self.health {
+            state.egg.health = v;
+        }
+        if let Some(v) = self.timer {
+            state.egg.timer = v;
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum AntDelta {
+    Full(AntState),
+    Fields(AntFields),
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum EggDelta {
+    Full(EggState),
+    Fields(EggFields),
+}
+
+/// Difference between a snapshot and the baseline the client acknowledged
+#[derive(Serialize, Deserialize)]
+pub struct SnapshotDelta {
+    pub seq: u32,
+    pub baseline: Option<u32>,
+    pub ants: Vec<(Entity, AntDelta)>,
+    pub eggs: Vec<(Entity, EggDelta)>,
+    pub removed: Vec<Entity>,
+}
+
+impl SnapshotDelta {
+    pub fn new(seq: u32, baseline: Option<(u32, &Snapshot)>, snapshot: &Snapshot) -> Self {
+        let empty = Snapshot::default();
+        let base = baseline.map_or(&empty, |(_, b)| b);
+
+        Self {
+            seq,
+            baseline: baseline.map(|(s, _)| s),
+            ants: snapshot
+                .ants
+                .iter()
+                .filter_map(|(e, new)| {
+                    let delta = match base.ants.get(e) {
+                        Some(old) if old == new => return None,
+                        Some(old) => AntFields::diff(old, new)
+                            .map_or_else(|| AntDelta::Full(new.clone()), AntDelta::Fields),
+                        None => AntDelta::Full(new.clone()),
+                    };
+                    Some((*e, delta))
+                })
+                .collect(),
+            eggs: snapshot
+                .eggs
+                .iter()
+                .filter_map(|(e, new)| {
+                    let delta = match base.eggs.get(e) {
+                        Some(old) if old == new => return None,
+                        Some(old) => EggFields::diff(old, new)
+                            .map_or_else(|| EggDelta::Full(new.clone()), EggDelta::Fields),
+                        None => EggDelta::Full(new.clone()),
+                    };
+                    Some((*e, delta))
+                })
+                .collect(),
+            removed: base
+                .ants
+                .keys()
+                .filter(|e| !snapshot.ants.contains_key(*e))
+                .chain(base.eggs.keys().filter(|e| !snapshot.eggs.contains_key(*e)))
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Number of entities that changed with respect to the baseline
+    pub fn len(&self) -> usize {
+        self.ants.len() + self.eggs.len() + self.removed.len()
+    }
+
+    /// Reconstruct the snapshot from the baseline
+    pub fn apply(self, baseline: Option<&Snapshot>) -> Snapshot {
+        let mut snapshot = baseline.cloned().unwrap_or_default();
+
+        for e in self.removed {
+            snapshot.ants.remove(&e);
+            snapshot.eggs.remove(&e);
+        }
+
+        for (e, delta) in self.ants {
+            match delta {
+                AntDelta::Full(state) => {
+                    snapshot.ants.insert(e, state);
+                }
+                AntDelta::Fields(fields) => {
+                    if let Some(state) = snapshot.ants.get_mut(&e) {
+                        fields.apply(state);
+                    }
+                }
+            }
+        }
+
+        for (e, delta) in self.eggs {
+            match delta {
+                EggDelta::Full(state) => {
+                    snapshot.eggs.insert(e, state);
+                }
+                EggDelta::Fields(fields) => {
+                    if let Some(state) = snapshot.eggs.get_mut(&e) {
+                        fields.apply(state);
+                    }
+                }
+            }
+        }
+
+        snapshot
+    }
+}
+
+/// Last snapshots sent (host) or received (client) to compute the deltas against
+#[derive(Resource, Default)]
+pub struct SnapshotHistory {
+    /// Sequence number of the last snapshot
+    pub seq: u32,
+    pub snapshots: VecDeque<(u32, Snapshot)>,
+
+    /// Last snapshot acknowledged by every client (only used by the host)
+    pub acked: HashMap<ClientId, u32>,
+}
+
+impl SnapshotHistory {
+    pub fn get(&self, seq: u32) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .find_map(|(s, snapshot)| (*s == seq).then_some(snapshot))
+    }
+
+    pub fn push(&mut self, seq: u32, snapshot: Snapshot) {
+        self.seq = seq;
+        self.snapshots.push_back((seq, snapshot));
+        if self.snapshots.len() > SNAPSHOT_HISTORY {
+            self.snapshots.pop_front();
+        }
+    }
+}
+
+/// Statistics of the last snapshot shown in the network overlay
+#[derive(Resource, Default)]
+pub struct NetworkStats {
+    /// Size in bytes of the snapshots sent
+    pub size: u64,
+
+    /// Number of entities in the snapshot
+    pub entities: usize,
+
+    /// Number of entities sent (changed since the baseline)
+    pub changed: usize,
+
+    /// Whether the snapshot was a delta or the full state
+    pub delta: bool,
+}
+
+#[derive(Component)]
+pub struct NetworkOverlayCmp;
+
+pub fn spawn_network_overlay(
+    mut commands: Commands,
+    assets: Local<WorldAssets>,
+    window: Single<&Window>,
+) {
+    commands.spawn((
+        Node {
+            bottom: Val::Percent(2.),
+            left: Val::Percent(2.),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        add_text("", "medium", 8., &assets, &window),
+        Transform::from_xyz(0., 0., MAX_Z_SCORE),
+        Visibility::Hidden,
+        PickingBehavior::IGNORE,
+        NetworkOverlayCmp,
+        MapCmp,
+    ));
+}
+
+pub fn update_network_overlay(
+    mut overlay_q: Query<(&mut Text, &mut Visibility), With<NetworkOverlayCmp>>,
+    server: Option<Res<RenetServer>>,
+    client: Option<Res<RenetClient>>,
+    stats: Res<NetworkStats>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+) {
+    let Ok((mut text, mut visibility)) = overlay_q.get_single_mut() else {
+        return;
+    };
+
+    if keyboard.just_pressed(KeyCode::F3) {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+
+    if *visibility == Visibility::Hidden {
+        return;
+    }
+
+    // Bandwidth summed over all clients for the host
+    let (sent, received, loss) = if let Some(server) = server {
+        server
+            .clients_id()
+            .iter()
+            .filter_map(|id| server.network_info(*id).ok())
+            .fold((0., 0., 0f64), |(s, r, l), info| {
+                (
+                    s + info.bytes_sent_per_second,
+                    r + info.bytes_received_per_second,
+                    l.max(info.packet_loss),
+                )
+            })
+    } else if let Some(client) = client {
+        let info = client.network_info();
+        (
+            info.bytes_sent_per_second,
+            info.bytes_received_per_second,
+            info.packet_loss,
+        )
+    } else {
+        text.0 = "Not connected".to_string();
+        return;
+    };
+
+    text.0 = format!(
+        "Up: {:.1} kB/s | Down: {:.1} kB/s | Loss: {:.1}%\nSnapshot: {} B, {}/{} entities ({})",
+        sent / 1e3,
+        received / 1e3,
+        loss * 100.,
+        stats.size,
+        stats.changed,
+        stats.entities,
+        if stats.delta { "delta" } else { "full" },
+    );
+}
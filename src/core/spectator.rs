@@ -0,0 +1,209 @@
+use crate::core::ants::components::{Ant, AntCmp};
+use crate::core::assets::WorldAssets;
+use crate::core::constants::{LABEL_TEXT_SIZE, MAX_COLONIES};
+use crate::core::game_settings::GameSettings;
+use crate::core::locale::Locale;
+use crate::core::map::systems::MapCmp;
+use crate::core::map::ui::utils::add_text;
+use crate::core::player::{Player, Players};
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use bevy_renet::renet::ClientId;
+
+/// Present on clients that watch the game without colony
+#[derive(Resource, Default)]
+pub struct Spectator {
+    /// Colony whose vision is shown (None shows the whole map)
+    pub view: Option<ClientId>,
+}
+
+#[derive(Component)]
+pub struct SpectatorTextCmp;
+
+#[derive(Component)]
+pub struct ColonyStatsCmp(pub usize);
+
+#[derive(Component)]
+pub struct ColonyColorCmp(pub usize);
+
+#[derive(Component)]
+pub struct ColonyTextCmp(pub usize);
+
+/// Colonies in the game (the first player is the observer itself)
+fn colonies(players: &Players) -> Vec<&Player> {
+    players
+        .0
+        .iter()
+        .skip(1)
+        .filter(|p| p.id != ClientId::MAX)
+        .collect()
+}
+
+pub fn colony_name(player: &Player, locale: &Locale) -> String {
+    if player.name.is_empty() {
+        locale.fmt("spectator.colony_name", &[("id", player.id.to_string())])
+    } else {
+        player.name.clone()
+    }
+}
+
+pub fn spawn_spectator_overlay(
+    mut commands: Commands,
+    spectator: Option<Res<Spectator>>,
+    assets: Local<WorldAssets>,
+    window: Single<&Window>,
+) {
+    if spectator.is_none() {
+        return;
+    }
+
+    commands
+        .spawn((
+            Node {
+                top: Val::Percent(3.),
+                left: Val::Percent(2.),
+                width: Val::Percent(45.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Percent(0.5)),
+                ..default()
+            },
+            BackgroundColor(Color::srgba_u8(88, 57, 39, 200)),
+            BorderRadius::all(Val::Px(10.)),
+            PickingBehavior::IGNORE,
+            MapCmp,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                add_text("", "bold", LABEL_TEXT_SIZE, &assets, &window),
+                Node {
+                    margin: UiRect::ZERO.with_bottom(Val::Percent(1.)),
+                    ..default()
+                },
+                SpectatorTextCmp,
+            ));
+
+            for i in 0..MAX_COLONIES {
+                parent
+                    .spawn((
+                        Node {
+                            align_items: AlignItems::Center,
+                            flex_direction: FlexDirection::Row,
+                            ..default()
+                        },
+                        ColonyStatsCmp(i),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Node {
+                                width: Val::Percent(2.),
+                                height: Val::Percent(60.),
+                                aspect_ratio: Some(1.),
+                                margin: UiRect::ZERO.with_right(Val::Percent(1.)),
+                                ..default()
+                            },
+                            BackgroundColor(Color::NONE),
+                            ColonyColorCmp(i),
+                        ));
+
+                        parent.spawn((
+                            add_text("", "medium", LABEL_TEXT_SIZE, &assets, &window),
+                            ColonyTextCmp(i),
+                        ));
+                    });
+            }
+        });
+}
+
+pub fn cycle_spectator_view(
+    mut spectator: ResMut<Spectator>,
+    players: Res<Players>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+) {
+    if keyboard.just_pressed(KeyCode::Tab) {
+        let colonies = colonies(&players).iter().map(|p| p.id).collect::<Vec<_>>();
+
+        // Cycle through the full vision and the vision of every colony
+        spectator.view = match spectator
+            .view
+            .and_then(|id| colonies.iter().position(|c| *c == id))
+        {
+            Some(i) => colonies.get(i + 1).copied(),
+            None => colonies.first().copied(),
+        };
+    }
+}
+
+pub fn update_spectator_overlay(
+    mut header_q: Query<&mut Text, With<SpectatorTextCmp>>,
+    mut row_q: Query<(&mut Node, &ColonyStatsCmp)>,
+    mut color_q: Query<(&mut BackgroundColor, &ColonyColorCmp)>,
+    mut text_q: Query<(&mut Text, &ColonyTextCmp), Without<SpectatorTextCmp>>,
+    ant_q: Query<&AntCmp>,
+    players: Res<Players>,
+    spectator: Res<Spectator>,
+    game_settings: Res<GameSettings>,
+    locale: Res<Locale>,
+) {
+    let colonies = colonies(&players);
+
+    // Number of living ants and whether the queen is alive per colony
+    let mut population: HashMap<ClientId, (usize, bool)> = HashMap::new();
+    for ant in ant_q.iter().filter(|a| a.health > 0.) {
+        let (n, queen) = population.entry(ant.team).or_default();
+        *n += 1;
+        *queen |= ant.kind == Ant::Queen;
+    }
+
+    if let Ok(mut text) = header_q.get_single_mut() {
+        let view = match spectator.view {
+            Some(id) => locale.fmt(
+                "spectator.vision_of",
+                &[("colony", colony_name(players.get(id), &locale))],
+            ),
+            None => locale.get("spectator.full_vision"),
+        };
+
+        text.0 = locale.fmt("spectator.header", &[("view", view)]);
+    }
+
+    for (mut node, ColonyStatsCmp(i)) in &mut row_q {
+        node.display = if *i < colonies.len() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+
+    for (mut bgcolor, ColonyColorCmp(i)) in &mut color_q {
+        if let Some(player) = colonies.get(*i) {
+            bgcolor.0 = player.color.to_color(&game_settings.palette);
+        }
+    }
+
+    for (mut text, ColonyTextCmp(i)) in &mut text_q {
+        if let Some(player) = colonies.get(*i) {
+            let team = player.alliance.map_or(String::new(), |a| {
+                format!(
+                    " ({})",
+                    locale.fmt("lobby.team", &[("team", a.to_string())])
+                )
+            });
+
+            let colony = ("colony", format!("{}{team}", colony_name(player, &locale)));
+            text.0 = match population.get(&player.id) {
+                Some((n, true)) => locale.fmt(
+                    "spectator.colony",
+                    &[
+                        colony,
+                        ("ants", n.to_string()),
+                        ("leaves", format!("{:.0}", player.resources.leaves)),
+                        ("nutrients", format!("{:.0}", player.resources.nutrients)),
+                        ("traits", player.traits.len().to_string()),
+                    ],
+                ),
+                _ => locale.fmt("spectator.defeated", &[colony]),
+            };
+        }
+    }
+}
@@ -11,6 +11,8 @@ pub enum AppState {
     Lobby,
     ConnectedLobby,
     Settings,
+    Controls,
+    Mixer,
     Game,
 }
 
@@ -0,0 +1,416 @@
+use crate::core::ants::components::{Ant, AntCmp};
+use crate::core::assets::WorldAssets;
+use crate::core::constants::*;
+use crate::core::game_settings::GameSettings;
+use crate::core::locale::Locale;
+use crate::core::map::ui::utils::add_text;
+use crate::core::network::{ServerMessage, ServerSendMessage};
+use crate::core::player::{Player, Players};
+use crate::core::resources::Resources;
+use crate::core::spectator::colony_name;
+use crate::core::traits::Trait;
+use crate::core::utils::scale_duration;
+use crate::utils::NameFromEnum;
+use bevy::hierarchy::{ChildBuild, ChildBuilder};
+use bevy::prelude::*;
+use bevy::utils::hashbrown::HashMap;
+use bevy_renet::renet::{ClientId, RenetServer};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+/// State of a colony at a moment of the match
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Sample {
+    pub time: f32,
+    pub population: u32,
+    pub resources: Resources,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ColonyStats {
+    pub bred: HashMap<Ant, u32>,
+    pub lost: HashMap<Ant, u32>,
+    pub kills: HashMap<Ant, u32>, // Enemies killed per caste of the killer
+    pub monsters_killed: u32,
+    pub gathered: Resources,
+    pub spent: Resources,
+    pub tiles_dug: u32,
+    pub traits: Vec<(f32, Trait)>, // Trait chosen and the time it was chosen
+    pub timeline: Vec<Sample>,
+}
+
+impl ColonyStats {
+    pub fn buy(&mut self, ant: &Ant, price: &Resources) {
+        *self.bred.entry(ant.clone()).or_default() += 1;
+        self.spent += price;
+    }
+
+    /// Undo the purchase of an ant removed from the queue
+    pub fn refund(&mut self, ant: &Ant, price: &Resources) {
+        if let Some(n) = self.bred.get_mut(ant) {
+            *n = n.saturating_sub(1);
+        }
+        self.spent -= price;
+    }
+
+    /// Counters of the colony with only the last sample of the timeline
+    fn last_update(&self) -> Self {
+        Self {
+            bred: self.bred.clone(),
+            lost: self.lost.clone(),
+            kills: self.kills.clone(),
+            traits: self.traits.clone(),
+            timeline: self.timeline.last().copied().into_iter().collect(),
+            ..*self
+        }
+    }
+}
+
+/// Statistics of the match, collected by the host and shared with the clients
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct GameStats {
+    /// Seconds of game time since the start of the match
+    pub time: f32,
+
+    /// Time at which the next sample of the timeline is taken
+    pub next_sample: f32,
+
+    pub colonies: HashMap<ClientId, ColonyStats>,
+}
+
+impl GameStats {
+    pub fn colony(&mut self, id: ClientId) -> &mut ColonyStats {
+        self.colonies.entry(id).or_default()
+    }
+
+    /// Statistics with only the last sample of every timeline, sent to the clients
+    /// while the match runs
+    pub fn last_update(&self) -> Self {
+        Self {
+            time: self.time,
+            next_sample: self.next_sample,
+            colonies: self
+                .colonies
+                .iter()
+                .map(|(id, colony)| (*id, colony.last_update()))
+                .collect(),
+        }
+    }
+
+    /// Replace the counters with the ones of an update and extend the timelines
+    pub fn merge(&mut self, update: GameStats) {
+        self.time = update.time;
+        self.next_sample = update.next_sample;
+
+        for (id, mut update) in update.colonies {
+            let colony = self.colony(id);
+            let mut timeline = std::mem::take(&mut colony.timeline);
+            timeline.append(&mut update.timeline);
+            *colony = ColonyStats { timeline, ..update };
+        }
+    }
+
+    /// Add the current population and resources of every colony to the timeline
+    pub fn sample<'a>(&mut self, players: &Players, ants: impl Iterator<Item = &'a AntCmp>) {
+        let mut population: HashMap<ClientId, u32> = HashMap::new();
+        for ant in ants.filter(|a| a.health > 0.) {
+            *population.entry(ant.team).or_default() += 1;
+        }
+
+        for player in players.0.iter().filter(|p| p.id != ClientId::MAX) {
+            // Skip players without colony (like the host of a dedicated server)
+            let n = population.get(&player.id).copied().unwrap_or(0);
+            if n > 0 || self.colonies.contains_key(&player.id) {
+                let time = self.time;
+                self.colony(player.id).timeline.push(Sample {
+                    time,
+                    population: n,
+                    resources: player.resources,
+                });
+            }
+        }
+    }
+}
+
+fn format_time(secs: f32) -> String {
+    format!("{}:{:02}", secs as u32 / 60, secs as u32 % 60)
+}
+
+/// Counts per caste in descending order, e.g. "12 Worker, 3 Soldier"
+fn format_castes(counts: &HashMap<Ant, u32>, locale: &Locale) -> String {
+    let mut counts = Ant::iter()
+        .filter_map(|a| counts.get(&a).map(|n| (a, *n)))
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1));
+
+    counts
+        .iter()
+        .map(|(a, n)| format!("{n} {}", locale.get(&format!("ant.{}", a.to_snake()))))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn update_stats(
+    ant_q: Query<&AntCmp>,
+    players: Res<Players>,
+    mut stats: ResMut<GameStats>,
+    server: Option<Res<RenetServer>>,
+    mut server_send_message: EventWriter<ServerSendMessage>,
+    game_settings: Res<GameSettings>,
+    time: Res<Time>,
+) {
+    stats.time += scale_duration(time.delta(), game_settings.speed).as_secs_f32();
+
+    if stats.time >= stats.next_sample {
+        stats.sample(&players, ant_q.iter());
+        stats.next_sample += STATS_INTERVAL;
+
+        // Keep the statistics of the clients up to date for the end-game screen
+        if server.is_some() {
+            server_send_message.send(ServerSendMessage {
+                message: ServerMessage::StatsUpdate(stats.last_update()),
+                client: None,
+            });
+        }
+    }
+}
+
+/// Add the final state of the colonies to the timeline and share the complete statistics
+pub fn end_stats(
+    ant_q: Query<&AntCmp>,
+    players: Res<Players>,
+    mut stats: ResMut<GameStats>,
+    server: Option<Res<RenetServer>>,
+    mut server_send_message: EventWriter<ServerSendMessage>,
+) {
+    stats.sample(&players, ant_q.iter());
+
+    if server.is_some() {
+        server_send_message.send(ServerSendMessage {
+            message: ServerMessage::Stats(stats.clone()),
+            client: None,
+        });
+    }
+}
+
+/// Draw the timeline of one value for every colony as a scatter line
+fn spawn_graph(
+    parent: &mut ChildBuilder,
+    title: &str,
+    colonies: &[(&Player, &ColonyStats)],
+    value: impl Fn(&Sample) -> f32,
+    duration: f32,
+    locale: &Locale,
+    assets: &WorldAssets,
+    window: &Window,
+) {
+    let max = colonies
+        .iter()
+        .flat_map(|(_, s)| s.timeline.iter().map(&value))
+        .fold(1., f32::max);
+
+    parent
+        .spawn(Node {
+            width: Val::Percent(30.),
+            flex_direction: FlexDirection::Column,
+            margin: UiRect::horizontal(Val::Percent(1.)),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(add_text(
+                locale.fmt(
+                    "stats.graph",
+                    &[("title", locale.get(title)), ("max", format!("{max:.0}"))],
+                ),
+                "bold",
+                LABEL_TEXT_SIZE,
+                assets,
+                window,
+            ));
+
+            parent
+                .spawn((
+                    Node {
+                        width: Val::Percent(100.),
+                        aspect_ratio: Some(2.),
+                        ..default()
+                    },
+                    BackgroundColor(Color::srgba(0., 0., 0., 0.5)),
+                ))
+                .with_children(|parent| {
+                    for (i, (_, colony)) in colonies.iter().enumerate() {
+                        for sample in &colony.timeline {
+                            parent.spawn((
+                                Node {
+                                    left: Val::Percent(98. * sample.time / duration),
+                                    bottom: Val::Percent(96. * value(sample) / max),
+                                    width: Val::Percent(1.),
+                                    aspect_ratio: Some(1.),
+                                    position_type: PositionType::Absolute,
+                                    ..default()
+                                },
+                                BackgroundColor(GRAPH_COLORS[i % GRAPH_COLORS.len()]),
+                            ));
+                        }
+                    }
+                });
+
+            parent
+                .spawn(Node {
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(add_text("0:00", "medium", LABEL_TEXT_SIZE, assets, window));
+                    parent.spawn(add_text(
+                        format_time(duration),
+                        "medium",
+                        LABEL_TEXT_SIZE,
+                        assets,
+                        window,
+                    ));
+                });
+        });
+}
+
+/// Spawn the statistics of every colony and the timeline graphs of the match
+pub fn spawn_stats(
+    parent: &mut ChildBuilder,
+    stats: &GameStats,
+    players: &Players,
+    locale: &Locale,
+    assets: &WorldAssets,
+    window: &Window,
+) {
+    let colonies = players
+        .0
+        .iter()
+        .filter(|p| p.id != ClientId::MAX)
+        .filter_map(|p| stats.colonies.get(&p.id).map(|s| (p, s)))
+        .collect::<Vec<_>>();
+
+    parent
+        .spawn(Node {
+            width: Val::Percent(90.),
+            justify_content: JustifyContent::Center,
+            margin: UiRect::vertical(Val::Percent(1.)),
+            ..default()
+        })
+        .with_children(|parent| {
+            for (i, (player, colony)) in colonies.iter().enumerate() {
+                let bred = colony.bred.values().sum::<u32>();
+                let lost = colony.lost.values().sum::<u32>();
+                let kills = colony.kills.values().sum::<u32>();
+                let traits = colony
+                    .traits
+                    .iter()
+                    .map(|(t, tr)| {
+                        format!(
+                            "{} ({})",
+                            locale.get(&format!("trait.{}", tr.to_snake())),
+                            format_time(*t)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let lines = [
+                    locale.fmt("stats.bred", &[("n", bred.to_string())]),
+                    format_castes(&colony.bred, locale),
+                    locale.fmt("stats.lost", &[("n", lost.to_string())]),
+                    format_castes(&colony.lost, locale),
+                    locale.fmt("stats.kills", &[("n", kills.to_string())]),
+                    format_castes(&colony.kills, locale),
+                    locale.fmt(
+                        "stats.monsters_killed",
+                        &[("n", colony.monsters_killed.to_string())],
+                    ),
+                    locale.fmt(
+                        "stats.leaves",
+                        &[
+                            ("gathered", format!("{:.0}", colony.gathered.leaves)),
+                            ("spent", format!("{:.0}", colony.spent.leaves)),
+                        ],
+                    ),
+                    locale.fmt(
+                        "stats.nutrients",
+                        &[
+                            ("gathered", format!("{:.0}", colony.gathered.nutrients)),
+                            ("spent", format!("{:.0}", colony.spent.nutrients)),
+                        ],
+                    ),
+                    locale.fmt("stats.tiles_dug", &[("n", colony.tiles_dug.to_string())]),
+                    locale.fmt("stats.traits", &[("traits", traits)]),
+                ];
+
+                parent
+                    .spawn((
+                        Node {
+                            width: Val::Percent(100. / colonies.len() as f32),
+                            flex_direction: FlexDirection::Column,
+                            padding: UiRect::all(Val::Percent(0.5)),
+                            margin: UiRect::horizontal(Val::Percent(0.5)),
+                            ..default()
+                        },
+                        BackgroundColor(Color::srgba_u8(88, 57, 39, 200)),
+                        BorderRadius::all(Val::Px(10.)),
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn((
+                            add_text(
+                                colony_name(player, locale),
+                                "bold",
+                                SUBTITLE_TEXT_SIZE,
+                                assets,
+                                window,
+                            ),
+                            TextColor(GRAPH_COLORS[i % GRAPH_COLORS.len()]),
+                        ));
+
+                        for line in lines.into_iter().filter(|l| !l.is_empty()) {
+                            parent.spawn(add_text(line, "medium", LABEL_TEXT_SIZE, assets, window));
+                        }
+                    });
+            }
+        });
+
+    parent
+        .spawn(Node {
+            width: Val::Percent(90.),
+            justify_content: JustifyContent::Center,
+            ..default()
+        })
+        .with_children(|parent| {
+            let duration = stats.time.max(1.);
+            spawn_graph(
+                parent,
+                "stats.population",
+                &colonies,
+                |s| s.population as f32,
+                duration,
+                locale,
+                assets,
+                window,
+            );
+            spawn_graph(
+                parent,
+                "stats.leaves_graph",
+                &colonies,
+                |s| s.resources.leaves,
+                duration,
+                locale,
+                assets,
+                window,
+            );
+            spawn_graph(
+                parent,
+                "stats.nutrients_graph",
+                &colonies,
+                |s| s.resources.nutrients,
+                duration,
+                locale,
+                assets,
+                window,
+            );
+        });
+}
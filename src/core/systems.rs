@@ -1,14 +1,22 @@
+use crate::core::alerts::Alerts;
 use crate::core::ants::components::{Ant, AntCmp};
 use crate::core::ants::events::QueueAntEv;
 use crate::core::ants::selection::{AntSelection, GroupSelection};
 use crate::core::audio::PlayAudioEv;
+use crate::core::camera::CameraControl;
+use crate::core::chat::{ChatHistory, ChatInput};
 use crate::core::constants::MAX_TRAITS;
+use crate::core::desync::DesyncCheck;
 use crate::core::game_settings::GameSettings;
 use crate::core::map::map::Map;
+use crate::core::map::ui::selection::SelectionPanel;
 use crate::core::map::ui::utils::TextSize;
 use crate::core::multiplayer::EntityMap;
 use crate::core::player::Players;
+use crate::core::snapshot::{NetworkStats, SnapshotHistory};
+use crate::core::spectator::Spectator;
 use crate::core::states::GameState;
+use crate::core::stats::GameStats;
 use crate::core::traits::AfterTraitCount;
 use crate::core::utils::scale_duration;
 use bevy::prelude::*;
@@ -23,8 +31,18 @@ pub fn initialize_game(mut commands: Commands, mut game_settings: ResMut<GameSet
     commands.insert_resource(Map::default());
     commands.insert_resource(AntSelection::default());
     commands.insert_resource(GroupSelection::default());
+    commands.insert_resource(SelectionPanel::default());
     commands.insert_resource(EntityMap::default());
     commands.insert_resource(AfterTraitCount::default());
+    commands.insert_resource(SnapshotHistory::default());
+    commands.insert_resource(NetworkStats::default());
+    commands.insert_resource(DesyncCheck::default());
+    commands.insert_resource(ChatHistory::default());
+    commands.insert_resource(ChatInput::default());
+    commands.insert_resource(GameStats::default());
+    commands.insert_resource(Alerts::default());
+    commands.insert_resource(CameraControl::default());
+    commands.remove_resource::<Spectator>();
 
     // Reset in-game settings
     game_settings.reset();
@@ -48,14 +66,15 @@ pub fn check_trait_timer(
     players: Res<Players>,
     time: Res<Time>,
 ) {
-    let player = players.main();
-
     // Only the host starts the trait selection
-    if player.id == 0 {
+    if players.main_id() == 0 {
         let time = scale_duration(time.delta(), game_settings.speed);
         game_settings.trait_timer.tick(time);
 
-        if game_settings.trait_timer.finished() && player.traits.len() < MAX_TRAITS {
+        // Use the colony with most traits since the host can be a spectator
+        let n_traits = players.0.iter().map(|p| p.traits.len()).max().unwrap_or(0);
+
+        if game_settings.trait_timer.finished() && n_traits < MAX_TRAITS {
             play_audio_ev.send(PlayAudioEv::new("message"));
             next_game_state.set(GameState::TraitSelection);
         }
@@ -78,7 +97,7 @@ pub fn npc_buy_ants(mut queue_ant_ev: EventWriter<QueueAntEv>, mut players: ResM
     for player in players
         .0
         .iter_mut()
-        .filter(|p| !p.is_human() && p.id != ClientId::MAX)
+        .filter(|p| (!p.is_human() || p.disconnected) && p.id != ClientId::MAX)
     {
         // Select ants that can be bought
         let ants = Ant::iter()
@@ -2,12 +2,17 @@ use crate::core::ants::components::{Action, Ant, AntCmp, Corpse};
 use crate::core::ants::events::SpawnAntEv;
 use crate::core::ants::utils::transform_ant;
 use crate::core::audio::PlayAudioEv;
+use crate::core::dedicated::DedicatedServer;
 use crate::core::game_settings::{GameMode, GameSettings};
+use crate::core::menu::lobby::Lobby;
+use crate::core::network::{ClientMessage, ClientSendMessage};
 use crate::core::player::Players;
 use crate::core::resources::Resources;
 use crate::core::states::GameState;
+use crate::core::stats::GameStats;
+use crate::utils::NameFromEnum;
 use bevy::prelude::*;
-use bevy_renet::renet::RenetServer;
+use bevy_renet::renet::{ClientId, RenetClient, RenetServer};
 use rand::prelude::IteratorRandom;
 use rand::{rng, Rng};
 use serde::{Deserialize, Serialize};
@@ -35,6 +40,7 @@ pub enum Trait {
     Metamorfosis,
     Necromancer,
     ScorpionKiller,
+    Spitter,
     SuddenArmy,
     SuperQueen,
     TermiteKiller,
@@ -44,200 +50,67 @@ pub enum Trait {
     WaspKiller,
 }
 
+impl Trait {
+    /// Factor applied to the damage dealt against the given ant type
+    pub fn damage_factor(&self, kind: &Ant) -> f32 {
+        match self {
+            Trait::ScorpionKiller if kind.is_scorpion() => 2.,
+            Trait::TermiteKiller if kind.is_termite() => 2.,
+            Trait::WaspKiller if *kind == Ant::Wasp => 2.,
+            _ => 1.,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct TraitCmp {
     pub kind: Trait,
     pub image: String,
-    pub description: String,
 }
 
 impl TraitCmp {
     pub fn new(kind: &Trait) -> Self {
-        match kind {
-            Trait::Alate => Self {
-                kind: Trait::Alate,
-                image: "alate".to_string(),
-                description: "\
-                    Unlocks the alate (flying) ants. Alates are incredibly fast ants when \
-                    attacking. They have high damage but low health, making them a good choice \
-                    in the offence but poor in defense."
-                    .to_string(),
-            },
-            Trait::Breeding => Self {
-                kind: Trait::Breeding,
-                image: "eggs".to_string(),
-                description: "\
-                    Eggs hatch twice as fast and have double the health. Enhance your colony's \
-                    growth by increasing the larva production rate."
-                    .to_string(),
-            },
-            Trait::Corpses => Self {
-                kind: Trait::Corpses,
-                image: "corpses".to_string(),
-                description: "\
-                    Corpses of your ants lie twice as long on the ground, allowing more nutrient \
-                    collection and the healing of your ants."
-                    .to_string(),
-            },
-            Trait::DoubleQueen => Self {
-                kind: Trait::DoubleQueen,
-                image: "double-queen".to_string(),
-                description: "\
-                    Your colony gains an extra queen. The queens cooperate, increasing egg \
-                    production and colony growth. Both queens need to die to lose the game."
-                    .to_string(),
-            },
-            Trait::EnhancedSoldiers => Self {
-                kind: Trait::EnhancedSoldiers,
-                image: "soldiers".to_string(),
-                description: "\
-                    Soldier ants increase their damage and speed. Use this trait to create a \
-                    powerful army."
-                    .to_string(),
-            },
-            Trait::EnhancedWarriors => Self {
-                kind: Trait::EnhancedWarriors,
-                image: "battle".to_string(),
-                description: "\
-                    Warriors ants increase their damage and health. Use this trait to create \
-                    a powerful army."
-                    .to_string(),
-            },
-            Trait::Harvest => Self {
-                kind: Trait::Harvest,
-                image: "harvest".to_string(),
-                description: "\
-                    Your workers harvest resources twice as fast. Resources are the lifeblood \
-                    of the colony. More leaves and nutrients means more and stronger ants."
-                    .to_string(),
-            },
-            Trait::Haste => Self {
-                kind: Trait::Haste,
-                image: "haste".to_string(),
-                description: "\
-                    All your ants move 20% faster. Speed is the key to productivity. Faster \
-                    ants means faster food collection and reaching the enemy earlier."
-                    .to_string(),
-            },
-            Trait::HealingQueen => Self {
-                kind: Trait::HealingQueen,
-                image: "healing".to_string(),
-                description: "\
-                    Your queen can heal her wounds. If not under attack, the queen regenerates \
-                    over time remaining idle. The game is lost if the queen dies, so a healthy \
-                    queen is paramount."
-                    .to_string(),
-            },
-            Trait::Influx => Self {
-                kind: Trait::Influx,
-                image: "influx".to_string(),
-                description: "\
-                    Immediately receive a large amount of leaves and nutrients."
-                    .to_string(),
-            },
-            Trait::Mastodon => Self {
-                kind: Trait::Mastodon,
-                image: "mastodon".to_string(),
-                description: "\
-                    Unlocks the mastodon ants. Mastodons are big ants known for their powerful \
-                    jaws. They have low damage but are very healthy, making them ideal units to \
-                    defend narrow tunnels and slow down the enemy's advance."
-                    .to_string(),
-            },
-            Trait::MegaColony => Self {
-                kind: Trait::MegaColony,
-                image: "megacolony".to_string(),
-                description: "\
-                    All your ants cost 10% less food to produce. Quickly become the largest \
-                    colony around and overcome your enemies by the sheer numbers."
-                    .to_string(),
-            },
-            Trait::Metamorfosis => Self {
-                kind: Trait::Metamorfosis,
-                image: "metamorfosis".to_string(),
-                description: "\
-                    All your workers turn into soldiers. This is a one-time transformation \
-                    for the current workers. Queued ants remain the same."
-                    .to_string(),
-            },
-            Trait::Necromancer => Self {
-                kind: Trait::Necromancer,
-                image: "necromancer".to_string(),
-                description: "\
-                    All the current corpses of your ants come back to live with full health."
-                    .to_string(),
-            },
-            Trait::ScorpionKiller => Self {
-                kind: Trait::ScorpionKiller,
-                image: "scorpion".to_string(),
-                description: "\
-                    All your ants have double the damage against scorpions. Scorpions are \
-                    dangerous enemies, often encountered by excavators when digging tunnels."
-                    .to_string(),
-            },
-            Trait::SuddenArmy => Self {
-                kind: Trait::SuddenArmy,
-                image: "sudden-army".to_string(),
-                description: "\
-                    A random number of soldier and warrior ants immediately spawn around your \
-                    queen. Surprise your enemies with a sudden army."
-                    .to_string(),
-            },
-            Trait::SuperQueen => Self {
-                kind: Trait::SuperQueen,
-                image: "super-queen".to_string(),
-                description: "\
-                    The queen increases in health and strength, but walks slower. If you \
-                    have more than one queen, they all gain the bonuses."
-                    .to_string(),
-            },
-            Trait::TermiteKiller => Self {
-                kind: Trait::TermiteKiller,
-                image: "termites".to_string(),
-                description: "\
-                    All your ants have double the damage against termites. Termites attack \
-                    in groups."
-                    .to_string(),
-            },
-            Trait::Tunneling => Self {
-                kind: Trait::Tunneling,
-                image: "tunneling".to_string(),
-                description: "\
-                    Excavator ants dig twice as fast. A rapid expansion of the nest means \
-                    discovering more food sources, but also encountering enemies faster."
-                    .to_string(),
-            },
-            Trait::WanderingQueen => Self {
-                kind: Trait::WanderingQueen,
-                image: "wandering".to_string(),
-                description: "\
-                    The queen is able to move outside the base. It can lay eggs anywhere and \
-                    her default behavior becomes wandering around the map. The player can give \
-                    commands to the queen."
-                    .to_string(),
-            },
-            Trait::Warlike => Self {
-                kind: Trait::Warlike,
-                image: "workers".to_string(),
-                description: "\
-                    Your workers become stronger, gaining twice the health and damage, but \
-                    reducing their harvesting speed by half."
-                    .to_string(),
-            },
-            Trait::WaspKiller => Self {
-                kind: Trait::WaspKiller,
-                image: "wasp".to_string(),
-                description: "\
-                    All your ants have double the damage against wasps. Wasps sometimes enter \
-                    the tunnels through chambers with holes that lead to the surface."
-                    .to_string(),
-            },
+        let image = match kind {
+            Trait::Alate => "alate",
+            Trait::Breeding => "eggs",
+            Trait::Corpses => "corpses",
+            Trait::DoubleQueen => "double-queen",
+            Trait::EnhancedSoldiers => "soldiers",
+            Trait::EnhancedWarriors => "battle",
+            Trait::Harvest => "harvest",
+            Trait::Haste => "haste",
+            Trait::HealingQueen => "healing",
+            Trait::Influx => "influx",
+            Trait::Mastodon => "mastodon",
+            Trait::MegaColony => "megacolony",
+            Trait::Metamorfosis => "metamorfosis",
+            Trait::Necromancer => "necromancer",
+            Trait::ScorpionKiller => "scorpion",
+            Trait::Spitter => "defense",
+            Trait::SuddenArmy => "sudden-army",
+            Trait::SuperQueen => "super-queen",
+            Trait::TermiteKiller => "termites",
+            Trait::Tunneling => "tunneling",
+            Trait::WanderingQueen => "wandering",
+            Trait::Warlike => "workers",
+            Trait::WaspKiller => "wasp",
+        };
+
+        Self {
+            kind: *kind,
+            image: image.to_string(),
         }
     }
+
+    /// Id of the description in the string tables
+    pub fn description_id(&self) -> String {
+        format!("trait.{}.description", self.kind.to_snake())
+    }
 }
 
 #[derive(Event)]
 pub struct TraitSelectedEv {
+    pub id: ClientId,
     pub selected: Trait,
 }
 
@@ -246,17 +119,36 @@ pub fn select_trait_event(
     mut ant_q: Query<(Entity, &mut Transform, &mut AntCmp)>,
     mut trait_selected_ev: EventReader<TraitSelectedEv>,
     mut spawn_ant_ev: EventWriter<SpawnAntEv>,
+    mut client_send_message: EventWriter<ClientSendMessage>,
     mut play_audio_ev: EventWriter<PlayAudioEv>,
     game_settings: Res<GameSettings>,
     mut players: ResMut<Players>,
+    mut stats: ResMut<GameStats>,
+    client: Option<Res<RenetClient>>,
+    dedicated: Option<Res<DedicatedServer>>,
     mut next_game_state: ResMut<NextState<GameState>>,
 ) {
     for ev in trait_selected_ev.read() {
-        play_audio_ev.send(PlayAudioEv::new("button"));
-
         let id = players.main_id();
-        for player in players.0.iter_mut().filter(|p| p.id == id || p.is_npc()) {
-            let selected = if player.is_human() {
+        if ev.id == id {
+            play_audio_ev.send(PlayAudioEv::new("button"));
+        }
+
+        if client.is_some() {
+            // Clients only send their choice, the host applies the trait
+            players.main_mut().traits.push(ev.selected);
+            client_send_message.send(ClientSendMessage {
+                message: ClientMessage::ChooseTrait(ev.selected),
+            });
+            next_game_state.set(GameState::AfterTraitSelection);
+            continue;
+        }
+
+        // The host's choice also selects the traits of the computer-controlled colonies
+        for player in players.0.iter_mut().filter(|p| {
+            (p.id == ev.id && (p.id != id || dedicated.is_none())) || (ev.id == id && p.is_ai())
+        }) {
+            let selected = if player.id == ev.id {
                 ev.selected.clone()
             } else {
                 // For NPCs, select a random trait they don't have
@@ -268,6 +160,9 @@ pub fn select_trait_event(
 
             player.traits.push(selected);
 
+            let time = stats.time;
+            stats.colony(player.id).traits.push((time, selected));
+
             match selected {
                 Trait::DoubleQueen => {
                     spawn_ant_ev.send(SpawnAntEv {
@@ -369,23 +264,40 @@ pub fn select_trait_event(
             }
         }
 
-        if game_settings.game_mode == GameMode::SinglePlayer {
-            next_game_state.set(GameState::Running);
-        } else {
-            next_game_state.set(GameState::AfterTraitSelection);
+        // Traits chosen by clients don't change the host's state
+        if ev.id == id {
+            if game_settings.game_mode == GameMode::SinglePlayer {
+                next_game_state.set(GameState::Running);
+            } else {
+                next_game_state.set(GameState::AfterTraitSelection);
+            }
         }
     }
 }
 
+pub fn dedicated_trait_selection(mut trait_selected_ev: EventWriter<TraitSelectedEv>) {
+    // The dedicated server only selects the traits of the npcs
+    trait_selected_ev.send(TraitSelectedEv {
+        id: 0,
+        selected: Trait::iter().choose(&mut rng()).unwrap(),
+    });
+}
+
 pub fn after_trait_check(
     server: Res<RenetServer>,
+    lobby: Res<Lobby>,
     mut trait_count: ResMut<AfterTraitCount>,
     game_state: Res<State<GameState>>,
     mut next_game_state: ResMut<NextState<GameState>>,
 ) {
-    if *game_state.get() == GameState::AfterTraitSelection
-        && trait_count.0 == server.clients_id().len()
-    {
+    // Observers don't choose traits
+    let n_players = server
+        .clients_id()
+        .iter()
+        .filter(|id| !lobby.observers.contains(id))
+        .count();
+
+    if *game_state.get() == GameState::AfterTraitSelection && trait_count.0 == n_players {
         trait_count.0 = 0;
         next_game_state.set(GameState::Running);
     }
@@ -0,0 +1,4 @@
+pub mod core;
+pub mod utils;
+
+pub const TITLE: &str = "Marabunta";
@@ -1,9 +1,5 @@
 #![windows_subsystem = "windows"]
 
-mod core;
-mod utils;
-
-use crate::core::GamePlugin;
 use bevy::asset::AssetMetaCheck;
 use bevy::prelude::NonSend;
 use bevy::prelude::*;
@@ -12,10 +8,10 @@ use bevy::winit::WinitWindows;
 use bevy_kira_audio::prelude::*;
 use bevy_renet::netcode::{NetcodeClientPlugin, NetcodeServerPlugin};
 use bevy_renet::{RenetClientPlugin, RenetServerPlugin};
+use marabunta::core::GamePlugin;
+use marabunta::TITLE;
 use winit::window::Icon;
 
-pub const TITLE: &str = "Marabunta";
-
 fn main() {
     let mut app = App::new();
 
@@ -42,9 +38,13 @@ fn main() {
                 meta_check: AssetMetaCheck::Never,
                 ..default()
             }),
-    )
+    );
+
+    #[cfg(target_os = "windows")]
+    app.add_systems(Startup, set_window_icon);
+
     // Networking: systems are disabled until server/client resource is added
-    .add_plugins((
+    app.add_plugins((
         RenetServerPlugin,
         NetcodeServerPlugin,
         RenetClientPlugin,
@@ -52,9 +52,6 @@ fn main() {
     ))
     .add_plugins((AudioPlugin, GamePlugin));
 
-    #[cfg(target_os = "windows")]
-    app.add_systems(Startup, set_window_icon);
-
     app.run();
 }
 
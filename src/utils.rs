@@ -35,7 +35,6 @@ fn extract_variant_name(text: String) -> String {
 pub trait NameFromEnum {
     fn to_name(&self) -> String;
     fn to_lowername(&self) -> String;
-    fn to_title(&self) -> String;
     fn to_snake(&self) -> String;
 }
 
@@ -51,15 +50,6 @@ impl<T: Debug> NameFromEnum for T {
         self.to_name().to_lowercase()
     }
 
-    fn to_title(&self) -> String {
-        let mut name = self.to_lowername();
-
-        // Capitalize only the first letter
-        name.replace_range(0..1, &name[0..1].to_uppercase());
-
-        name
-    }
-
     fn to_snake(&self) -> String {
         let re = Regex::new(r"([a-z])([A-Z])").unwrap();
 